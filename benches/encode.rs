@@ -0,0 +1,47 @@
+use criterion::{Criterion, criterion_group, criterion_main};
+use std::hint::black_box;
+
+use rndc::RndcClient;
+
+fn addzone_command(notify_targets: usize) -> String {
+    let also_notify = (0..notify_targets)
+        .map(|i| format!("10.{}.{}.{};", (i >> 16) & 0xff, (i >> 8) & 0xff, i & 0xff))
+        .collect::<Vec<_>>()
+        .join(" ");
+    format!(
+        "addzone example.com {{ type primary; file \"example.com.db\"; \
+         allow-update {{ key ddns-key; }}; also-notify {{ {} }}; }};",
+        also_notify
+    )
+}
+
+fn bench_encode(c: &mut Criterion) {
+    let client = RndcClient::new("127.0.0.1:953", "sha256", "YmluZGl6cg==").unwrap();
+    let mut buf = Vec::new();
+
+    for targets in [10, 1_000, 10_000] {
+        let command = addzone_command(targets);
+        c.bench_function(&format!("encode_addzone_{}", targets), |b| {
+            b.iter(|| {
+                client
+                    .encode_command_into(black_box(&command), &mut buf)
+                    .unwrap();
+                black_box(&buf);
+            })
+        });
+    }
+
+    let command = addzone_command(1_000);
+    let md5_client = RndcClient::new("127.0.0.1:953", "md5", "YmluZGl6cg==").unwrap();
+    c.bench_function("encode_addzone_1000_md5", |b| {
+        b.iter(|| {
+            md5_client
+                .encode_command_into(black_box(&command), &mut buf)
+                .unwrap();
+            black_box(&buf);
+        })
+    });
+}
+
+criterion_group!(benches, bench_encode);
+criterion_main!(benches);
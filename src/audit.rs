@@ -0,0 +1,290 @@
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::RndcError;
+use crate::internal::args;
+use crate::internal::log_file::LogFile;
+use crate::{RndcClient, RndcResult};
+
+/// One control command as seen by an `AuditObserver`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditEvent {
+    /// Address of the server the command was sent to.
+    pub server: String,
+    /// The command verb, e.g. `reload`.
+    pub command: String,
+    pub args: Vec<String>,
+    /// Name of the key the command was signed with, if the handshake got
+    /// that far.
+    pub key_name: Option<String>,
+    /// The `_ser` value of the signed command, if it was sent.
+    pub serial: Option<u32>,
+    pub started: SystemTime,
+    pub finished: SystemTime,
+    /// The numeric `result` returned by named, `0` on success.
+    pub result_code: Option<u32>,
+    /// The transport error, or the `err` text returned by named.
+    pub error: Option<String>,
+    /// The command was only built and signed by a dry-run client.
+    pub dry_run: bool,
+}
+
+impl AuditEvent {
+    pub(crate) fn begin(server: &str, command: &str) -> Self {
+        let mut words = args::split_command(command).into_iter();
+        let now = SystemTime::now();
+
+        AuditEvent {
+            server: server.to_string(),
+            command: words.next().unwrap_or_default(),
+            args: words.collect(),
+            key_name: None,
+            serial: None,
+            started: now,
+            finished: now,
+            result_code: None,
+            error: None,
+            dry_run: false,
+        }
+    }
+
+    /// Record the outcome of the command, and its timing on the result.
+    pub(crate) fn finish(&mut self, res: &mut Result<RndcResult, RndcError>) {
+        self.finished = SystemTime::now();
+        match res {
+            Ok(res) => {
+                res.started = self.started;
+                res.elapsed = self
+                    .finished
+                    .duration_since(self.started)
+                    .unwrap_or_default();
+                self.result_code = res.result_code;
+                self.error = res.err.clone();
+            }
+            Err(e) => self.error = Some(e.to_string()),
+        }
+    }
+
+    /// The event as a single-line JSON object, with times in milliseconds
+    /// since the Unix epoch.
+    pub fn to_json(&self) -> String {
+        let mut out = String::from("{");
+        out.push_str(&format!("\"server\":{}", json_string(&self.server)));
+        out.push_str(&format!(",\"command\":{}", json_string(&self.command)));
+        let args: Vec<String> = self.args.iter().map(|a| json_string(a)).collect();
+        out.push_str(&format!(",\"args\":[{}]", args.join(",")));
+        out.push_str(&format!(
+            ",\"key_name\":{}",
+            json_option(self.key_name.as_deref().map(json_string))
+        ));
+        out.push_str(&format!(
+            ",\"serial\":{}",
+            json_option(self.serial.map(|s| s.to_string()))
+        ));
+        out.push_str(&format!(",\"start_ms\":{}", unix_millis(self.started)));
+        out.push_str(&format!(",\"end_ms\":{}", unix_millis(self.finished)));
+        out.push_str(&format!(
+            ",\"result_code\":{}",
+            json_option(self.result_code.map(|c| c.to_string()))
+        ));
+        out.push_str(&format!(
+            ",\"error\":{}",
+            json_option(self.error.as_deref().map(json_string))
+        ));
+        out.push_str(&format!(",\"dry_run\":{}", self.dry_run));
+        out.push('}');
+        out
+    }
+}
+
+/// Receives an `AuditEvent` after every command sent by a client, its
+/// sessions and pools, whether it succeeded or not.
+pub trait AuditObserver: Send + Sync + fmt::Debug {
+    fn on_command(&self, event: &AuditEvent);
+}
+
+/// Appends every event as one JSON object per line to a file.
+///
+/// Each line is flushed as it is written. A failed write cannot fail the
+/// command that was audited, so the first one is kept for `write_error`.
+#[derive(Debug)]
+pub struct JsonLinesAuditSink {
+    file: LogFile,
+}
+
+impl JsonLinesAuditSink {
+    /// Open `path` for appending, creating it if needed.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, RndcError> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path.as_ref())
+            .map_err(|e| {
+                RndcError::InvalidArgument(format!(
+                    "Failed to open audit log {}: {}",
+                    path.as_ref().display(),
+                    e
+                ))
+            })?;
+
+        Ok(JsonLinesAuditSink {
+            file: LogFile::new(file, "audit log"),
+        })
+    }
+
+    /// The first error writing the log, if any.
+    pub fn write_error(&self) -> Option<&io::Error> {
+        self.file.error()
+    }
+}
+
+impl AuditObserver for JsonLinesAuditSink {
+    fn on_command(&self, event: &AuditEvent) {
+        let mut line = event.to_json();
+        line.push('\n');
+
+        self.file.append(line.as_bytes());
+    }
+}
+
+impl RndcClient {
+    /// Add an observer that is notified of every command sent through this
+    /// client and the sessions and pools built from it.
+    pub fn with_observer(mut self, observer: Arc<dyn AuditObserver>) -> Self {
+        self.observers.push(observer);
+        self
+    }
+
+    pub(crate) fn notify(&self, event: &AuditEvent) {
+        for observer in &self.observers {
+            observer.on_command(event);
+        }
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn json_option(value: Option<String>) -> String {
+    value.unwrap_or_else(|| "null".to_string())
+}
+
+fn unix_millis(time: SystemTime) -> u128 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RndcAlg;
+    use crate::internal::test_server::TestServer;
+    use std::sync::Mutex;
+
+    #[derive(Debug, Default)]
+    struct Recorder(Mutex<Vec<AuditEvent>>);
+
+    impl AuditObserver for Recorder {
+        fn on_command(&self, event: &AuditEvent) {
+            self.0.lock().unwrap().push(event.clone());
+        }
+    }
+
+    #[test]
+    fn test_observer_receives_events() {
+        let server = TestServer::start(RndcAlg::SHA256, None);
+        let recorder = Arc::new(Recorder::default());
+        let client = server.client("sha256").with_observer(recorder.clone());
+
+        client.rndc_command("reload example.com").unwrap();
+        client.session().unwrap().rndc_command("status").unwrap();
+
+        let events = recorder.0.lock().unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].server, server.addr.to_string());
+        assert_eq!(events[0].command, "reload");
+        assert_eq!(events[0].args, ["example.com"]);
+        assert_eq!(events[0].key_name.as_deref(), Some("rndc-key"));
+        assert!(events[0].serial.is_some());
+        assert_eq!(events[0].result_code, Some(0));
+        assert_eq!(events[0].error, None);
+        assert!(events[0].finished >= events[0].started);
+        assert_eq!(events[1].command, "status");
+    }
+
+    #[test]
+    fn test_observer_sees_failures() {
+        let server = TestServer::start(RndcAlg::SHA256, None);
+        let recorder = Arc::new(Recorder::default());
+        let client = server.client("md5").with_observer(recorder.clone());
+
+        assert!(client.rndc_command("status").is_err());
+
+        let events = recorder.0.lock().unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].key_name, None);
+        assert_eq!(events[0].serial, None);
+        assert!(events[0].error.is_some());
+    }
+
+    #[test]
+    fn test_json_lines_sink() {
+        let path = std::env::temp_dir().join(format!("rndc-{}-audit.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let server = TestServer::start(RndcAlg::SHA256, None);
+        let sink = Arc::new(JsonLinesAuditSink::open(&path).unwrap());
+        let client = server.client("sha256").with_observer(sink);
+        client.rndc_command("flushname \"my name\"").unwrap();
+        client.rndc_command("status").unwrap();
+
+        let log = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = log.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with(&format!(
+            "{{\"server\":\"{}\",\"command\":\"flushname\",\"args\":[\"my name\"],\"key_name\":\"rndc-key\",\"serial\":",
+            server.addr
+        )));
+        assert!(lines[0].ends_with(",\"result_code\":0,\"error\":null,\"dry_run\":false}"));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_json_lines_sink_keeps_write_error() {
+        let server = TestServer::start(RndcAlg::SHA256, None);
+        let sink = Arc::new(JsonLinesAuditSink::open("/dev/full").unwrap());
+        let client = server.client("sha256").with_observer(sink.clone());
+
+        assert!(sink.write_error().is_none());
+        assert!(client.rndc_command("status").unwrap().result);
+        let error = sink.write_error().unwrap();
+        assert!(error.to_string().starts_with("failed to write audit log: "));
+    }
+
+    #[test]
+    fn test_json_string_escapes() {
+        assert_eq!(json_string("a\"b\\c\nd\u{1}"), "\"a\\\"b\\\\c\\nd\\u0001\"");
+    }
+}
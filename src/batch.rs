@@ -0,0 +1,495 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::error::RndcError;
+use crate::internal::args::{command_line, split_command};
+use crate::{RndcClient, RndcResult};
+
+/// One command of a batch script and the command that reverts it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchStep {
+    /// Line of the script the command was read from, starting at 1.
+    pub line: usize,
+    pub command: String,
+    pub undo: Option<String>,
+}
+
+/// A parsed batch script.
+///
+/// The script has one rndc command per line. Blank lines and lines starting
+/// with `#` are ignored. `set NAME=value` defines a variable, which later
+/// lines use as `$NAME` or `${NAME}`; variables passed to `parse` take
+/// precedence over the script's own. A line `undo: <command>` right after a
+/// step sets the command that reverts it, and `undo: none` clears it.
+/// Without one, `addzone` is undone with `delzone`, and `freeze` and `thaw`
+/// with each other. An `addzone` with a comment or other unexpected words
+/// before its configuration block gets no undo.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BatchScript {
+    pub steps: Vec<BatchStep>,
+}
+
+impl BatchScript {
+    pub fn parse(text: &str, vars: &HashMap<String, String>) -> Result<Self, RndcError> {
+        let mut script_vars: HashMap<String, String> = HashMap::new();
+        let mut steps: Vec<BatchStep> = Vec::new();
+
+        for (index, raw) in text.lines().enumerate() {
+            let line = index + 1;
+            let trimmed = raw.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            let err = |msg: String| RndcError::InvalidArgument(format!("line {}: {}", line, msg));
+
+            if let Some(definition) = trimmed.strip_prefix("set ") {
+                let Some((name, value)) = definition.split_once('=') else {
+                    return Err(err("expected set NAME=value".to_string()));
+                };
+                let name = name.trim();
+                if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                    return Err(err(format!("invalid variable name {:?}", name)));
+                }
+                let value = substitute(value.trim(), vars, &script_vars).map_err(err)?;
+                script_vars.insert(name.to_string(), value);
+                continue;
+            }
+
+            let expanded = substitute(trimmed, vars, &script_vars).map_err(err)?;
+            if let Some(undo) = expanded.strip_prefix("undo:") {
+                let Some(step) = steps.last_mut() else {
+                    return Err(err("undo: without a preceding command".to_string()));
+                };
+                step.undo = match undo.trim() {
+                    "none" => None,
+                    "" => return Err(err("empty undo: command".to_string())),
+                    undo => Some(undo.to_string()),
+                };
+                continue;
+            }
+
+            steps.push(BatchStep {
+                line,
+                undo: inverse(&expanded)?,
+                command: expanded,
+            });
+        }
+
+        Ok(BatchScript { steps })
+    }
+}
+
+/// Replace `$NAME` and `${NAME}` with their values.
+fn substitute(
+    text: &str,
+    vars: &HashMap<String, String>,
+    script_vars: &HashMap<String, String>,
+) -> Result<String, String> {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(pos) = rest.find('$') {
+        out.push_str(&rest[..pos]);
+        rest = &rest[pos + 1..];
+
+        let (name, after) = if let Some(braced) = rest.strip_prefix('{') {
+            let end = braced
+                .find('}')
+                .ok_or_else(|| "unterminated ${".to_string())?;
+            (&braced[..end], &braced[end + 1..])
+        } else {
+            let end = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            (&rest[..end], &rest[end..])
+        };
+        if name.is_empty() {
+            return Err("$ without a variable name".to_string());
+        }
+        let value = vars
+            .get(name)
+            .or_else(|| script_vars.get(name))
+            .ok_or_else(|| format!("undefined variable {}", name))?;
+        out.push_str(value);
+        rest = after;
+    }
+    out.push_str(rest);
+
+    Ok(out)
+}
+
+/// The command that reverts `command`, for the commands that have one.
+fn inverse(command: &str) -> Result<Option<String>, RndcError> {
+    let words = split_command(command);
+    let Some((verb, args)) = words.split_first() else {
+        return Ok(None);
+    };
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    match verb.to_ascii_lowercase().as_str() {
+        "addzone" => match addzone_target(&args) {
+            Some(target) => Ok(Some(command_line("delzone", &target)?)),
+            None => Ok(None),
+        },
+        "freeze" => Ok(Some(command_line("thaw", &args)?)),
+        "thaw" => Ok(Some(command_line("freeze", &args)?)),
+        _ => Ok(None),
+    }
+}
+
+/// The `zone [class [view]]` words of an addzone before its configuration
+/// block, which may be glued to the last of them. `None` when anything else
+/// comes first, such as a comment, since named would read the words
+/// differently.
+fn addzone_target<'a>(args: &[&'a str]) -> Option<Vec<&'a str>> {
+    let mut target = Vec::new();
+    for arg in args {
+        let (word, block) = match arg.split_once('{') {
+            Some((word, _)) => (word, true),
+            None => (*arg, false),
+        };
+        if word.contains(['#', ';', '}', '(', ')', '\\'])
+            || word.contains("/*")
+            || word.contains("//")
+        {
+            return None;
+        }
+        if !word.is_empty() {
+            if target.len() == 3 {
+                return None;
+            }
+            target.push(word);
+        }
+        if block {
+            return (!target.is_empty()).then_some(target);
+        }
+    }
+    None
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BatchOptions {
+    /// Stop at the first failing step and skip the rest.
+    pub stop_on_error: bool,
+    /// On failure, stop and run the undo commands of the completed steps in
+    /// reverse order.
+    pub rollback: bool,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(tag = "status", content = "detail", rename_all = "lowercase")
+)]
+pub enum StepOutcome {
+    Succeeded(RndcResult),
+    /// The command failed, with named's `err` text or the client error.
+    Failed(String),
+    Skipped,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct StepReport {
+    pub line: usize,
+    pub command: String,
+    pub outcome: StepOutcome,
+    /// Serialized as `elapsed_ms`.
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            rename = "elapsed_ms",
+            serialize_with = "crate::internal::utils::serialize_millis"
+        )
+    )]
+    pub elapsed: Duration,
+}
+
+impl StepReport {
+    pub fn succeeded(&self) -> bool {
+        matches!(self.outcome, StepOutcome::Succeeded(_))
+    }
+}
+
+/// What happened to every step of a batch, and to the undo commands run
+/// when it was rolled back.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct BatchReport {
+    pub steps: Vec<StepReport>,
+    pub rollback: Vec<StepReport>,
+}
+
+impl BatchReport {
+    /// Whether every step succeeded.
+    pub fn succeeded(&self) -> bool {
+        self.steps.iter().all(StepReport::succeeded)
+    }
+}
+
+impl fmt::Display for BatchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn write_step(f: &mut fmt::Formatter<'_>, step: &StepReport) -> fmt::Result {
+            let (status, detail) = match &step.outcome {
+                StepOutcome::Succeeded(_) => ("ok", String::new()),
+                StepOutcome::Failed(e) => ("FAILED", format!(": {}", e)),
+                StepOutcome::Skipped => ("skipped", String::new()),
+            };
+            writeln!(
+                f,
+                "line {:<4} {:<8} {}{} ({} ms)",
+                step.line,
+                status,
+                step.command,
+                detail,
+                step.elapsed.as_millis()
+            )
+        }
+
+        for step in &self.steps {
+            write_step(f, step)?;
+        }
+        if !self.rollback.is_empty() {
+            writeln!(f, "rollback:")?;
+            for step in &self.rollback {
+                write_step(f, step)?;
+            }
+        }
+
+        let count = |outcome: fn(&StepOutcome) -> bool| {
+            self.steps.iter().filter(|s| outcome(&s.outcome)).count()
+        };
+        write!(
+            f,
+            "{} steps: {} succeeded, {} failed, {} skipped",
+            self.steps.len(),
+            count(|o| matches!(o, StepOutcome::Succeeded(_))),
+            count(|o| matches!(o, StepOutcome::Failed(_))),
+            count(|o| matches!(o, StepOutcome::Skipped)),
+        )?;
+        if !self.rollback.is_empty() {
+            write!(
+                f,
+                "; {} of {} undone",
+                self.rollback.iter().filter(|s| s.succeeded()).count(),
+                self.rollback.len()
+            )?;
+        }
+        writeln!(f)
+    }
+}
+
+impl RndcClient {
+    /// Run the steps of `script` in order and report the outcome of each.
+    ///
+    /// On a dry-run client every step is recorded and succeeds, so nothing
+    /// is rolled back; `dry_run_log` lists the commands in order.
+    pub fn run_batch(&self, script: &BatchScript, options: BatchOptions) -> BatchReport {
+        let mut report = BatchReport::default();
+        let mut done: Vec<&BatchStep> = Vec::new();
+        let mut failed = false;
+
+        for step in &script.steps {
+            if failed && (options.stop_on_error || options.rollback) {
+                report.steps.push(StepReport {
+                    line: step.line,
+                    command: step.command.clone(),
+                    outcome: StepOutcome::Skipped,
+                    elapsed: Duration::ZERO,
+                });
+                continue;
+            }
+
+            let step_report = self.run_step(step.line, &step.command);
+            if step_report.succeeded() {
+                done.push(step);
+            } else {
+                failed = true;
+            }
+            report.steps.push(step_report);
+        }
+
+        if failed && options.rollback {
+            for step in done.iter().rev() {
+                if let Some(undo) = &step.undo {
+                    report.rollback.push(self.run_step(step.line, undo));
+                }
+            }
+        }
+
+        report
+    }
+
+    fn run_step(&self, line: usize, command: &str) -> StepReport {
+        let started = Instant::now();
+        let outcome = match self.rndc_command(command) {
+            Ok(res) if res.result => StepOutcome::Succeeded(res),
+            Ok(res) => StepOutcome::Failed(res.err.unwrap_or_else(|| "command failed".to_string())),
+            Err(e) => StepOutcome::Failed(e.to_string()),
+        };
+
+        StepReport {
+            line,
+            command: command.to_string(),
+            outcome,
+            elapsed: started.elapsed(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal::test_server::TestServer;
+    use crate::{CommandPolicy, RndcAlg};
+
+    const SCRIPT: &str = r#"
+# migrate example zones
+set VIEW=internal
+set ZONE=example.com
+
+addzone $ZONE IN ${VIEW} { type primary; file "$ZONE.db"; };
+freeze $ZONE IN $VIEW
+reload $ZONE IN $VIEW
+undo: reload $ZONE IN $VIEW
+thaw $ZONE IN $VIEW
+undo: none
+"#;
+
+    #[test]
+    fn test_parse_script() {
+        let script = BatchScript::parse(SCRIPT, &HashMap::new()).unwrap();
+        let steps: Vec<(usize, &str, Option<&str>)> = script
+            .steps
+            .iter()
+            .map(|s| (s.line, s.command.as_str(), s.undo.as_deref()))
+            .collect();
+
+        assert_eq!(
+            steps,
+            [
+                (
+                    6,
+                    "addzone example.com IN internal { type primary; file \"example.com.db\"; };",
+                    Some("delzone example.com IN internal")
+                ),
+                (
+                    7,
+                    "freeze example.com IN internal",
+                    Some("thaw example.com IN internal")
+                ),
+                (
+                    8,
+                    "reload example.com IN internal",
+                    Some("reload example.com IN internal")
+                ),
+                (10, "thaw example.com IN internal", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_variables() {
+        let vars = HashMap::from([("ZONE".to_string(), "example.org".to_string())]);
+        let script = BatchScript::parse(SCRIPT, &vars).unwrap();
+        assert_eq!(script.steps[2].command, "reload example.org IN internal");
+
+        assert!(BatchScript::parse("reload $MISSING", &HashMap::new()).is_err());
+        assert!(BatchScript::parse("reload ${ZONE", &HashMap::new()).is_err());
+        assert!(BatchScript::parse("set =x", &HashMap::new()).is_err());
+        assert!(BatchScript::parse("undo: reload", &HashMap::new()).is_err());
+    }
+
+    fn script(commands: &[&str]) -> BatchScript {
+        BatchScript::parse(&commands.join("\n"), &HashMap::new()).unwrap()
+    }
+
+    #[test]
+    fn test_inverse() {
+        let undo = |command| inverse(command).unwrap();
+
+        assert_eq!(
+            undo("ADDZONE a.test IN internal { type primary; };").as_deref(),
+            Some("delzone a.test IN internal")
+        );
+        assert_eq!(
+            undo("addzone a.test{ type primary; file \"a.db\"; };").as_deref(),
+            Some("delzone a.test")
+        );
+        assert_eq!(undo("addzone a.test /* c */ { type primary; };"), None);
+        assert_eq!(
+            undo("addzone a.test IN internal extra { type primary; };"),
+            None
+        );
+        assert_eq!(undo("addzone a.test"), None);
+        assert_eq!(undo("FREEZE a.test").as_deref(), Some("thaw a.test"));
+        assert_eq!(undo("\"freeze\" a.test").as_deref(), Some("thaw a.test"));
+        assert_eq!(
+            undo("thaw a.test IN \"my view\"").as_deref(),
+            Some("freeze a.test IN \"my view\"")
+        );
+        assert_eq!(undo("reload a.test"), None);
+    }
+
+    #[test]
+    fn test_run_batch_continues_by_default() {
+        let server = TestServer::start(RndcAlg::SHA256, None);
+        let client = server
+            .client("sha256")
+            .with_policy(CommandPolicy::new().deny_verbs(&["stop"]));
+
+        let report = client.run_batch(
+            &script(&["freeze a.test", "stop", "thaw a.test"]),
+            BatchOptions::default(),
+        );
+
+        assert!(!report.succeeded());
+        assert!(report.steps[0].succeeded());
+        assert!(matches!(report.steps[1].outcome, StepOutcome::Failed(_)));
+        assert!(report.steps[2].succeeded());
+        assert!(report.rollback.is_empty());
+        assert_eq!(server.commands(), ["freeze a.test", "thaw a.test"]);
+    }
+
+    #[test]
+    fn test_run_batch_rolls_back() {
+        let server = TestServer::start(RndcAlg::SHA256, None);
+        let client = server
+            .client("sha256")
+            .with_policy(CommandPolicy::new().deny_verbs(&["stop"]));
+
+        let report = client.run_batch(
+            &script(&[
+                "addzone a.test { type primary; file \"a.db\"; };",
+                "freeze b.test",
+                "reload b.test",
+                "stop",
+                "thaw b.test",
+            ]),
+            BatchOptions {
+                stop_on_error: true,
+                rollback: true,
+            },
+        );
+
+        assert!(matches!(report.steps[4].outcome, StepOutcome::Skipped));
+        assert_eq!(
+            server.commands(),
+            [
+                "addzone a.test { type primary; file \"a.db\"; };",
+                "freeze b.test",
+                "reload b.test",
+                "thaw b.test",
+                "delzone a.test",
+            ]
+        );
+
+        let text = report.to_string();
+        assert!(text.contains("line 4    FAILED   stop: Policy denied"));
+        assert!(text.contains("line 5    skipped  thaw b.test"));
+        assert!(text.contains("rollback:\nline 2    ok       thaw b.test"));
+        assert!(text.ends_with("5 steps: 3 succeeded, 1 failed, 1 skipped; 2 of 2 undone\n"));
+    }
+}
@@ -0,0 +1,116 @@
+//! `rndc-cli batch`: run a script of commands and report on every step.
+
+use std::collections::HashMap;
+use std::process::ExitCode;
+
+use rndc::{BatchOptions, BatchScript};
+
+use crate::output::BatchOutput;
+use crate::{Config, USAGE, print_dry_run};
+
+#[derive(Debug, PartialEq, Eq)]
+struct BatchArgs {
+    file: String,
+    options: BatchOptions,
+    vars: HashMap<String, String>,
+}
+
+fn parse_args(args: &[String]) -> Result<BatchArgs, String> {
+    let mut file = None;
+    let mut options = BatchOptions::default();
+    let mut vars = HashMap::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--stop-on-error" => options.stop_on_error = true,
+            "--rollback" => options.rollback = true,
+            "--var" => {
+                let var = args.next().ok_or("--var needs a value")?;
+                let (name, value) = var
+                    .split_once('=')
+                    .ok_or_else(|| format!("invalid --var {}, expected NAME=VALUE", var))?;
+                vars.insert(name.to_string(), value.to_string());
+            }
+            flag if flag.starts_with("--") => {
+                return Err(format!("unknown batch argument {}\n{}", arg, USAGE));
+            }
+            _ if file.is_none() => file = Some(arg.clone()),
+            _ => return Err(format!("unexpected argument {}\n{}", arg, USAGE)),
+        }
+    }
+
+    Ok(BatchArgs {
+        file: file.ok_or_else(|| format!("batch needs a script file\n{}", USAGE))?,
+        options,
+        vars,
+    })
+}
+
+pub(crate) fn run(config: &Config) -> ExitCode {
+    let args = match parse_args(&config.command[1..]) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::from(2);
+        }
+    };
+
+    let script = match std::fs::read_to_string(&args.file)
+        .map_err(|e| e.to_string())
+        .and_then(|text| BatchScript::parse(&text, &args.vars).map_err(|e| e.to_string()))
+    {
+        Ok(script) => script,
+        Err(e) => {
+            eprintln!("rndc-cli: {}: {}", args.file, e);
+            return ExitCode::from(2);
+        }
+    };
+
+    let server = &config.servers[0];
+    let client = config.client(server);
+    let report = client.run_batch(&script, args.options);
+    if let Some(rendered) = config.output.render(&BatchOutput::new(server, &report)) {
+        print!("{}", rendered);
+    } else {
+        print_dry_run(&client);
+        print!("{}", report);
+    }
+
+    if report.succeeded() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn test_parse_batch_args() {
+        let parsed = parse_args(&args(
+            "plan.rndc --rollback --var VIEW=internal --var A=b=c",
+        ))
+        .unwrap();
+        assert_eq!(parsed.file, "plan.rndc");
+        assert_eq!(
+            parsed.options,
+            BatchOptions {
+                stop_on_error: false,
+                rollback: true,
+            }
+        );
+        assert_eq!(parsed.vars["VIEW"], "internal");
+        assert_eq!(parsed.vars["A"], "b=c");
+
+        assert!(parse_args(&args("--stop-on-error")).is_err());
+        assert!(parse_args(&args("a.rndc b.rndc")).is_err());
+        assert!(parse_args(&args("a.rndc --var NOVALUE")).is_err());
+    }
+}
@@ -0,0 +1,191 @@
+//! `rndc-cli inspect`: decode raw packets, e.g. a TCP payload saved from
+//! tcpdump or Wireshark, and check their signatures.
+
+use std::io::Read;
+use std::process::ExitCode;
+
+use rndc::{RndcError, RndcKey, inspect};
+
+use crate::output::{Format, InspectOutput};
+use crate::{Config, USAGE};
+
+/// Where the packets come from: a file, `-` for stdin, or `--hex` inline.
+#[derive(Debug, PartialEq, Eq)]
+enum Source {
+    File(String),
+    Hex(String),
+}
+
+fn parse_args(args: &[String]) -> Result<Source, String> {
+    match args {
+        [flag, hex] if flag == "--hex" => Ok(Source::Hex(hex.clone())),
+        [file] if !file.starts_with("--") => Ok(Source::File(file.clone())),
+        _ => Err(format!("inspect needs a FILE, - or --hex HEX\n{}", USAGE)),
+    }
+}
+
+/// Hex text with optional whitespace, colons and `0x` prefixes, as copied
+/// out of packet tools.
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    let digits: String = text
+        .split_whitespace()
+        .map(|word| word.trim_start_matches("0x"))
+        .collect::<String>()
+        .replace(':', "");
+    if digits.is_empty() || !digits.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(digits.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Read the source, taking file contents as hex when they look like it and
+/// as raw bytes otherwise.
+fn read_source(source: &Source) -> Result<Vec<u8>, String> {
+    let bytes = match source {
+        Source::Hex(hex) => return decode_hex(hex).ok_or("--hex is not valid hex".to_string()),
+        Source::File(path) if path == "-" => {
+            let mut bytes = Vec::new();
+            std::io::stdin()
+                .read_to_end(&mut bytes)
+                .map_err(|e| format!("stdin: {}", e))?;
+            bytes
+        }
+        Source::File(path) => std::fs::read(path).map_err(|e| format!("{}: {}", path, e))?,
+    };
+
+    Ok(std::str::from_utf8(&bytes)
+        .ok()
+        .and_then(decode_hex)
+        .unwrap_or(bytes))
+}
+
+/// Split a byte stream into packets by their length prefixes.
+fn split_packets(mut bytes: &[u8]) -> Result<Vec<&[u8]>, String> {
+    let mut packets = Vec::new();
+    let mut offset = 0;
+    while !bytes.is_empty() {
+        let len = match bytes.get(..4) {
+            Some(prefix) => u32::from_be_bytes(prefix.try_into().unwrap()) as usize + 4,
+            None => usize::MAX,
+        };
+        if len > bytes.len() {
+            return Err(format!(
+                "truncated packet at offset {}: only {} bytes left",
+                offset,
+                bytes.len()
+            ));
+        }
+        let (packet, rest) = bytes.split_at(len);
+        packets.push(packet);
+        offset += len;
+        bytes = rest;
+    }
+    Ok(packets)
+}
+
+/// The key to check signatures with. Without a key on the command line,
+/// signatures are only checked when the default key file can be loaded.
+fn load_key(config: &Config) -> Result<Option<RndcKey>, RndcError> {
+    match config.key.key() {
+        Ok(key) => Ok(Some(key)),
+        Err(_) if config.default_key => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+pub(crate) fn run(config: &Config) -> ExitCode {
+    let key = match load_key(config) {
+        Ok(key) => key,
+        Err(e) => {
+            eprintln!("rndc-cli: {}", e);
+            return ExitCode::from(2);
+        }
+    };
+    let bytes = match parse_args(&config.command[1..]).and_then(|source| read_source(&source)) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            eprintln!("rndc-cli: {}", e);
+            return ExitCode::from(2);
+        }
+    };
+    let packets = match split_packets(&bytes) {
+        Ok(packets) => packets,
+        Err(e) => {
+            eprintln!("rndc-cli: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut succeeded = true;
+    let mut outputs = Vec::new();
+    for (i, packet) in packets.iter().enumerate() {
+        let res = inspect(packet, key.as_ref());
+        succeeded &= matches!(&res, Ok(inspection) if inspection.signature_valid != Some(false));
+        if config.output != Format::Text {
+            outputs.push(InspectOutput::from(res));
+            continue;
+        }
+
+        println!("--- packet {} ({} bytes)", i + 1, packet.len());
+        match res {
+            Ok(inspection) => print!("{}", inspection),
+            Err(e) => println!("error: {}", e),
+        }
+    }
+    if let Some(rendered) = config.output.render(&outputs) {
+        print!("{}", rendered);
+    }
+
+    if succeeded {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use rndc::KeyFileProvider;
+
+    use super::*;
+
+    #[test]
+    fn test_decode_and_split() {
+        assert_eq!(
+            decode_hex("00 00:00 0x02\nab cd"),
+            Some(vec![0, 0, 0, 2, 0xab, 0xcd])
+        );
+        assert_eq!(decode_hex("abc"), None);
+        assert_eq!(decode_hex("zz"), None);
+        assert_eq!(decode_hex(" \n"), None);
+
+        let stream = [0, 0, 0, 2, 0xab, 0xcd, 0, 0, 0, 0];
+        let packets = split_packets(&stream).unwrap();
+        assert_eq!(packets, [&stream[..6], &stream[6..]]);
+        assert!(split_packets(&stream[..5]).is_err());
+        assert!(split_packets(&stream[..8]).is_err());
+
+        assert_eq!(
+            parse_args(&["--hex".to_string(), "00".to_string()]),
+            Ok(Source::Hex("00".to_string()))
+        );
+        assert!(parse_args(&[]).is_err());
+    }
+
+    #[test]
+    fn test_load_key() {
+        let args = |line: &'static str| line.split_whitespace().map(str::to_string);
+        let config = crate::parse_args(args("--key-file /nonexistent/rndc.key inspect -")).unwrap();
+        assert!(load_key(&config).is_err());
+
+        // the default key file may exist here, so point it elsewhere
+        let mut config = crate::parse_args(args("inspect -")).unwrap();
+        config.key = Arc::new(KeyFileProvider::new("/nonexistent/rndc.key", None));
+        assert!(load_key(&config).unwrap().is_none());
+    }
+}
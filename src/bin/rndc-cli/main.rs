@@ -0,0 +1,317 @@
+//! Command line client for the BIND control channel.
+//!
+//! ```text
+//! rndc-cli --server 127.0.0.1:953 --key-file /etc/bind/rndc.key reload example.com
+//! rndc-cli --key-file /etc/bind/rndc.key batch migrate.rndc --rollback --var VIEW=internal
+//! rndc-cli --server ns1:953 --server ns2:953 --zone example.com shell
+//! rndc-cli --key-file /etc/bind/rndc.key inspect payload.hex
+//! ```
+
+mod batch;
+mod inspect;
+mod output;
+mod repl;
+
+use std::io::{self, Write};
+use std::process::ExitCode;
+use std::sync::Arc;
+
+use rndc::{
+    DryRunRecord, EnvKeyProvider, KeyFileProvider, KeyProvider, PcapTransport, RecordingTransport,
+    ReplayTransport, RndcClient, RndcError, RndcKey, TcpTransport, Transport, join_command,
+};
+
+use crate::output::{CommandOutput, Format};
+
+const USAGE: &str = "usage: rndc-cli [--server HOST:PORT]...
+        [--key-file PATH [--key-name NAME] | --algorithm ALG (--secret B64 | --secret-env VAR)]
+        [--dry-run] [--output json|yaml|text] [--record FILE | --replay FILE] [--pcap FILE]
+        COMMAND...
+       rndc-cli [OPTIONS] batch FILE [--stop-on-error] [--rollback] [--var NAME=VALUE]...
+       rndc-cli [OPTIONS] [--zone ZONE]... shell
+       rndc-cli [OPTIONS] inspect (FILE | - | --hex HEX)";
+
+const DEFAULT_SERVER: &str = "127.0.0.1:953";
+const DEFAULT_KEY_FILE: &str = "/etc/bind/rndc.key";
+
+#[derive(Debug)]
+struct Config {
+    /// The servers given with `--server`; commands go to the first one.
+    servers: Vec<String>,
+    key: Arc<dyn KeyProvider>,
+    /// No key was given, so `key` reads the default key file.
+    default_key: bool,
+    /// Zone names offered for completion by the shell.
+    zones: Vec<String>,
+    dry_run: bool,
+    output: Format,
+    /// Transcript file to record the exchanged packets to.
+    record: Option<String>,
+    /// Transcript file to answer from instead of connecting.
+    replay: Option<String>,
+    /// pcap file to capture the exchanged packets to.
+    pcap: Option<String>,
+    /// Opened by `open_transport` from `record`, `replay` and `pcap`.
+    transport: Option<Arc<dyn Transport>>,
+    /// The recording and capturing layers of `transport`, kept to report
+    /// write errors.
+    recorder: Option<Arc<RecordingTransport>>,
+    capture: Option<Arc<PcapTransport>>,
+    /// The command, or the subcommand and its arguments.
+    command: Vec<String>,
+}
+
+impl Config {
+    fn client(&self, server: &str) -> RndcClient {
+        let client = RndcClient::with_providers(server, vec![self.key.clone()])
+            .expect("one key provider is always configured");
+        let client = match &self.transport {
+            Some(transport) => client.with_transport(transport.clone()),
+            None => client,
+        };
+        if self.dry_run {
+            client.with_dry_run()
+        } else {
+            client
+        }
+    }
+
+    fn open_transport(&mut self) -> Result<(), RndcError> {
+        if self.record.is_none() && self.replay.is_none() && self.pcap.is_none() {
+            return Ok(());
+        }
+
+        let mut transport: Arc<dyn Transport> = match &self.replay {
+            Some(path) => Arc::new(ReplayTransport::open(path)?),
+            None => Arc::new(TcpTransport),
+        };
+        if let Some(path) = &self.record {
+            let recorder = Arc::new(RecordingTransport::create(path, transport)?);
+            self.recorder = Some(recorder.clone());
+            transport = recorder;
+        }
+        if let Some(path) = &self.pcap {
+            let capture = Arc::new(PcapTransport::create(path, transport)?);
+            self.capture = Some(capture.clone());
+            transport = capture;
+        }
+        self.transport = Some(transport);
+        Ok(())
+    }
+
+    fn report_write_errors(&self) {
+        let errors = [
+            self.recorder.as_ref().and_then(|r| r.write_error()),
+            self.capture.as_ref().and_then(|c| c.write_error()),
+        ];
+        for e in errors.into_iter().flatten() {
+            eprintln!("rndc-cli: {}", e);
+        }
+    }
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Config, String> {
+    let mut servers = Vec::new();
+    let mut key_file = None;
+    let mut key_name = None;
+    let mut algorithm = None;
+    let mut secret = None;
+    let mut secret_env = None;
+    let mut zones = Vec::new();
+    let mut dry_run = false;
+    let mut output = Format::Text;
+    let mut record = None;
+    let mut replay = None;
+    let mut pcap = None;
+    let mut command = Vec::new();
+
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--server" => servers.push(value()?),
+            "--key-file" => key_file = Some(value()?),
+            "--key-name" => key_name = Some(value()?),
+            "--algorithm" => algorithm = Some(value()?),
+            "--secret" => secret = Some(value()?),
+            "--secret-env" => secret_env = Some(value()?),
+            "--zone" => zones.push(value()?),
+            "--dry-run" => dry_run = true,
+            "--output" => output = Format::parse(&value()?)?,
+            "--record" => record = Some(value()?),
+            "--replay" => replay = Some(value()?),
+            "--pcap" => pcap = Some(value()?),
+            "-h" | "--help" => return Err(USAGE.to_string()),
+            flag if flag.starts_with("--") => {
+                return Err(format!("unknown argument {}\n{}", arg, USAGE));
+            }
+            _ => {
+                command.push(arg);
+                command.extend(args);
+                break;
+            }
+        }
+    }
+
+    if command.is_empty() {
+        return Err(format!("a command is required\n{}", USAGE));
+    }
+    if record.is_some() && replay.is_some() {
+        return Err(format!("--record and --replay are exclusive\n{}", USAGE));
+    }
+    if servers.is_empty() {
+        servers.push(DEFAULT_SERVER.to_string());
+    }
+
+    let default_key = key_file.is_none() && algorithm.is_none();
+    let key: Arc<dyn KeyProvider> = match (key_file, algorithm, secret, secret_env) {
+        (None, None, None, None) => {
+            Arc::new(KeyFileProvider::new(DEFAULT_KEY_FILE, key_name.as_deref()))
+        }
+        (Some(path), None, None, None) => Arc::new(KeyFileProvider::new(path, key_name.as_deref())),
+        (None, Some(alg), Some(secret), None) => Arc::new(
+            RndcKey::new(key_name.as_deref().unwrap_or("rndc-key"), &alg, &secret)
+                .map_err(|e| e.to_string())?,
+        ),
+        (None, Some(alg), None, Some(var)) => Arc::new(
+            EnvKeyProvider::new(key_name.as_deref().unwrap_or("rndc-key"), &alg, &var)
+                .map_err(|e| e.to_string())?,
+        ),
+        _ => return Err(format!("at most one key source may be given\n{}", USAGE)),
+    };
+
+    Ok(Config {
+        servers,
+        key,
+        default_key,
+        zones,
+        dry_run,
+        output,
+        record,
+        replay,
+        pcap,
+        transport: None,
+        recorder: None,
+        capture: None,
+        command,
+    })
+}
+
+/// Write the messages a dry-run client built instead of sending them.
+fn write_dry_run(out: &mut dyn Write, records: &[DryRunRecord]) -> io::Result<()> {
+    for record in records {
+        writeln!(
+            out,
+            "--- {} (key {})\n{}{}",
+            record.command, record.key_name, record.decoded, record.hex_dump
+        )?;
+    }
+    Ok(())
+}
+
+fn print_dry_run(client: &RndcClient) {
+    let _ = write_dry_run(&mut io::stdout(), &client.dry_run_log());
+}
+
+fn run_command(config: &Config) -> ExitCode {
+    let server = &config.servers[0];
+    let client = config.client(server);
+    let command = match join_command(&config.command) {
+        Ok(command) => command,
+        Err(e) => {
+            eprintln!("rndc-cli: {}", e);
+            return ExitCode::from(2);
+        }
+    };
+
+    let res = client.rndc_command(&command);
+    let succeeded = matches!(&res, Ok(res) if res.result);
+    let output = CommandOutput::new(server, &command, config.dry_run, &res);
+    if let Some(rendered) = config.output.render(&output) {
+        print!("{}", rendered);
+    } else {
+        print_dry_run(&client);
+        match res {
+            Ok(res) => {
+                if let Some(text) = &res.text {
+                    println!("{}", text);
+                }
+                if let Some(err) = &res.err {
+                    eprintln!("rndc-cli: '{}' failed: {}", command, err);
+                }
+            }
+            Err(e) => eprintln!("rndc-cli: {}", e),
+        }
+    }
+
+    if succeeded {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+fn main() -> ExitCode {
+    let mut config = match parse_args(std::env::args().skip(1)) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::from(2);
+        }
+    };
+    if let Err(e) = config.open_transport() {
+        eprintln!("rndc-cli: {}", e);
+        return ExitCode::from(2);
+    }
+
+    let code = match config.command[0].as_str() {
+        "batch" => batch::run(&config),
+        "shell" => repl::run(&config),
+        "inspect" => inspect::run(&config),
+        _ => run_command(&config),
+    };
+    config.report_write_errors();
+    code
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> impl Iterator<Item = String> {
+        line.split_whitespace()
+            .map(str::to_string)
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let config = parse_args(args(
+            "--server 10.0.0.1:953 --algorithm sha256 --secret YmluZGl6cg== reload example.com --dry-run",
+        ))
+        .unwrap();
+        assert_eq!(config.servers, ["10.0.0.1:953"]);
+        assert!(!config.dry_run);
+        // everything after the command belongs to it
+        assert_eq!(config.command, ["reload", "example.com", "--dry-run"]);
+        assert_eq!(config.key.key().unwrap().name, "rndc-key");
+        assert!(!config.default_key);
+
+        let config = parse_args(args("--dry-run --output text --pcap out.pcap status")).unwrap();
+        assert_eq!(config.servers, [DEFAULT_SERVER]);
+        assert!(config.default_key);
+        assert!(config.dry_run);
+        assert_eq!(config.output, Format::Text);
+        assert_eq!(config.pcap.as_deref(), Some("out.pcap"));
+        assert_eq!(
+            parse_args(args("--output yaml status")).is_ok(),
+            cfg!(feature = "yaml")
+        );
+
+        assert!(parse_args(args("--server 10.0.0.1:953")).is_err());
+        assert!(parse_args(args("--bogus status")).is_err());
+        assert!(parse_args(args("--output xml status")).is_err());
+        assert!(parse_args(args("--record a --replay b status")).is_err());
+        assert!(parse_args(args("--key-file a --secret b status")).is_err());
+    }
+}
@@ -0,0 +1,235 @@
+//! `--output json|yaml|text`: machine-readable results for scripts.
+//!
+//! The documents are the library's own types as serialized by their serde
+//! derives. `--output json` needs the `json` feature and `--output yaml` the
+//! `yaml` feature. Without either the documents are built but never read.
+
+#![cfg_attr(not(any(feature = "json", feature = "yaml")), allow(dead_code))]
+
+use rndc::{
+    BatchReport, PacketInspection, RndcError, RndcResult, ServeStaleStatus, ServerStatus,
+    SigningStatus, ZoneStatus,
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum Format {
+    #[default]
+    Text,
+    #[cfg(feature = "json")]
+    Json,
+    #[cfg(feature = "yaml")]
+    Yaml,
+}
+
+impl Format {
+    pub(crate) fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "text" => Ok(Format::Text),
+            #[cfg(feature = "json")]
+            "json" => Ok(Format::Json),
+            #[cfg(feature = "yaml")]
+            "yaml" => Ok(Format::Yaml),
+            #[cfg(not(feature = "json"))]
+            "json" => Err(missing_feature(name)),
+            #[cfg(not(feature = "yaml"))]
+            "yaml" => Err(missing_feature(name)),
+            _ => Err(format!(
+                "invalid --output {}, expected json, yaml or text",
+                name
+            )),
+        }
+    }
+
+    /// Render `value` as one JSON line or a YAML document, or `None` for
+    /// `Text`, which the callers print themselves.
+    #[cfg(any(feature = "json", feature = "yaml"))]
+    pub(crate) fn render<T: serde::Serialize>(self, value: &T) -> Option<String> {
+        match self {
+            Format::Text => None,
+            #[cfg(feature = "json")]
+            Format::Json => Some(format!(
+                "{}\n",
+                serde_json::to_string(value).expect("output serializes to JSON")
+            )),
+            #[cfg(feature = "yaml")]
+            Format::Yaml => {
+                Some(serde_norway::to_string(value).expect("output serializes to YAML"))
+            }
+        }
+    }
+
+    #[cfg(not(any(feature = "json", feature = "yaml")))]
+    pub(crate) fn render<T>(self, _value: &T) -> Option<String> {
+        None
+    }
+}
+
+#[cfg(not(all(feature = "json", feature = "yaml")))]
+fn missing_feature(name: &str) -> String {
+    format!(
+        "--output {} needs rndc-cli built with the {} feature",
+        name, name
+    )
+}
+
+/// The typed form of a response text, for the commands that have one.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize), serde(untagged))]
+pub(crate) enum Parsed {
+    Status(ServerStatus),
+    Zone(ZoneStatus),
+    Signing(SigningStatus),
+    ServeStale(Vec<ServeStaleStatus>),
+}
+
+impl Parsed {
+    fn parse(command: &str, text: &str) -> Option<Self> {
+        let words: Vec<&str> = command.split_whitespace().collect();
+        let verb = words.first()?.to_ascii_lowercase();
+        match (verb.as_str(), words.get(1).copied()) {
+            ("status", _) => Some(Parsed::Status(ServerStatus::parse(text))),
+            ("zonestatus", _) => Some(Parsed::Zone(ZoneStatus::parse(text))),
+            ("signing", Some("-list")) => SigningStatus::parse(text).ok().map(Parsed::Signing),
+            ("serve-stale" | "servestale", Some("status")) => {
+                Some(Parsed::ServeStale(ServeStaleStatus::parse(text)))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The outcome of a single command.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub(crate) struct CommandOutput<'a> {
+    server: &'a str,
+    command: &'a str,
+    dry_run: bool,
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    response: Option<&'a RndcResult>,
+    /// Why there is no response.
+    error: Option<String>,
+    parsed: Option<Parsed>,
+}
+
+impl<'a> CommandOutput<'a> {
+    pub(crate) fn new(
+        server: &'a str,
+        command: &'a str,
+        dry_run: bool,
+        res: &'a Result<RndcResult, RndcError>,
+    ) -> Self {
+        let parsed = match res {
+            Ok(res) if res.result && !dry_run => res
+                .text
+                .as_deref()
+                .and_then(|text| Parsed::parse(command, text)),
+            _ => None,
+        };
+        CommandOutput {
+            server,
+            command,
+            dry_run,
+            response: res.as_ref().ok(),
+            error: res.as_ref().err().map(ToString::to_string),
+            parsed,
+        }
+    }
+}
+
+/// Every step of a batch and its rollback.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub(crate) struct BatchOutput<'a> {
+    server: &'a str,
+    succeeded: bool,
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    report: &'a BatchReport,
+}
+
+impl<'a> BatchOutput<'a> {
+    pub(crate) fn new(server: &'a str, report: &'a BatchReport) -> Self {
+        BatchOutput {
+            server,
+            succeeded: report.succeeded(),
+            report,
+        }
+    }
+}
+
+/// A decoded packet, or why it could not be decoded.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize), serde(untagged))]
+pub(crate) enum InspectOutput {
+    Packet(PacketInspection),
+    Error { error: String },
+}
+
+impl From<Result<PacketInspection, RndcError>> for InspectOutput {
+    fn from(res: Result<PacketInspection, RndcError>) -> Self {
+        match res {
+            Ok(inspection) => InspectOutput::Packet(inspection),
+            Err(e) => InspectOutput::Error {
+                error: e.to_string(),
+            },
+        }
+    }
+}
+
+#[cfg(all(test, feature = "json"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_json() {
+        let res = Err(RndcError::PolicyDenied("no".to_string()));
+        let output = CommandOutput::new("ns1:953", "stop", false, &res);
+        assert_eq!(
+            Format::Json.render(&output).unwrap(),
+            "{\"server\":\"ns1:953\",\"command\":\"stop\",\"dry_run\":false,\
+             \"error\":\"Policy denied: no\",\"parsed\":null}\n"
+        );
+
+        let res = Ok(RndcResult {
+            result: true,
+            result_code: Some(0),
+            text: Some("number of zones: 3 (1 automatic)".to_string()),
+            err: None,
+            started: std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_millis(1500),
+            elapsed: std::time::Duration::from_millis(5),
+        });
+        let value: serde_json::Value = serde_json::from_str(
+            &Format::Json
+                .render(&CommandOutput::new("ns1:953", "status", false, &res))
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(value["result"], true);
+        assert_eq!(value["result_code"], 0);
+        assert_eq!(value["started"], 1.5);
+        assert_eq!(value["elapsed_ms"], 5);
+        assert_eq!(value["error"], serde_json::Value::Null);
+        assert_eq!(value["parsed"]["number_of_zones"], 3);
+    }
+
+    #[test]
+    fn test_inspect_error_json() {
+        let output = InspectOutput::from(Err(RndcError::DecodingError("short".to_string())));
+        assert_eq!(
+            Format::Json.render(&vec![output]).unwrap(),
+            "[{\"error\":\"Decoding error: short\"}]\n"
+        );
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_yaml() {
+        let report = BatchReport::default();
+        assert_eq!(
+            Format::Yaml
+                .render(&BatchOutput::new("ns1:953", &report))
+                .unwrap(),
+            "server: ns1:953\nsucceeded: true\nsteps: []\nrollback: []\n"
+        );
+    }
+}
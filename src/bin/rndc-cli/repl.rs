@@ -0,0 +1,473 @@
+//! `rndc-cli shell`: an interactive prompt that keeps one connection open.
+//!
+//! With the `repl` feature the prompt has line editing, history and tab
+//! completion of verbs and zone names; without it, lines are read from
+//! standard input as they are.
+
+use std::collections::BTreeSet;
+use std::io::{self, IsTerminal, Write};
+use std::process::ExitCode;
+
+use rndc::{RndcSession, ZoneStatus};
+
+use crate::{Config, write_dry_run};
+
+#[cfg(any(feature = "repl", test))]
+const VERBS: &[&str] = &[
+    "addzone",
+    "closelogs",
+    "delzone",
+    "dnssec",
+    "dnstap",
+    "dumpdb",
+    "fetchlimit",
+    "flush",
+    "flushname",
+    "flushtree",
+    "freeze",
+    "halt",
+    "loadkeys",
+    "managed-keys",
+    "memprof",
+    "modzone",
+    "notify",
+    "notrace",
+    "nta",
+    "querylog",
+    "reconfig",
+    "recursing",
+    "refresh",
+    "reload",
+    "responselog",
+    "retransfer",
+    "scan",
+    "secroots",
+    "serve-stale",
+    "showzone",
+    "sign",
+    "signing",
+    "stats",
+    "status",
+    "stop",
+    "sync",
+    "tcp-timeouts",
+    "thaw",
+    "trace",
+    "tsig-delete",
+    "tsig-list",
+    "validation",
+    "zonestatus",
+];
+
+#[cfg(any(feature = "repl", test))]
+const META: &[&str] = &[":help", ":quit", ":server", ":zones"];
+
+const HELP: &str = "\
+:server              list the configured servers
+:server N|HOST:PORT  switch to another configured server
+:zones               list the zones known for completion
+:zones FILE          add the zones of a `dumpdb -zones` file
+:quit                leave the shell
+Anything else is sent to the server as an rndc command.";
+
+#[derive(Debug, PartialEq, Eq)]
+enum Flow {
+    Continue,
+    Quit,
+}
+
+struct Shell<'a> {
+    config: &'a Config,
+    /// Index of the current server in `config.servers`.
+    server: usize,
+    session: Option<RndcSession>,
+    zones: BTreeSet<String>,
+    color: bool,
+}
+
+impl<'a> Shell<'a> {
+    fn new(config: &'a Config, color: bool) -> Self {
+        Shell {
+            config,
+            server: 0,
+            session: None,
+            zones: config.zones.iter().cloned().collect(),
+            color,
+        }
+    }
+
+    fn prompt(&self) -> String {
+        format!("rndc {}> ", self.config.servers[self.server])
+    }
+
+    fn paint(&self, text: &str, color: &str) -> String {
+        if self.color {
+            format!("\x1b[{}m{}\x1b[0m", color, text)
+        } else {
+            text.to_string()
+        }
+    }
+
+    /// Open a session to the current server unless one is open already.
+    fn connect(&mut self, out: &mut dyn Write) -> io::Result<()> {
+        if self.session.is_none() {
+            match self
+                .config
+                .client(&self.config.servers[self.server])
+                .session()
+            {
+                Ok(session) => self.session = Some(session),
+                Err(e) => writeln!(out, "{}", self.paint(&format!("error: {}", e), "31"))?,
+            }
+        }
+        Ok(())
+    }
+
+    fn handle(&mut self, line: &str, out: &mut dyn Write) -> io::Result<Flow> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return Ok(Flow::Continue);
+        }
+
+        let (word, arg) = match line.split_once(char::is_whitespace) {
+            Some((word, arg)) => (word, arg.trim()),
+            None => (line, ""),
+        };
+        match word {
+            ":quit" | ":exit" => return Ok(Flow::Quit),
+            ":help" => writeln!(out, "{}", HELP)?,
+            ":server" if arg.is_empty() => {
+                for (i, server) in self.config.servers.iter().enumerate() {
+                    let marker = if i == self.server { '*' } else { ' ' };
+                    writeln!(out, "{} {} {}", marker, i + 1, server)?;
+                }
+            }
+            ":server" => self.switch_server(arg, out)?,
+            ":zones" if arg.is_empty() => {
+                for zone in &self.zones {
+                    writeln!(out, "{}", zone)?;
+                }
+            }
+            ":zones" => match std::fs::read_to_string(arg) {
+                Ok(text) => {
+                    let before = self.zones.len();
+                    self.zones.extend(read_zone_dump(&text));
+                    writeln!(out, "{} new zones", self.zones.len() - before)?;
+                }
+                Err(e) => writeln!(out, "{}", self.paint(&format!("{}: {}", arg, e), "31"))?,
+            },
+            _ if word.starts_with(':') => {
+                writeln!(out, "unknown shell command {}, see :help", word)?;
+            }
+            _ => self.send(line, word, out)?,
+        }
+
+        Ok(Flow::Continue)
+    }
+
+    fn switch_server(&mut self, target: &str, out: &mut dyn Write) -> io::Result<()> {
+        let servers = &self.config.servers;
+        let index = match target.parse::<usize>() {
+            Ok(n) if (1..=servers.len()).contains(&n) => Some(n - 1),
+            _ => servers.iter().position(|s| s == target),
+        };
+
+        match index {
+            Some(index) => {
+                if let Some(mut session) = self.session.take() {
+                    let _ = session.close();
+                }
+                self.server = index;
+                self.connect(out)
+            }
+            None => writeln!(out, "no configured server {}, see :server", target),
+        }
+    }
+
+    fn send(&mut self, command: &str, verb: &str, out: &mut dyn Write) -> io::Result<()> {
+        self.connect(out)?;
+        let Some(session) = self.session.as_mut() else {
+            return Ok(());
+        };
+
+        let recorded = session.client().dry_run_log().len();
+        let res = session.rndc_command(command);
+        write_dry_run(out, &session.client().dry_run_log()[recorded..])?;
+        match res {
+            Ok(res) => {
+                if let Some(text) = res.text.as_deref().filter(|t| !t.is_empty()) {
+                    writeln!(out, "{}", self.paint(text.trim_end(), "32"))?;
+                    if verb == "zonestatus"
+                        && res.result
+                        && let Some(name) = ZoneStatus::parse(text).name
+                    {
+                        self.zones.insert(name);
+                    }
+                }
+                if let Some(err) = &res.err {
+                    writeln!(out, "{}", self.paint(err, "31"))?;
+                }
+            }
+            Err(e) => {
+                writeln!(out, "{}", self.paint(&format!("error: {}", e), "31"))?;
+                // the session reconnects on the next command
+                self.session = None;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The zone names in a dump file written by `rndc dumpdb -zones`.
+fn read_zone_dump(text: &str) -> Vec<String> {
+    text.lines()
+        .filter_map(|line| line.strip_prefix("; Zone dump of '"))
+        .filter_map(|rest| rest.split(['/', '\'']).next())
+        .filter(|zone| !zone.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Completion candidates for the word before `pos`, and where it starts.
+#[cfg(any(feature = "repl", test))]
+fn complete(
+    line: &str,
+    pos: usize,
+    zones: &BTreeSet<String>,
+    servers: &[String],
+) -> (usize, Vec<String>) {
+    let before = &line[..pos];
+    let start = before
+        .char_indices()
+        .rev()
+        .find(|(_, c)| c.is_whitespace())
+        .map_or(0, |(i, c)| i + c.len_utf8());
+    let word = &before[start..];
+    let first = before.split_whitespace().next().unwrap_or("");
+
+    let candidates: Vec<String> = if before[..start].trim().is_empty() {
+        VERBS
+            .iter()
+            .chain(META)
+            .filter(|v| v.starts_with(word))
+            .map(|v| v.to_string())
+            .collect()
+    } else if first == ":server" {
+        servers
+            .iter()
+            .filter(|s| s.starts_with(word))
+            .cloned()
+            .collect()
+    } else if first.starts_with(':') {
+        Vec::new()
+    } else {
+        zones
+            .iter()
+            .filter(|z| z.starts_with(word))
+            .cloned()
+            .collect()
+    };
+
+    (start, candidates)
+}
+
+pub(crate) fn run(config: &Config) -> ExitCode {
+    let color = io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+    let mut shell = Shell::new(config, color);
+    if let Err(e) = shell.connect(&mut io::stdout()) {
+        eprintln!("rndc-cli: {}", e);
+        return ExitCode::FAILURE;
+    }
+
+    match read_loop(&mut shell) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("rndc-cli: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(feature = "repl")]
+fn read_loop(shell: &mut Shell) -> Result<(), String> {
+    use rustyline::error::ReadlineError;
+
+    let mut editor: rustyline::Editor<Helper, rustyline::history::DefaultHistory> =
+        rustyline::Editor::new().map_err(|e| e.to_string())?;
+    let history = std::env::var_os("HOME")
+        .map(|home| std::path::PathBuf::from(home).join(".rndc_cli_history"));
+    if let Some(history) = &history {
+        let _ = editor.load_history(history);
+    }
+
+    loop {
+        editor.set_helper(Some(Helper {
+            zones: shell.zones.clone(),
+            servers: shell.config.servers.clone(),
+        }));
+        let line = match editor.readline(&shell.prompt()) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e.to_string()),
+        };
+        if !line.trim().is_empty() {
+            let _ = editor.add_history_entry(line.as_str());
+        }
+        if shell
+            .handle(&line, &mut io::stdout())
+            .map_err(|e| e.to_string())?
+            == Flow::Quit
+        {
+            break;
+        }
+    }
+
+    if let Some(history) = &history {
+        let _ = editor.save_history(history);
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "repl"))]
+fn read_loop(shell: &mut Shell) -> Result<(), String> {
+    use std::io::BufRead;
+
+    let interactive = io::stdin().is_terminal();
+    let mut lines = io::stdin().lock().lines();
+    loop {
+        if interactive {
+            print!("{}", shell.prompt());
+            io::stdout().flush().map_err(|e| e.to_string())?;
+        }
+        let Some(line) = lines.next() else {
+            break;
+        };
+        let line = line.map_err(|e| e.to_string())?;
+        if shell
+            .handle(&line, &mut io::stdout())
+            .map_err(|e| e.to_string())?
+            == Flow::Quit
+        {
+            break;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "repl")]
+struct Helper {
+    zones: BTreeSet<String>,
+    servers: Vec<String>,
+}
+
+#[cfg(feature = "repl")]
+impl rustyline::completion::Completer for Helper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &rustyline::Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(complete(line, pos, &self.zones, &self.servers))
+    }
+}
+
+#[cfg(feature = "repl")]
+impl rustyline::hint::Hinter for Helper {
+    type Hint = String;
+}
+
+#[cfg(feature = "repl")]
+impl rustyline::highlight::Highlighter for Helper {}
+
+#[cfg(feature = "repl")]
+impl rustyline::validate::Validator for Helper {}
+
+#[cfg(feature = "repl")]
+impl rustyline::Helper for Helper {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_args;
+
+    fn config() -> Config {
+        let args = "--server 10.0.0.1:953 --server 10.0.0.2:953 --algorithm sha256 \
+                    --secret YmluZGl6cg== --zone example.com --dry-run shell";
+        parse_args(args.split_whitespace().map(str::to_string)).unwrap()
+    }
+
+    fn run_lines(shell: &mut Shell, lines: &[&str]) -> String {
+        let mut out = Vec::new();
+        for line in lines {
+            shell.handle(line, &mut out).unwrap();
+        }
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_complete() {
+        let zones = BTreeSet::from(["example.com".to_string(), "example.org".to_string()]);
+        let servers = ["10.0.0.1:953".to_string()];
+
+        assert_eq!(
+            complete("rec", 3, &zones, &servers),
+            (0, vec!["reconfig".to_string(), "recursing".to_string()])
+        );
+        assert_eq!(
+            complete(":se", 3, &zones, &servers),
+            (0, vec![":server".to_string()])
+        );
+        assert_eq!(
+            complete("reload example.c", 16, &zones, &servers),
+            (7, vec!["example.com".to_string()])
+        );
+        assert_eq!(
+            complete(":server 10", 10, &zones, &servers),
+            (8, vec!["10.0.0.1:953".to_string()])
+        );
+        assert_eq!(complete(":zones ex", 9, &zones, &servers).1.len(), 0);
+        // multi-byte whitespace before the word
+        assert_eq!(
+            complete("reload\u{3000}example.c", 18, &zones, &servers),
+            (9, vec!["example.com".to_string()])
+        );
+        assert_eq!(complete("reload\u{a0}", 8, &zones, &servers).0, 8);
+    }
+
+    #[test]
+    fn test_read_zone_dump() {
+        let dump = ";\n; Zone dump of 'example.com/IN'\n;\nexample.com. 300 IN SOA ...\n\
+                    ;\n; Zone dump of 'internal.test/IN/internal'\n;\n";
+        assert_eq!(read_zone_dump(dump), ["example.com", "internal.test"]);
+    }
+
+    #[test]
+    fn test_shell_commands() {
+        let config = config();
+        let mut shell = Shell::new(&config, false);
+
+        let out = run_lines(
+            &mut shell,
+            &["# comment", "", "reload example.com", ":zones"],
+        );
+        assert!(out.starts_with("--- reload example.com (key rndc-key)\n_auth:\n"));
+        assert!(out.ends_with("\ndry run: reload example.com\nexample.com\n"));
+
+        let out = run_lines(&mut shell, &[":server 2", ":server"]);
+        assert_eq!(out, "  1 10.0.0.1:953\n* 2 10.0.0.2:953\n");
+        assert_eq!(shell.prompt(), "rndc 10.0.0.2:953> ");
+
+        let out = run_lines(&mut shell, &[":server 10.0.0.1:953", ":server 3", ":bogus"]);
+        assert_eq!(
+            out,
+            "no configured server 3, see :server\nunknown shell command :bogus, see :help\n"
+        );
+        assert_eq!(shell.server, 0);
+
+        assert_eq!(shell.handle(":quit", &mut Vec::new()).unwrap(), Flow::Quit);
+    }
+}
@@ -0,0 +1,464 @@
+//! Prometheus exporter that reads BIND statistics over the control channel,
+//! for servers where the statistics-channel is disabled.
+//!
+//! ```text
+//! rndc-exporter --server 127.0.0.1:953 --key-file /etc/bind/rndc.key \
+//!     --zone example.com --zone example.org --listen 127.0.0.1:9119
+//! ```
+
+use std::fmt::Write as _;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::process::ExitCode;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use rndc::{
+    EnvKeyProvider, KeyFileProvider, KeyProvider, RndcClient, RndcError, RndcKey, ServeStaleStatus,
+    ServerStatus, ZoneStatus,
+};
+
+const USAGE: &str = "usage: rndc-exporter --server HOST:PORT
+        (--key-file PATH [--key-name NAME] | --algorithm ALG (--secret B64 | --secret-env VAR))
+        [--zone ZONE]... [--listen ADDR] [--interval SECONDS]";
+
+#[derive(Debug)]
+struct Config {
+    server: String,
+    key: Arc<dyn KeyProvider>,
+    zones: Vec<String>,
+    listen: String,
+    interval: Duration,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Config, String> {
+    let mut server = None;
+    let mut key_file = None;
+    let mut key_name = None;
+    let mut algorithm = None;
+    let mut secret = None;
+    let mut secret_env = None;
+    let mut zones = Vec::new();
+    let mut listen = "127.0.0.1:9119".to_string();
+    let mut interval = Duration::from_secs(30);
+
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--server" => server = Some(value()?),
+            "--key-file" => key_file = Some(value()?),
+            "--key-name" => key_name = Some(value()?),
+            "--algorithm" => algorithm = Some(value()?),
+            "--secret" => secret = Some(value()?),
+            "--secret-env" => secret_env = Some(value()?),
+            "--zone" => zones.push(value()?),
+            "--listen" => listen = value()?,
+            "--interval" => {
+                let secs = value()?
+                    .parse()
+                    .map_err(|e| format!("invalid --interval: {}", e))?;
+                if secs == 0 {
+                    return Err("--interval must be at least 1 second".to_string());
+                }
+                interval = Duration::from_secs(secs);
+            }
+            "-h" | "--help" => return Err(USAGE.to_string()),
+            _ => return Err(format!("unknown argument {}\n{}", arg, USAGE)),
+        }
+    }
+
+    let server = server.ok_or_else(|| format!("--server is required\n{}", USAGE))?;
+    let key: Arc<dyn KeyProvider> = match (key_file, algorithm, secret, secret_env) {
+        (Some(path), None, None, None) => Arc::new(KeyFileProvider::new(path, key_name.as_deref())),
+        (None, Some(alg), Some(secret), None) => Arc::new(
+            RndcKey::new(key_name.as_deref().unwrap_or("rndc-key"), &alg, &secret)
+                .map_err(|e| e.to_string())?,
+        ),
+        (None, Some(alg), None, Some(var)) => Arc::new(
+            EnvKeyProvider::new(key_name.as_deref().unwrap_or("rndc-key"), &alg, &var)
+                .map_err(|e| e.to_string())?,
+        ),
+        _ => return Err(format!("exactly one key source is required\n{}", USAGE)),
+    };
+
+    Ok(Config {
+        server,
+        key,
+        zones,
+        listen,
+        interval,
+    })
+}
+
+/// Everything read from the server in one scrape.
+#[derive(Debug, Default)]
+struct Scrape {
+    status: Option<ServerStatus>,
+    zones: Vec<(String, Option<ZoneStatus>)>,
+    serve_stale: Vec<ServeStaleStatus>,
+    duration: Duration,
+}
+
+fn scrape(client: &RndcClient, zones: &[String]) -> Scrape {
+    let started = Instant::now();
+    let mut scrape = Scrape::default();
+
+    match client.status() {
+        Ok(status) => scrape.status = Some(status),
+        Err(e) => log_error("status", &e),
+    }
+    if scrape.status.is_some() {
+        for zone in zones {
+            let status = client
+                .zonestatus(zone, None, None)
+                .map_err(|e| log_error(&format!("zonestatus {}", zone), &e))
+                .ok();
+            scrape.zones.push((zone.clone(), status));
+        }
+        match client.serve_stale_status(None, None) {
+            Ok(views) => scrape.serve_stale = views,
+            Err(e) => log_error("serve-stale status", &e),
+        }
+    }
+
+    scrape.duration = started.elapsed();
+    scrape
+}
+
+fn log_error(command: &str, e: &RndcError) {
+    eprintln!("rndc-exporter: {} failed: {}", command, e);
+}
+
+fn render(scrape: &Scrape, now: SystemTime) -> String {
+    let mut out = String::new();
+    let mut gauge = |name: &str, help: &str, samples: &[(String, f64)]| {
+        if samples.is_empty() {
+            return;
+        }
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} gauge", name);
+        for (labels, value) in samples {
+            let _ = writeln!(out, "{}{} {}", name, labels, value);
+        }
+    };
+    let single = |value: Option<u64>| -> Vec<(String, f64)> {
+        value
+            .map(|v| vec![(String::new(), v as f64)])
+            .unwrap_or_default()
+    };
+    let status = scrape.status.as_ref();
+
+    gauge(
+        "rndc_up",
+        "Whether the last status command succeeded.",
+        &[(String::new(), if status.is_some() { 1.0 } else { 0.0 })],
+    );
+    gauge(
+        "rndc_scrape_duration_seconds",
+        "Time spent reading statistics from the control channel.",
+        &[(String::new(), scrape.duration.as_secs_f64())],
+    );
+    gauge(
+        "rndc_zones",
+        "Number of zones served.",
+        &single(status.and_then(|s| s.number_of_zones)),
+    );
+    gauge(
+        "rndc_automatic_zones",
+        "Number of automatic empty zones.",
+        &single(status.and_then(|s| s.automatic_zones)),
+    );
+    gauge(
+        "rndc_xfers_running",
+        "Zone transfers in progress.",
+        &single(status.and_then(|s| s.xfers_running)),
+    );
+    gauge(
+        "rndc_xfers_deferred",
+        "Zone transfers waiting to start.",
+        &single(status.and_then(|s| s.xfers_deferred)),
+    );
+
+    let recursive = status.and_then(|s| s.recursive_clients);
+    gauge(
+        "rndc_recursive_clients",
+        "Recursive clients being served.",
+        &single(recursive.map(|c| c.current)),
+    );
+    gauge(
+        "rndc_recursive_clients_soft_limit",
+        "Soft quota of recursive clients.",
+        &single(recursive.and_then(|c| c.soft_limit)),
+    );
+    gauge(
+        "rndc_recursive_clients_limit",
+        "Hard quota of recursive clients.",
+        &single(recursive.map(|c| c.hard_limit)),
+    );
+
+    let tcp = status.and_then(|s| s.tcp_clients);
+    gauge(
+        "rndc_tcp_clients",
+        "TCP clients being served.",
+        &single(tcp.map(|c| c.current)),
+    );
+    gauge(
+        "rndc_tcp_clients_limit",
+        "Quota of TCP clients.",
+        &single(tcp.map(|c| c.hard_limit)),
+    );
+
+    gauge(
+        "rndc_zone_up",
+        "Whether the last zonestatus command for the zone succeeded.",
+        &scrape
+            .zones
+            .iter()
+            .map(|(zone, status)| (zone_label(zone), status.is_some() as u8 as f64))
+            .collect::<Vec<_>>(),
+    );
+    gauge(
+        "rndc_zone_serial",
+        "SOA serial of the zone.",
+        &scrape
+            .zones
+            .iter()
+            .filter_map(|(zone, status)| Some((zone_label(zone), status.as_ref()?.serial? as f64)))
+            .collect::<Vec<_>>(),
+    );
+    gauge(
+        "rndc_zone_expiry_seconds",
+        "Seconds until a secondary zone expires unless it is refreshed.",
+        &scrape
+            .zones
+            .iter()
+            .filter_map(|(zone, status)| {
+                let expires = status.as_ref()?.expires()?;
+                let remaining = match expires.duration_since(now) {
+                    Ok(left) => left.as_secs_f64(),
+                    Err(e) => -e.duration().as_secs_f64(),
+                };
+                Some((zone_label(zone), remaining))
+            })
+            .collect::<Vec<_>>(),
+    );
+
+    gauge(
+        "rndc_serve_stale_cache_enabled",
+        "Whether the view keeps stale records in its cache.",
+        &scrape
+            .serve_stale
+            .iter()
+            .map(|v| (view_label(&v.view), v.cache_enabled as u8 as f64))
+            .collect::<Vec<_>>(),
+    );
+    gauge(
+        "rndc_serve_stale_answers_enabled",
+        "Whether the view answers from stale records.",
+        &scrape
+            .serve_stale
+            .iter()
+            .filter_map(|v| Some((view_label(&v.view), v.answers_enabled? as u8 as f64)))
+            .collect::<Vec<_>>(),
+    );
+
+    out
+}
+
+fn zone_label(zone: &str) -> String {
+    format!("{{zone=\"{}\"}}", escape_label(zone))
+}
+
+fn view_label(view: &str) -> String {
+    format!("{{view=\"{}\"}}", escape_label(view))
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn serve(mut stream: TcpStream, metrics: &Mutex<String>) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+    let mut request_line = String::new();
+    BufReader::new(&stream).read_line(&mut request_line)?;
+
+    let mut parts = request_line.split_whitespace();
+    let (status, content_type, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/metrics")) => (
+            "200 OK",
+            "text/plain; version=0.0.4",
+            metrics.lock().unwrap().clone(),
+        ),
+        (Some("GET"), _) => ("404 Not Found", "text/plain", "not found\n".to_string()),
+        _ => (
+            "405 Method Not Allowed",
+            "text/plain",
+            "method not allowed\n".to_string(),
+        ),
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )
+}
+
+fn main() -> ExitCode {
+    let config = match parse_args(std::env::args().skip(1)) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::from(2);
+        }
+    };
+    let client = match RndcClient::with_providers(&config.server, vec![config.key.clone()]) {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("rndc-exporter: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let listener = match TcpListener::bind(&config.listen) {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!(
+                "rndc-exporter: failed to listen on {}: {}",
+                config.listen, e
+            );
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let metrics = Arc::new(Mutex::new(render(
+        &scrape(&client, &config.zones),
+        SystemTime::now(),
+    )));
+    {
+        let metrics = metrics.clone();
+        thread::spawn(move || {
+            loop {
+                thread::sleep(config.interval);
+                let text = render(&scrape(&client, &config.zones), SystemTime::now());
+                *metrics.lock().unwrap() = text;
+            }
+        });
+    }
+
+    for stream in listener.incoming().flatten() {
+        let metrics = metrics.clone();
+        thread::spawn(move || {
+            let _ = serve(stream, &metrics);
+        });
+    }
+
+    ExitCode::SUCCESS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> impl Iterator<Item = String> {
+        line.split_whitespace()
+            .map(str::to_string)
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let config = parse_args(args(
+            "--server 127.0.0.1:953 --algorithm sha256 --secret YmluZGl6cg== --zone a.test --zone b.test --interval 5",
+        ))
+        .unwrap();
+        assert_eq!(config.server, "127.0.0.1:953");
+        assert_eq!(config.zones, ["a.test", "b.test"]);
+        assert_eq!(config.interval, Duration::from_secs(5));
+        assert_eq!(config.key.key().unwrap().secret, b"bindizr");
+
+        assert!(parse_args(args("--algorithm sha256 --secret YmluZGl6cg==")).is_err());
+        assert!(parse_args(args("--server 127.0.0.1:953")).is_err());
+        assert!(
+            parse_args(args(
+                "--server 127.0.0.1:953 --key-file /etc/bind/rndc.key --secret YmluZGl6cg=="
+            ))
+            .is_err()
+        );
+        assert!(
+            parse_args(args(
+                "--server 127.0.0.1:953 --key-file /etc/bind/rndc.key --interval 0"
+            ))
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_render() {
+        let scrape = Scrape {
+            status: Some(ServerStatus::parse(
+                "number of zones: 105 (98 automatic)\n\
+                 xfers running: 1\n\
+                 xfers deferred: 0\n\
+                 recursive clients: 3/900/1000\n\
+                 tcp clients: 1/150",
+            )),
+            zones: vec![
+                (
+                    "example.com".to_string(),
+                    Some(ZoneStatus::parse(
+                        "serial: 2024010101\nexpires: Thu, 01 Jan 1970 00:01:40 GMT",
+                    )),
+                ),
+                ("missing.test".to_string(), None),
+            ],
+            serve_stale: ServeStaleStatus::parse(
+                "_default: stale cache enabled; stale answers disabled (stale-answer-ttl=30 max-stale-ttl=86400 stale-refresh-time=30)",
+            ),
+            duration: Duration::from_millis(250),
+        };
+        let text = render(&scrape, SystemTime::UNIX_EPOCH + Duration::from_secs(40));
+
+        for expected in [
+            "# TYPE rndc_up gauge\nrndc_up 1\n",
+            "rndc_scrape_duration_seconds 0.25\n",
+            "rndc_zones 105\n",
+            "rndc_automatic_zones 98\n",
+            "rndc_xfers_running 1\n",
+            "rndc_xfers_deferred 0\n",
+            "rndc_recursive_clients 3\n",
+            "rndc_recursive_clients_soft_limit 900\n",
+            "rndc_recursive_clients_limit 1000\n",
+            "rndc_tcp_clients 1\n",
+            "rndc_tcp_clients_limit 150\n",
+            "rndc_zone_up{zone=\"example.com\"} 1\n",
+            "rndc_zone_up{zone=\"missing.test\"} 0\n",
+            "rndc_zone_serial{zone=\"example.com\"} 2024010101\n",
+            "rndc_zone_expiry_seconds{zone=\"example.com\"} 60\n",
+            "rndc_serve_stale_cache_enabled{view=\"_default\"} 1\n",
+            "rndc_serve_stale_answers_enabled{view=\"_default\"} 0\n",
+        ] {
+            assert!(
+                text.contains(expected),
+                "missing {:?} in\n{}",
+                expected,
+                text
+            );
+        }
+        assert!(!text.contains("rndc_zone_serial{zone=\"missing.test\"}"));
+    }
+
+    #[test]
+    fn test_render_down() {
+        let text = render(&Scrape::default(), SystemTime::now());
+        assert!(text.contains("rndc_up 0\n"));
+        assert!(!text.contains("rndc_zones"));
+    }
+}
@@ -0,0 +1,727 @@
+//! HTTP/JSON gateway translating REST calls into control channel commands,
+//! for tools that cannot speak the RNDC protocol.
+//!
+//! ```text
+//! rndc-gateway /etc/rndc-gateway.json
+//! ```
+//!
+//! Endpoints, all requiring `Authorization: Bearer <token>`:
+//!
+//! - `POST /servers/{name}/commands` with `{"command": "reload example.com"}`
+//! - `GET /servers/{name}/status`
+//! - `POST /zones` with `{"server": "ns1", "zone": "example.com", "config": "{ ... };"}`
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use rndc::{
+    CommandPolicy, EnvKeyProvider, KeyFileProvider, KeyProvider, RndcClient, RndcError, RndcKey,
+    RndcResult, ServerStatus,
+};
+
+/// Largest request body accepted.
+const MAX_BODY: usize = 64 * 1024;
+
+/// Longest request line or header line accepted.
+const MAX_LINE: usize = 8 * 1024;
+
+/// Largest request line and headers accepted, together.
+const MAX_HEAD: usize = 32 * 1024;
+
+/// How long a client may take to send its whole request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Config {
+    #[serde(default = "default_listen")]
+    listen: String,
+    /// Connections served at once; further ones wait to be accepted.
+    #[serde(default = "default_workers")]
+    workers: usize,
+    servers: HashMap<String, ServerConfig>,
+    tokens: Vec<TokenConfig>,
+}
+
+fn default_listen() -> String {
+    "127.0.0.1:8953".to_string()
+}
+
+fn default_workers() -> usize {
+    16
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ServerConfig {
+    address: String,
+    key_file: Option<String>,
+    key_name: Option<String>,
+    algorithm: Option<String>,
+    secret: Option<String>,
+    secret_env: Option<String>,
+}
+
+impl ServerConfig {
+    fn provider(&self) -> Result<Arc<dyn KeyProvider>, RndcError> {
+        let name = self.key_name.as_deref().unwrap_or("rndc-key");
+        match (
+            &self.key_file,
+            &self.algorithm,
+            &self.secret,
+            &self.secret_env,
+        ) {
+            (Some(path), None, None, None) => Ok(Arc::new(KeyFileProvider::new(path, Some(name)))),
+            (None, Some(alg), Some(secret), None) => Ok(Arc::new(RndcKey::new(name, alg, secret)?)),
+            (None, Some(alg), None, Some(var)) => {
+                Ok(Arc::new(EnvKeyProvider::new(name, alg, var)?))
+            }
+            _ => Err(RndcError::InvalidArgument(format!(
+                "Server {} needs key_file, or algorithm with secret or secret_env",
+                self.address
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TokenConfig {
+    token: Option<String>,
+    token_env: Option<String>,
+    /// Command verbs the token may run; `*` allows every command.
+    commands: Vec<String>,
+    /// Servers the token may use; every server when absent.
+    servers: Option<Vec<String>>,
+    /// Zone patterns the token's zone commands are limited to.
+    zones: Option<Vec<String>>,
+    /// View patterns the token's commands are limited to.
+    views: Option<Vec<String>>,
+}
+
+impl TokenConfig {
+    fn policy(&self) -> CommandPolicy {
+        fn patterns(list: &[String]) -> Vec<&str> {
+            list.iter().map(String::as_str).collect()
+        }
+
+        let mut policy = CommandPolicy::new().allow_verbs(&patterns(&self.commands));
+        if let Some(zones) = &self.zones {
+            policy = policy.allow_zones(&patterns(zones));
+        }
+        if let Some(views) = &self.views {
+            policy = policy.allow_views(&patterns(views));
+        }
+        policy
+    }
+}
+
+/// A bearer token and what it may do.
+#[derive(Debug)]
+struct Grant {
+    token: String,
+    servers: Option<Vec<String>>,
+    policy: CommandPolicy,
+}
+
+impl Grant {
+    fn may_use(&self, server: &str) -> bool {
+        self.servers
+            .as_ref()
+            .is_none_or(|servers| servers.iter().any(|s| s == server))
+    }
+}
+
+#[derive(Debug)]
+struct Gateway {
+    clients: HashMap<String, RndcClient>,
+    grants: Vec<Grant>,
+}
+
+impl Gateway {
+    fn from_config(config: &Config) -> Result<Self, String> {
+        let mut clients = HashMap::new();
+        for (name, server) in &config.servers {
+            let provider = server.provider().map_err(|e| e.to_string())?;
+            let client = RndcClient::with_providers(&server.address, vec![provider])
+                .map_err(|e| e.to_string())?;
+            clients.insert(name.clone(), client);
+        }
+
+        let mut grants = Vec::new();
+        for token in &config.tokens {
+            let value = match (&token.token, &token.token_env) {
+                (Some(value), None) => value.clone(),
+                (None, Some(var)) => std::env::var(var)
+                    .map_err(|e| format!("Failed to read token from {}: {}", var, e))?,
+                _ => return Err("Each token needs exactly one of token or token_env".to_string()),
+            };
+            if value.is_empty() {
+                return Err("Tokens must not be empty".to_string());
+            }
+            grants.push(Grant {
+                token: value,
+                servers: token.servers.clone(),
+                policy: token.policy(),
+            });
+        }
+
+        Ok(Gateway { clients, grants })
+    }
+
+    fn handle(&self, request: &Request) -> Response {
+        let Some(grant) = self.authenticate(request) else {
+            return Response::error(401, "missing or invalid bearer token");
+        };
+
+        let path: Vec<&str> = request.path.trim_matches('/').split('/').collect();
+        match (request.method.as_str(), path.as_slice()) {
+            ("POST", ["servers", server, "commands"]) => {
+                #[derive(Deserialize)]
+                #[serde(deny_unknown_fields)]
+                struct Body {
+                    command: String,
+                }
+                match serde_json::from_slice::<Body>(&request.body) {
+                    Ok(body) => self.run(grant, server, &body.command, |client| {
+                        client.rndc_command(&body.command)
+                    }),
+                    Err(e) => Response::error(400, &format!("invalid request body: {}", e)),
+                }
+            }
+            ("GET", ["servers", server, "status"]) => {
+                let mut response = self.run(grant, server, "status", |client| {
+                    client.rndc_command("status")
+                });
+                if response.status == 200
+                    && let Some(text) = response.body["text"].as_str()
+                {
+                    response.body["status"] = json!(ServerStatus::parse(text));
+                }
+                response
+            }
+            ("POST", ["zones"]) => {
+                #[derive(Deserialize)]
+                #[serde(deny_unknown_fields)]
+                struct Body {
+                    server: String,
+                    zone: String,
+                    config: String,
+                    class: Option<String>,
+                    view: Option<String>,
+                }
+                match serde_json::from_slice::<Body>(&request.body) {
+                    Ok(body) => self.run(
+                        grant,
+                        &body.server,
+                        &addzone_line(&body.zone, body.class.as_deref(), body.view.as_deref()),
+                        |client| {
+                            client.addzone(
+                                &body.zone,
+                                body.class.as_deref(),
+                                body.view.as_deref(),
+                                &body.config,
+                            )
+                        },
+                    ),
+                    Err(e) => Response::error(400, &format!("invalid request body: {}", e)),
+                }
+            }
+            (_, ["servers", _, "commands" | "status"] | ["zones"]) => {
+                Response::error(405, "method not allowed")
+            }
+            _ => Response::error(404, "not found"),
+        }
+    }
+
+    fn authenticate(&self, request: &Request) -> Option<&Grant> {
+        let token = request
+            .header("authorization")?
+            .strip_prefix("Bearer ")?
+            .trim();
+        self.grants
+            .iter()
+            .find(|grant| constant_time_eq(grant.token.as_bytes(), token.as_bytes()))
+    }
+
+    /// Run `command` on `server` with a client that enforces the policy of
+    /// `grant`, so the policy sees exactly the command that is sent.
+    fn run(
+        &self,
+        grant: &Grant,
+        server: &str,
+        command: &str,
+        f: impl FnOnce(&RndcClient) -> Result<RndcResult, RndcError>,
+    ) -> Response {
+        // the same answer for servers that do not exist, so tokens cannot
+        // probe for them
+        let client = match self.clients.get(server) {
+            Some(client) if grant.may_use(server) => {
+                client.clone().with_policy(grant.policy.clone())
+            }
+            _ => return Response::error(403, &format!("token may not use server {}", server)),
+        };
+
+        match f(&client) {
+            Ok(res) => Response {
+                status: if res.result { 200 } else { 422 },
+                body: json!({
+                    "server": server,
+                    "command": command,
+                    "result": res.result,
+                    "text": res.text,
+                    "err": res.err,
+                    "elapsed_ms": res.elapsed.as_millis() as u64,
+                }),
+            },
+            Err(e @ RndcError::InvalidArgument(_)) => Response::error(400, &e.to_string()),
+            Err(e @ RndcError::PolicyDenied(_)) => Response::error(403, &e.to_string()),
+            Err(e) => Response::error(502, &e.to_string()),
+        }
+    }
+}
+
+/// The `addzone` command line without its configuration block, reported
+/// in the response. Words are quoted the way the sent command quotes them.
+fn addzone_line(zone: &str, class: Option<&str>, view: Option<&str>) -> String {
+    let mut words = vec!["addzone", zone];
+    match (class, view) {
+        (Some(class), Some(view)) => words.extend([class, view]),
+        (Some(class), None) => words.push(class),
+        (None, Some(view)) => words.extend(["IN", view]),
+        (None, None) => {}
+    }
+    words
+        .iter()
+        .map(|word| {
+            if word
+                .chars()
+                .any(|c| c.is_whitespace() || matches!(c, '{' | '}' | ';' | '(' | ')'))
+            {
+                format!("\"{}\"", word)
+            } else {
+                word.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[derive(Debug, Default)]
+struct Request {
+    method: String,
+    path: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Request {
+    fn read(stream: &TcpStream) -> Result<Self, Response> {
+        Self::parse(Deadline {
+            stream,
+            deadline: Instant::now() + REQUEST_TIMEOUT,
+        })
+    }
+
+    fn parse(stream: impl Read) -> Result<Self, Response> {
+        let bad_request = |msg: &str| Response::error(400, msg);
+        let mut head = BufReader::new(stream).take(MAX_HEAD as u64);
+
+        let line = read_head_line(&mut head)?;
+        let mut parts = line.split_whitespace();
+        let (Some(method), Some(path)) = (parts.next(), parts.next()) else {
+            return Err(bad_request("malformed request line"));
+        };
+        let mut request = Request {
+            method: method.to_string(),
+            path: path.to_string(),
+            ..Default::default()
+        };
+
+        loop {
+            let line = read_head_line(&mut head)?;
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                request
+                    .headers
+                    .push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+            }
+        }
+
+        let length: usize = match request.header("content-length") {
+            Some(value) => value
+                .parse()
+                .map_err(|_| bad_request("invalid content-length"))?,
+            None => 0,
+        };
+        if length > MAX_BODY {
+            return Err(Response::error(413, "request body too large"));
+        }
+        request.body = vec![0; length];
+        head.into_inner()
+            .read_exact(&mut request.body)
+            .map_err(|_| bad_request("truncated body"))?;
+
+        Ok(request)
+    }
+
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// Read one line of the request line and headers, refusing lines longer
+/// than `MAX_LINE` and heads longer than `MAX_HEAD`.
+fn read_head_line(head: &mut io::Take<impl BufRead>) -> Result<String, Response> {
+    let mut line = String::new();
+    head.take(MAX_LINE as u64)
+        .read_line(&mut line)
+        .map_err(|_| Response::error(400, "unreadable request"))?;
+    if !line.ends_with('\n') && (line.len() == MAX_LINE || head.limit() == 0) {
+        return Err(Response::error(431, "request headers too large"));
+    }
+    Ok(line)
+}
+
+/// Reads from a connection until `deadline`, so a client sending a byte at
+/// a time cannot hold a worker for longer.
+struct Deadline<'a> {
+    stream: &'a TcpStream,
+    deadline: Instant,
+}
+
+impl Read for Deadline<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let left = self.deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Err(io::Error::new(ErrorKind::TimedOut, "request took too long"));
+        }
+        self.stream.set_read_timeout(Some(left))?;
+        let mut stream = self.stream;
+        stream.read(buf)
+    }
+}
+
+#[derive(Debug)]
+struct Response {
+    status: u16,
+    body: Value,
+}
+
+impl Response {
+    fn error(status: u16, message: &str) -> Self {
+        Response {
+            status,
+            body: json!({ "error": message }),
+        }
+    }
+
+    fn write(&self, stream: &mut TcpStream) -> std::io::Result<()> {
+        let reason = match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            401 => "Unauthorized",
+            403 => "Forbidden",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            413 => "Payload Too Large",
+            431 => "Request Header Fields Too Large",
+            422 => "Unprocessable Entity",
+            _ => "Bad Gateway",
+        };
+        let body = self.body.to_string();
+        let auth = if self.status == 401 {
+            "WWW-Authenticate: Bearer\r\n"
+        } else {
+            ""
+        };
+        write!(
+            stream,
+            "HTTP/1.1 {} {}\r\n{}Content-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            self.status,
+            reason,
+            auth,
+            body.len(),
+            body
+        )
+    }
+}
+
+fn serve(mut stream: TcpStream, gateway: &Gateway) -> std::io::Result<()> {
+    stream.set_write_timeout(Some(Duration::from_secs(10)))?;
+    let response = match Request::read(&stream) {
+        Ok(request) => gateway.handle(&request),
+        Err(response) => response,
+    };
+    response.write(&mut stream)
+}
+
+fn main() -> ExitCode {
+    let Some(path) = std::env::args().nth(1) else {
+        eprintln!("usage: rndc-gateway CONFIG.json");
+        return ExitCode::from(2);
+    };
+    let config: Config = match std::fs::read(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|data| serde_json::from_slice(&data).map_err(|e| e.to_string()))
+    {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("rndc-gateway: failed to read {}: {}", path, e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let gateway = match Gateway::from_config(&config) {
+        Ok(gateway) => Arc::new(gateway),
+        Err(e) => {
+            eprintln!("rndc-gateway: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let listener = match TcpListener::bind(&config.listen) {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("rndc-gateway: failed to listen on {}: {}", config.listen, e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if config.workers == 0 {
+        eprintln!("rndc-gateway: workers must be at least 1");
+        return ExitCode::FAILURE;
+    }
+
+    // each worker accepts and serves one connection at a time
+    let listener = Arc::new(listener);
+    let workers: Vec<_> = (0..config.workers)
+        .map(|_| {
+            let listener = listener.clone();
+            let gateway = gateway.clone();
+            thread::spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    let _ = serve(stream, &gateway);
+                }
+            })
+        })
+        .collect();
+    for worker in workers {
+        let _ = worker.join();
+    }
+
+    ExitCode::SUCCESS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"{
+        "servers": {
+            "ns1": {"address": "127.0.0.1:1", "algorithm": "sha256", "secret": "YmluZGl6cg=="},
+            "ns2": {"address": "127.0.0.1:1", "key_file": "/nonexistent/rndc.key"}
+        },
+        "tokens": [
+            {"token": "panel", "commands": ["status", "reload", "addzone"], "servers": ["ns1"], "zones": ["*.example.com"]},
+            {"token": "admin", "commands": ["*"]},
+            {"token": "internal", "commands": ["addzone"], "views": ["internal"]}
+        ]
+    }"#;
+
+    fn gateway() -> Gateway {
+        let config: Config = serde_json::from_str(CONFIG).unwrap();
+        assert_eq!(config.listen, "127.0.0.1:8953");
+        Gateway::from_config(&config).unwrap()
+    }
+
+    fn request(method: &str, path: &str, token: Option<&str>, body: &str) -> Request {
+        Request {
+            method: method.to_string(),
+            path: path.to_string(),
+            headers: token
+                .map(|t| vec![("authorization".to_string(), format!("Bearer {}", t))])
+                .unwrap_or_default(),
+            body: body.as_bytes().to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_requires_token() {
+        let gateway = gateway();
+        let status = |token| {
+            gateway
+                .handle(&request("GET", "/servers/ns1/status", token, ""))
+                .status
+        };
+        assert_eq!(status(None), 401);
+        assert_eq!(status(Some("wrong")), 401);
+        // authorized, but nothing listens on the server address
+        assert_eq!(status(Some("panel")), 502);
+    }
+
+    #[test]
+    fn test_command_allowlist() {
+        let gateway = gateway();
+        let post = |token, path, body| gateway.handle(&request("POST", path, Some(token), body));
+
+        let res = post("panel", "/servers/ns1/commands", r#"{"command": "stop"}"#);
+        assert_eq!(res.status, 403);
+        assert_eq!(
+            post("panel", "/servers/ns2/commands", r#"{"command": "reload"}"#).status,
+            403
+        );
+        assert_eq!(
+            post(
+                "panel",
+                "/servers/ns1/commands",
+                r#"{"command": "reload example.org"}"#
+            )
+            .status,
+            403
+        );
+        assert_eq!(
+            post(
+                "panel",
+                "/servers/ns1/commands",
+                r#"{"command": "reload www.example.com"}"#
+            )
+            .status,
+            502
+        );
+        assert_eq!(
+            post(
+                "panel",
+                "/zones",
+                r#"{"server": "ns1", "zone": "example.org", "config": "{ type primary; };"}"#
+            )
+            .status,
+            403
+        );
+        // named would skip the comment and add evil.org
+        assert_eq!(
+            post(
+                "panel",
+                "/servers/ns1/commands",
+                r#"{"command": "addzone /*a.example.com */ evil.org { type primary; file \"x\"; };"}"#
+            )
+            .status,
+            403
+        );
+        assert_eq!(
+            post("admin", "/servers/ns1/commands", r#"{"command": "stop"}"#).status,
+            502
+        );
+        // unknown servers look the same as servers the token may not use
+        assert_eq!(
+            post("admin", "/servers/ns3/commands", r#"{"command": "stop"}"#).status,
+            403
+        );
+        assert_eq!(
+            post("panel", "/servers/ns3/commands", r#"{"command": "reload"}"#).body["error"],
+            "token may not use server ns3"
+        );
+    }
+
+    #[test]
+    fn test_addzone_policy_sees_quoted_view() {
+        let gateway = gateway();
+        let addzone = |view: &str| {
+            let body = json!({
+                "server": "ns1",
+                "zone": "example.com",
+                "config": "{ type primary; };",
+                "view": view,
+            });
+            gateway.handle(&request(
+                "POST",
+                "/zones",
+                Some("internal"),
+                &body.to_string(),
+            ))
+        };
+
+        // allowed, but nothing listens on the server address
+        assert_eq!(addzone("internal").status, 502);
+        // sent as one quoted view name, not as view "internal"
+        let res = addzone("internal extra");
+        assert_eq!(res.status, 403);
+        assert!(
+            res.body["error"]
+                .as_str()
+                .unwrap()
+                .contains("internal extra")
+        );
+        assert_eq!(
+            addzone_line("example.com", None, Some("internal extra")),
+            "addzone example.com IN \"internal extra\""
+        );
+    }
+
+    #[test]
+    fn test_request_validation() {
+        let gateway = gateway();
+        let handle = |method, path, body| {
+            gateway
+                .handle(&request(method, path, Some("admin"), body))
+                .status
+        };
+
+        assert_eq!(handle("POST", "/servers/ns1/commands", "{}"), 400);
+        assert_eq!(handle("GET", "/servers/ns1/commands", ""), 405);
+        assert_eq!(handle("GET", "/nothing", ""), 404);
+        assert_eq!(
+            handle(
+                "POST",
+                "/zones",
+                r#"{"server": "ns1", "zone": "example.com", "config": "type primary;"}"#
+            ),
+            400
+        );
+    }
+
+    #[test]
+    fn test_request_limits() {
+        let status = |raw: &[u8]| Request::parse(raw).err().map(|res| res.status);
+
+        let request =
+            Request::parse(&b"POST /zones HTTP/1.1\r\nContent-Length: 2\r\n\r\n{}"[..]).unwrap();
+        assert_eq!(request.path, "/zones");
+        assert_eq!(request.body, b"{}");
+
+        let long_line = format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(MAX_LINE));
+        assert_eq!(status(long_line.as_bytes()), Some(431));
+        let many_headers = format!("GET / HTTP/1.1\r\n{}\r\n", "X-A: b\r\n".repeat(MAX_HEAD));
+        assert_eq!(status(many_headers.as_bytes()), Some(431));
+        assert_eq!(status(b""), Some(400));
+        assert_eq!(
+            status(b"POST / HTTP/1.1\r\nContent-Length: 99999999\r\n\r\n"),
+            Some(413)
+        );
+    }
+
+    #[test]
+    fn test_status_json() {
+        let status = ServerStatus::parse("number of zones: 3 (1 automatic)\ntcp clients: 1/150");
+        let value = json!(status);
+        assert_eq!(value["number_of_zones"], 3);
+        assert_eq!(value["tcp_clients"]["hard_limit"], 150);
+        assert_eq!(value["fields"]["tcp clients"], "1/150");
+    }
+}
@@ -0,0 +1,110 @@
+use crate::error::RndcError;
+use crate::internal::args::{command_line, validate_domain_name};
+use crate::{RndcClient, RndcResult};
+
+/// Which databases `rndc dumpdb` should write to the dump file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DumpDbScope {
+    All,
+    Cache,
+    Zones,
+    Adb,
+    Bad,
+    Expired,
+    Fail,
+}
+impl DumpDbScope {
+    fn as_flag(&self) -> &'static str {
+        match self {
+            DumpDbScope::All => "-all",
+            DumpDbScope::Cache => "-cache",
+            DumpDbScope::Zones => "-zones",
+            DumpDbScope::Adb => "-adb",
+            DumpDbScope::Bad => "-bad",
+            DumpDbScope::Expired => "-expired",
+            DumpDbScope::Fail => "-fail",
+        }
+    }
+}
+
+fn flush_command(view: Option<&str>) -> Result<String, RndcError> {
+    match view {
+        Some(view) => command_line("flush", &[view]),
+        None => command_line("flush", &[]),
+    }
+}
+
+fn flush_name_command(verb: &str, name: &str, view: Option<&str>) -> Result<String, RndcError> {
+    validate_domain_name(name)?;
+    match view {
+        Some(view) => command_line(verb, &[name, view]),
+        None => command_line(verb, &[name]),
+    }
+}
+
+fn dumpdb_command(scope: DumpDbScope, views: &[&str]) -> Result<String, RndcError> {
+    let mut args = vec![scope.as_flag()];
+    args.extend_from_slice(views);
+    command_line("dumpdb", &args)
+}
+
+impl RndcClient {
+    /// Flush the whole cache of `view`, or of every view when `None`.
+    pub fn flush(&self, view: Option<&str>) -> Result<RndcResult, RndcError> {
+        self.rndc_command(&flush_command(view)?)
+    }
+
+    /// Flush the cached records for exactly `name`.
+    pub fn flush_name(&self, name: &str, view: Option<&str>) -> Result<RndcResult, RndcError> {
+        self.rndc_command(&flush_name_command("flushname", name, view)?)
+    }
+
+    /// Flush `name` and every name below it from the cache.
+    pub fn flush_tree(&self, name: &str, view: Option<&str>) -> Result<RndcResult, RndcError> {
+        self.rndc_command(&flush_name_command("flushtree", name, view)?)
+    }
+
+    /// Dump the selected databases of `views` (all views when empty) to the
+    /// server's dump file.
+    pub fn dumpdb(&self, scope: DumpDbScope, views: &[&str]) -> Result<RndcResult, RndcError> {
+        self.rndc_command(&dumpdb_command(scope, views)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flush_command() {
+        assert_eq!(flush_command(None).unwrap(), "flush");
+        assert_eq!(flush_command(Some("internal")).unwrap(), "flush internal");
+        assert!(flush_command(Some("")).is_err());
+    }
+
+    #[test]
+    fn test_flush_name_command() {
+        assert_eq!(
+            flush_name_command("flushname", "www.example.com", None).unwrap(),
+            "flushname www.example.com"
+        );
+        assert_eq!(
+            flush_name_command("flushtree", "example.com.", Some("external")).unwrap(),
+            "flushtree example.com. external"
+        );
+        assert!(flush_name_command("flushname", "", None).is_err());
+        assert!(flush_name_command("flushname", "example.com internal", None).is_err());
+    }
+
+    #[test]
+    fn test_dumpdb_command() {
+        assert_eq!(
+            dumpdb_command(DumpDbScope::Cache, &[]).unwrap(),
+            "dumpdb -cache"
+        );
+        assert_eq!(
+            dumpdb_command(DumpDbScope::All, &["internal", "external"]).unwrap(),
+            "dumpdb -all internal external"
+        );
+    }
+}
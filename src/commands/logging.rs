@@ -0,0 +1,184 @@
+use crate::commands::expect_success;
+use crate::error::RndcError;
+use crate::{RndcClient, RndcResult};
+
+fn querylog_command(enabled: bool) -> &'static str {
+    if enabled {
+        "querylog on"
+    } else {
+        "querylog off"
+    }
+}
+
+fn trace_command(level: Option<u32>) -> String {
+    match level {
+        Some(0) => "notrace".to_string(),
+        Some(level) => format!("trace {}", level),
+        None => "trace".to_string(),
+    }
+}
+
+impl RndcClient {
+    /// Turn query logging on or off (`rndc querylog on|off`).
+    pub fn querylog(&self, enabled: bool) -> Result<RndcResult, RndcError> {
+        self.rndc_command(querylog_command(enabled))
+    }
+
+    /// Set the debug level, or raise it by one when `level` is `None`
+    /// (`rndc trace [level]`).
+    pub fn trace(&self, level: Option<u32>) -> Result<RndcResult, RndcError> {
+        self.rndc_command(&trace_command(level))
+    }
+
+    /// Reset the debug level to 0 (`rndc notrace`).
+    pub fn notrace(&self) -> Result<RndcResult, RndcError> {
+        self.rndc_command("notrace")
+    }
+
+    /// Set query logging to `enabled` until the returned guard is dropped,
+    /// then restore the state reported by `status` beforehand.
+    ///
+    /// A dry run has no state to read, so it only records `querylog` and
+    /// the guard restores nothing.
+    pub fn querylog_guard(&self, enabled: bool) -> Result<QueryLogGuard<'_>, RndcError> {
+        if self.is_dry_run() {
+            expect_success(self.querylog(enabled)?, querylog_command(enabled))?;
+            return Ok(QueryLogGuard {
+                client: self,
+                previous: enabled,
+                restored: true,
+            });
+        }
+        let previous = self.status()?.query_logging.ok_or_else(|| {
+            RndcError::DecodingError("Query logging state missing from status".to_string())
+        })?;
+        if previous != enabled {
+            expect_success(self.querylog(enabled)?, querylog_command(enabled))?;
+        }
+        Ok(QueryLogGuard {
+            client: self,
+            previous,
+            restored: previous == enabled,
+        })
+    }
+
+    /// Set the debug level to `level` until the returned guard is dropped,
+    /// then restore the level reported by `status` beforehand.
+    ///
+    /// A dry run only records `trace`, and the guard restores nothing.
+    pub fn trace_guard(&self, level: u32) -> Result<TraceGuard<'_>, RndcError> {
+        if self.is_dry_run() {
+            expect_success(self.trace(Some(level))?, &trace_command(Some(level)))?;
+            return Ok(TraceGuard {
+                client: self,
+                previous: level,
+                restored: true,
+            });
+        }
+        let previous = self.status()?.debug_level.ok_or_else(|| {
+            RndcError::DecodingError("Debug level missing from status".to_string())
+        })?;
+        if previous != level {
+            expect_success(self.trace(Some(level))?, &trace_command(Some(level)))?;
+        }
+        Ok(TraceGuard {
+            client: self,
+            previous,
+            restored: previous == level,
+        })
+    }
+}
+
+/// Restores the previous query logging state when dropped.
+#[derive(Debug)]
+pub struct QueryLogGuard<'a> {
+    client: &'a RndcClient,
+    previous: bool,
+    restored: bool,
+}
+impl QueryLogGuard<'_> {
+    /// The state that will be restored; the requested state under a dry
+    /// run.
+    pub fn previous(&self) -> bool {
+        self.previous
+    }
+
+    /// Restore the previous state now and report any error, instead of
+    /// silently ignoring it on drop.
+    pub fn restore(mut self) -> Result<(), RndcError> {
+        self.restore_inner()
+    }
+
+    fn restore_inner(&mut self) -> Result<(), RndcError> {
+        if self.restored {
+            return Ok(());
+        }
+        self.restored = true;
+        expect_success(
+            self.client.querylog(self.previous)?,
+            querylog_command(self.previous),
+        )?;
+        Ok(())
+    }
+}
+impl Drop for QueryLogGuard<'_> {
+    fn drop(&mut self) {
+        let _ = self.restore_inner();
+    }
+}
+
+/// Restores the previous debug level when dropped.
+#[derive(Debug)]
+pub struct TraceGuard<'a> {
+    client: &'a RndcClient,
+    previous: u32,
+    restored: bool,
+}
+impl TraceGuard<'_> {
+    /// The level that will be restored; the requested level under a dry
+    /// run.
+    pub fn previous(&self) -> u32 {
+        self.previous
+    }
+
+    /// Restore the previous level now and report any error, instead of
+    /// silently ignoring it on drop.
+    pub fn restore(mut self) -> Result<(), RndcError> {
+        self.restore_inner()
+    }
+
+    fn restore_inner(&mut self) -> Result<(), RndcError> {
+        if self.restored {
+            return Ok(());
+        }
+        self.restored = true;
+        expect_success(
+            self.client.trace(Some(self.previous))?,
+            &trace_command(Some(self.previous)),
+        )?;
+        Ok(())
+    }
+}
+impl Drop for TraceGuard<'_> {
+    fn drop(&mut self) {
+        let _ = self.restore_inner();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trace_command() {
+        assert_eq!(trace_command(None), "trace");
+        assert_eq!(trace_command(Some(3)), "trace 3");
+        assert_eq!(trace_command(Some(0)), "notrace");
+    }
+
+    #[test]
+    fn test_querylog_command() {
+        assert_eq!(querylog_command(true), "querylog on");
+        assert_eq!(querylog_command(false), "querylog off");
+    }
+}
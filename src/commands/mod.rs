@@ -0,0 +1,21 @@
+pub(crate) mod cache;
+pub(crate) mod logging;
+pub(crate) mod serve_stale;
+pub(crate) mod signing;
+pub(crate) mod status;
+pub(crate) mod tsig;
+pub(crate) mod zone;
+
+use crate::RndcResult;
+use crate::error::RndcError;
+
+/// Turn a failed `RndcResult` into `RndcError::CommandFailed`.
+pub(crate) fn expect_success(res: RndcResult, command: &str) -> Result<RndcResult, RndcError> {
+    if res.result {
+        Ok(res)
+    } else {
+        Err(RndcError::CommandFailed(
+            res.err.unwrap_or_else(|| format!("{} failed", command)),
+        ))
+    }
+}
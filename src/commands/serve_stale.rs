@@ -0,0 +1,142 @@
+use crate::RndcClient;
+use crate::commands::expect_success;
+use crate::error::RndcError;
+use crate::internal::args::command_line;
+
+/// One view of `rndc serve-stale status`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ServeStaleStatus {
+    pub view: String,
+    /// Whether stale records are kept in the cache at all.
+    pub cache_enabled: bool,
+    /// Whether stale answers are served; `None` when they are unavailable
+    /// because the stale cache is disabled.
+    pub answers_enabled: Option<bool>,
+    pub stale_answer_ttl: Option<u64>,
+    pub max_stale_ttl: Option<u64>,
+    pub stale_refresh_time: Option<u64>,
+}
+impl ServeStaleStatus {
+    /// Parse the `text` of a `serve-stale status` response, one line per
+    /// view, e.g. `_default: stale cache enabled; stale answers disabled
+    /// (stale-answer-ttl=30 max-stale-ttl=86400 stale-refresh-time=30)`.
+    pub fn parse(text: &str) -> Vec<Self> {
+        let mut views = Vec::new();
+
+        for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            let Some((view, rest)) = line.split_once(": ") else {
+                continue;
+            };
+            let (states, params) = match rest.split_once('(') {
+                Some((states, params)) => (states, params.trim_end_matches(')')),
+                None => (rest, ""),
+            };
+
+            let mut status = ServeStaleStatus {
+                view: view.to_string(),
+                ..Default::default()
+            };
+            for state in states.split(';').map(str::trim) {
+                if let Some(value) = state.strip_prefix("stale cache ") {
+                    status.cache_enabled = matches!(value.trim(), "enabled" | "on");
+                } else if let Some(value) = state.strip_prefix("stale answers ") {
+                    status.answers_enabled = match value.trim() {
+                        "enabled" | "on" => Some(true),
+                        "disabled" | "off" => Some(false),
+                        _ => None,
+                    };
+                }
+            }
+            for param in params.split_whitespace() {
+                let Some((name, value)) = param.split_once('=') else {
+                    continue;
+                };
+                let value = value.parse().ok();
+                match name {
+                    "stale-answer-ttl" => status.stale_answer_ttl = value,
+                    "max-stale-ttl" => status.max_stale_ttl = value,
+                    "stale-refresh-time" => status.stale_refresh_time = value,
+                    _ => {}
+                }
+            }
+            views.push(status);
+        }
+
+        views
+    }
+}
+
+fn serve_stale_status_command(
+    class: Option<&str>,
+    view: Option<&str>,
+) -> Result<String, RndcError> {
+    let mut args = vec!["status"];
+    match (class, view) {
+        (Some(class), Some(view)) => args.extend([class, view]),
+        (Some(class), None) => args.push(class),
+        (None, Some(view)) => args.extend(["IN", view]),
+        (None, None) => {}
+    }
+    command_line("serve-stale", &args)
+}
+
+impl RndcClient {
+    /// Run `rndc serve-stale status` for `view`, or every view when `None`,
+    /// and parse the result.
+    pub fn serve_stale_status(
+        &self,
+        class: Option<&str>,
+        view: Option<&str>,
+    ) -> Result<Vec<ServeStaleStatus>, RndcError> {
+        let res = expect_success(
+            self.rndc_command(&serve_stale_status_command(class, view)?)?,
+            "serve-stale",
+        )?;
+        Ok(ServeStaleStatus::parse(
+            res.text.as_deref().unwrap_or_default(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_serve_stale_status() {
+        let text = "_default: stale cache enabled; stale answers disabled (stale-answer-ttl=30 max-stale-ttl=86400 stale-refresh-time=30)\n\
+                    internal: stale cache disabled; stale answers unavailable";
+        let views = ServeStaleStatus::parse(text);
+
+        assert_eq!(
+            views,
+            [
+                ServeStaleStatus {
+                    view: "_default".to_string(),
+                    cache_enabled: true,
+                    answers_enabled: Some(false),
+                    stale_answer_ttl: Some(30),
+                    max_stale_ttl: Some(86400),
+                    stale_refresh_time: Some(30),
+                },
+                ServeStaleStatus {
+                    view: "internal".to_string(),
+                    ..Default::default()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_serve_stale_status_command() {
+        assert_eq!(
+            serve_stale_status_command(None, None).unwrap(),
+            "serve-stale status"
+        );
+        assert_eq!(
+            serve_stale_status_command(None, Some("internal")).unwrap(),
+            "serve-stale status IN internal"
+        );
+    }
+}
@@ -0,0 +1,439 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::commands::expect_success;
+use crate::error::RndcError;
+use crate::internal::args::{command_line, zone_args};
+use crate::{RndcClient, RndcResult};
+
+/// State of a single key record reported by `rndc signing -list`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum SigningState {
+    /// `Signing with key ...`
+    Signing,
+    /// `Done signing with key ...`
+    Done,
+    /// `Removing signatures for key ...`
+    Removing,
+    /// `Done removing signatures for key ...`
+    Removed,
+}
+
+/// One key record from `rndc signing -list`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SigningKeyEntry {
+    pub key_id: u16,
+    pub algorithm: String,
+    pub state: SigningState,
+}
+impl SigningKeyEntry {
+    pub fn is_complete(&self) -> bool {
+        matches!(self.state, SigningState::Done | SigningState::Removed)
+    }
+}
+
+/// Parsed output of `rndc signing -list`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SigningStatus {
+    pub keys: Vec<SigningKeyEntry>,
+    /// Lines that are not key records, e.g. NSEC3 chain progress.
+    pub other: Vec<String>,
+}
+impl SigningStatus {
+    /// Parse the `text` of a `signing -list` response.
+    pub fn parse(text: &str) -> Result<Self, RndcError> {
+        let mut status = SigningStatus::default();
+
+        for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            if line == "No signing records found" {
+                continue;
+            }
+
+            let (state, rest) = if let Some(rest) = line.strip_prefix("Done signing with key ") {
+                (SigningState::Done, rest)
+            } else if let Some(rest) = line.strip_prefix("Signing with key ") {
+                (SigningState::Signing, rest)
+            } else if let Some(rest) = line.strip_prefix("Done removing signatures for key ") {
+                (SigningState::Removed, rest)
+            } else if let Some(rest) = line.strip_prefix("Removing signatures for key ") {
+                (SigningState::Removing, rest)
+            } else {
+                status.other.push(line.to_string());
+                continue;
+            };
+
+            let (key_id, algorithm) = rest.split_once('/').ok_or_else(|| {
+                RndcError::DecodingError(format!("Malformed signing record: {}", line))
+            })?;
+            let key_id = key_id.parse::<u16>().map_err(|e| {
+                RndcError::DecodingError(format!("Invalid key id in signing record: {}", e))
+            })?;
+
+            status.keys.push(SigningKeyEntry {
+                key_id,
+                algorithm: algorithm.to_string(),
+                state,
+            });
+        }
+
+        Ok(status)
+    }
+
+    /// True when no key record is still in progress and no NSEC3 chain
+    /// is being built or removed.
+    pub fn is_complete(&self) -> bool {
+        self.keys.iter().all(SigningKeyEntry::is_complete)
+            && !self
+                .other
+                .iter()
+                .any(|l| l.starts_with("Creating") || l.starts_with("Removing"))
+    }
+}
+
+/// Which private signing records `rndc signing -clear` removes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SigningClear {
+    /// Every completed record (`-clear all`).
+    All,
+    /// The record of one key (`-clear keyid/algorithm`). named takes the
+    /// algorithm as a number or a mnemonic like `RSASHA256`, as listed by
+    /// `signing -list`.
+    Key { key_id: u16, algorithm: String },
+}
+
+impl From<&SigningKeyEntry> for SigningClear {
+    fn from(entry: &SigningKeyEntry) -> Self {
+        SigningClear::Key {
+            key_id: entry.key_id,
+            algorithm: entry.algorithm.clone(),
+        }
+    }
+}
+
+/// NSEC3 parameters for `rndc signing -nsec3param`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Nsec3Param {
+    /// Switch the zone back to NSEC (`-nsec3param none`).
+    None,
+    Params {
+        hash: u8,
+        flags: u8,
+        iterations: u16,
+        /// Hex salt; `None` for no salt.
+        salt: Option<String>,
+    },
+}
+
+fn signing_command(
+    option: &[&str],
+    zone: &str,
+    class: Option<&str>,
+    view: Option<&str>,
+) -> Result<String, RndcError> {
+    let mut args = vec!["signing"];
+    args.extend_from_slice(option);
+    args.extend(zone_args(zone, class, view)?);
+    command_line(args[0], &args[1..])
+}
+
+fn signing_clear_command(
+    clear: &SigningClear,
+    zone: &str,
+    class: Option<&str>,
+    view: Option<&str>,
+) -> Result<String, RndcError> {
+    let selector = match clear {
+        SigningClear::All => "all".to_string(),
+        SigningClear::Key { key_id, algorithm } => {
+            if algorithm.is_empty() || !algorithm.chars().all(|c| c.is_ascii_alphanumeric()) {
+                return Err(RndcError::InvalidArgument(format!(
+                    "Invalid signing key algorithm: {}",
+                    algorithm
+                )));
+            }
+            format!("{}/{}", key_id, algorithm)
+        }
+    };
+    signing_command(&["-clear", &selector], zone, class, view)
+}
+
+fn signing_nsec3param_command(
+    param: &Nsec3Param,
+    zone: &str,
+    class: Option<&str>,
+    view: Option<&str>,
+) -> Result<String, RndcError> {
+    match param {
+        Nsec3Param::None => signing_command(&["-nsec3param", "none"], zone, class, view),
+        Nsec3Param::Params {
+            hash,
+            flags,
+            iterations,
+            salt,
+        } => {
+            let salt = match salt {
+                Some(salt) if salt.is_empty() || !salt.chars().all(|c| c.is_ascii_hexdigit()) => {
+                    return Err(RndcError::InvalidArgument(format!(
+                        "NSEC3 salt must be hexadecimal: {}",
+                        salt
+                    )));
+                }
+                Some(salt) => salt.as_str(),
+                None => "-",
+            };
+            signing_command(
+                &[
+                    "-nsec3param",
+                    &hash.to_string(),
+                    &flags.to_string(),
+                    &iterations.to_string(),
+                    salt,
+                ],
+                zone,
+                class,
+                view,
+            )
+        }
+    }
+}
+
+impl RndcClient {
+    /// List the private signing records of `zone` (`rndc signing -list`).
+    pub fn signing_list(
+        &self,
+        zone: &str,
+        class: Option<&str>,
+        view: Option<&str>,
+    ) -> Result<SigningStatus, RndcError> {
+        let res = expect_success(
+            self.rndc_command(&signing_command(&["-list"], zone, class, view)?)?,
+            "signing -list",
+        )?;
+        SigningStatus::parse(res.text.as_deref().unwrap_or_default())
+    }
+
+    /// Remove completed private signing records (`rndc signing -clear`).
+    pub fn signing_clear(
+        &self,
+        clear: &SigningClear,
+        zone: &str,
+        class: Option<&str>,
+        view: Option<&str>,
+    ) -> Result<RndcResult, RndcError> {
+        self.rndc_command(&signing_clear_command(clear, zone, class, view)?)
+    }
+
+    /// Change the NSEC3 chain of `zone` (`rndc signing -nsec3param`).
+    pub fn signing_nsec3param(
+        &self,
+        param: &Nsec3Param,
+        zone: &str,
+        class: Option<&str>,
+        view: Option<&str>,
+    ) -> Result<RndcResult, RndcError> {
+        self.rndc_command(&signing_nsec3param_command(param, zone, class, view)?)
+    }
+
+    /// Set the SOA serial of a signed zone (`rndc signing -serial`).
+    pub fn signing_serial(
+        &self,
+        serial: u32,
+        zone: &str,
+        class: Option<&str>,
+        view: Option<&str>,
+    ) -> Result<RndcResult, RndcError> {
+        self.rndc_command(&signing_command(
+            &["-serial", &serial.to_string()],
+            zone,
+            class,
+            view,
+        )?)
+    }
+
+    /// Poll `signing -list` every `interval` until every record is complete
+    /// or `timeout` elapses. The last check is made at the deadline.
+    ///
+    /// A zero `interval` fails with `RndcError::InvalidArgument`. A dry run
+    /// has nothing to wait for and returns an empty status without recording
+    /// a command.
+    pub fn wait_for_signing(
+        &self,
+        zone: &str,
+        class: Option<&str>,
+        view: Option<&str>,
+        interval: Duration,
+        timeout: Duration,
+    ) -> Result<SigningStatus, RndcError> {
+        if interval.is_zero() {
+            return Err(RndcError::InvalidArgument(
+                "Signing poll interval must not be zero".to_string(),
+            ));
+        }
+        if self.is_dry_run() {
+            return Ok(SigningStatus::default());
+        }
+        let deadline = Instant::now() + timeout;
+        loop {
+            let status = self.signing_list(zone, class, view)?;
+            if status.is_complete() {
+                return Ok(status);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(RndcError::Timeout(format!(
+                    "Signing of {} did not complete within {:?}",
+                    zone, timeout
+                )));
+            }
+            thread::sleep(interval.min(deadline - now));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_signing_list() {
+        let text = "Done signing with key 12345/RSASHA256\n\
+                    Signing with key 54321/ECDSAP256SHA256\n\
+                    Done removing signatures for key 1111/RSASHA1\n";
+        let status = SigningStatus::parse(text).unwrap();
+
+        assert_eq!(
+            status.keys,
+            vec![
+                SigningKeyEntry {
+                    key_id: 12345,
+                    algorithm: "RSASHA256".to_string(),
+                    state: SigningState::Done,
+                },
+                SigningKeyEntry {
+                    key_id: 54321,
+                    algorithm: "ECDSAP256SHA256".to_string(),
+                    state: SigningState::Signing,
+                },
+                SigningKeyEntry {
+                    key_id: 1111,
+                    algorithm: "RSASHA1".to_string(),
+                    state: SigningState::Removed,
+                },
+            ]
+        );
+        assert!(!status.is_complete());
+    }
+
+    #[test]
+    fn test_clear_listed_key() {
+        let status = SigningStatus::parse("Done signing with key 12345/RSASHA256").unwrap();
+        let clear = SigningClear::from(&status.keys[0]);
+        assert_eq!(
+            signing_clear_command(&clear, "example.com", None, None).unwrap(),
+            "signing -clear 12345/RSASHA256 example.com"
+        );
+    }
+
+    #[test]
+    fn test_parse_signing_list_empty() {
+        let status = SigningStatus::parse("No signing records found").unwrap();
+        assert!(status.keys.is_empty());
+        assert!(status.is_complete());
+    }
+
+    #[test]
+    fn test_parse_signing_list_nsec3() {
+        let status = SigningStatus::parse(
+            "Done signing with key 12345/RSASHA256\nCreating NSEC3 chain 1 0 0 -",
+        )
+        .unwrap();
+        assert_eq!(status.other, vec!["Creating NSEC3 chain 1 0 0 -"]);
+        assert!(!status.is_complete());
+    }
+
+    #[test]
+    fn test_parse_signing_list_malformed() {
+        assert!(SigningStatus::parse("Signing with key abc/RSASHA256").is_err());
+    }
+
+    #[test]
+    fn test_signing_commands() {
+        assert_eq!(
+            signing_command(&["-list"], "example.com", None, Some("internal")).unwrap(),
+            "signing -list example.com IN internal"
+        );
+        assert_eq!(
+            signing_clear_command(
+                &SigningClear::Key {
+                    key_id: 12345,
+                    algorithm: "13".to_string()
+                },
+                "example.com",
+                None,
+                None
+            )
+            .unwrap(),
+            "signing -clear 12345/13 example.com"
+        );
+        assert!(
+            signing_clear_command(
+                &SigningClear::Key {
+                    key_id: 1,
+                    algorithm: "RSA SHA256".to_string()
+                },
+                "example.com",
+                None,
+                None
+            )
+            .is_err()
+        );
+        assert_eq!(
+            signing_nsec3param_command(
+                &Nsec3Param::Params {
+                    hash: 1,
+                    flags: 0,
+                    iterations: 0,
+                    salt: None
+                },
+                "example.com",
+                None,
+                None
+            )
+            .unwrap(),
+            "signing -nsec3param 1 0 0 - example.com"
+        );
+        assert!(
+            signing_nsec3param_command(
+                &Nsec3Param::Params {
+                    hash: 1,
+                    flags: 0,
+                    iterations: 0,
+                    salt: Some("xyz".to_string())
+                },
+                "example.com",
+                None,
+                None
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_wait_for_signing_rejects_zero_interval() {
+        let client = RndcClient::new("127.0.0.1:1", "sha256", "YmluZGl6cg==").unwrap();
+        assert!(matches!(
+            client.wait_for_signing(
+                "example.com",
+                None,
+                None,
+                Duration::ZERO,
+                Duration::from_secs(1)
+            ),
+            Err(RndcError::InvalidArgument(_))
+        ));
+    }
+}
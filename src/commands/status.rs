@@ -0,0 +1,178 @@
+use indexmap::IndexMap;
+
+use crate::RndcClient;
+use crate::commands::expect_success;
+use crate::error::RndcError;
+
+/// A `current/soft/hard` client counter from `rndc status`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ClientCount {
+    pub current: u64,
+    /// Soft quota; only reported for recursive clients.
+    pub soft_limit: Option<u64>,
+    pub hard_limit: u64,
+}
+impl ClientCount {
+    fn parse(value: &str) -> Option<Self> {
+        let parts = value
+            .split('/')
+            .map(|p| p.trim().parse::<u64>().ok())
+            .collect::<Option<Vec<_>>>()?;
+        match parts[..] {
+            [current, hard_limit] => Some(ClientCount {
+                current,
+                soft_limit: None,
+                hard_limit,
+            }),
+            [current, soft_limit, hard_limit] => Some(ClientCount {
+                current,
+                soft_limit: Some(soft_limit),
+                hard_limit,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Parsed output of `rndc status`.
+///
+/// Every `name: value` line is kept in `fields`; the commonly used ones are
+/// also exposed as typed fields when the server reports them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ServerStatus {
+    pub version: Option<String>,
+    pub number_of_zones: Option<u64>,
+    pub automatic_zones: Option<u64>,
+    pub debug_level: Option<u32>,
+    pub xfers_running: Option<u64>,
+    pub xfers_deferred: Option<u64>,
+    pub soa_queries_in_progress: Option<u64>,
+    pub query_logging: Option<bool>,
+    pub recursive_clients: Option<ClientCount>,
+    pub tcp_clients: Option<ClientCount>,
+    pub running: bool,
+    pub fields: IndexMap<String, String>,
+}
+impl ServerStatus {
+    /// Parse the `text` of a `status` response.
+    pub fn parse(text: &str) -> Self {
+        let mut status = ServerStatus::default();
+
+        for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            if line == "server is up and running" {
+                status.running = true;
+                continue;
+            }
+            if let Some(state) = line.strip_prefix("query logging is ") {
+                status.query_logging = Some(state.eq_ignore_ascii_case("on"));
+                continue;
+            }
+
+            let Some((name, value)) = line.split_once(": ") else {
+                continue;
+            };
+            let value = value.trim();
+            match name {
+                "version" => status.version = Some(value.to_string()),
+                "number of zones" => {
+                    // "105 (98 automatic)"
+                    let mut parts = value.split_whitespace();
+                    status.number_of_zones = parts.next().and_then(|v| v.parse().ok());
+                    status.automatic_zones = parts
+                        .next()
+                        .and_then(|v| v.strip_prefix('('))
+                        .and_then(|v| v.parse().ok());
+                }
+                "debug level" => status.debug_level = value.parse().ok(),
+                "xfers running" => status.xfers_running = value.parse().ok(),
+                "xfers deferred" => status.xfers_deferred = value.parse().ok(),
+                "soa queries in progress" => status.soa_queries_in_progress = value.parse().ok(),
+                "recursive clients" => status.recursive_clients = ClientCount::parse(value),
+                "tcp clients" => status.tcp_clients = ClientCount::parse(value),
+                _ => {}
+            }
+            status.fields.insert(name.to_string(), value.to_string());
+        }
+
+        status
+    }
+}
+
+impl RndcClient {
+    /// Run `rndc status` and parse the result.
+    pub fn status(&self) -> Result<ServerStatus, RndcError> {
+        let res = expect_success(self.rndc_command("status")?, "status")?;
+        Ok(ServerStatus::parse(res.text.as_deref().unwrap_or_default()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_status() {
+        let text = "version: BIND 9.18.30-0ubuntu0.24.04.2-Ubuntu (Extended Support Version) <id:>\n\
+                    running on localhost: Linux x86_64 5.15.167.4\n\
+                    boot time: Mon, 11 Aug 2025 13:32:16 GMT\n\
+                    configuration file: /etc/bind/named.conf\n\
+                    CPUs found: 8\n\
+                    number of zones: 105 (98 automatic)\n\
+                    debug level: 2\n\
+                    xfers running: 1\n\
+                    xfers deferred: 0\n\
+                    soa queries in progress: 0\n\
+                    query logging is ON\n\
+                    recursive clients: 3/900/1000\n\
+                    tcp clients: 1/150\n\
+                    TCP high-water: 4\n\
+                    server is up and running";
+        let status = ServerStatus::parse(text);
+
+        assert_eq!(
+            status.version.as_deref(),
+            Some("BIND 9.18.30-0ubuntu0.24.04.2-Ubuntu (Extended Support Version) <id:>")
+        );
+        assert_eq!(status.number_of_zones, Some(105));
+        assert_eq!(status.automatic_zones, Some(98));
+        assert_eq!(status.debug_level, Some(2));
+        assert_eq!(status.xfers_running, Some(1));
+        assert_eq!(status.query_logging, Some(true));
+        assert_eq!(
+            status.recursive_clients,
+            Some(ClientCount {
+                current: 3,
+                soft_limit: Some(900),
+                hard_limit: 1000
+            })
+        );
+        assert_eq!(
+            status.tcp_clients,
+            Some(ClientCount {
+                current: 1,
+                soft_limit: None,
+                hard_limit: 150
+            })
+        );
+        assert!(status.running);
+        assert_eq!(
+            status.fields.get("TCP high-water").map(String::as_str),
+            Some("4")
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serialize_status() {
+        let status = ServerStatus::parse("number of zones: 3 (1 automatic)\ntcp clients: 1/150");
+        let value = serde_json::to_value(&status).unwrap();
+
+        assert_eq!(value["number_of_zones"], 3);
+        assert_eq!(value["tcp_clients"]["soft_limit"], serde_json::Value::Null);
+        assert_eq!(value["tcp_clients"]["hard_limit"], 150);
+        assert_eq!(value["fields"]["tcp clients"], "1/150");
+        assert_eq!(value["running"], false);
+    }
+}
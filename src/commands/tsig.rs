@@ -0,0 +1,174 @@
+use crate::commands::expect_success;
+use crate::error::RndcError;
+use crate::internal::args::{command_line, validate_domain_name};
+use crate::{RndcClient, RndcResult};
+
+/// How a TSIG key was created on the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum TsigKeyType {
+    /// Configured in `named.conf`.
+    Static,
+    /// Negotiated at run time, e.g. TKEY session keys.
+    Dynamic,
+}
+
+/// One key from `rndc tsig-list`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct TsigKeyEntry {
+    pub view: String,
+    pub name: String,
+    /// Only reported by servers that include it in the listing.
+    pub algorithm: Option<String>,
+    pub key_type: TsigKeyType,
+    /// Identity that negotiated a dynamic key.
+    pub creator: Option<String>,
+}
+
+/// Split a `field "value"; field "value";` line into its pairs.
+fn parse_fields(line: &str) -> Result<Vec<(&str, &str)>, RndcError> {
+    let mut fields = Vec::new();
+    let mut rest = line.trim();
+
+    while !rest.is_empty() {
+        let (name, after) = rest.split_once(char::is_whitespace).ok_or_else(|| {
+            RndcError::DecodingError(format!("Malformed tsig-list line: {}", line))
+        })?;
+        let after = after.trim_start();
+
+        let (value, after) = if let Some(quoted) = after.strip_prefix('"') {
+            let end = quoted.find('"').ok_or_else(|| {
+                RndcError::DecodingError(format!("Unterminated string in tsig-list: {}", line))
+            })?;
+            (&quoted[..end], &quoted[end + 1..])
+        } else {
+            let end = after.find(';').unwrap_or(after.len());
+            (after[..end].trim(), &after[end..])
+        };
+
+        fields.push((name, value));
+        rest = after.trim_start().trim_start_matches(';').trim_start();
+    }
+
+    Ok(fields)
+}
+
+/// Parse the `text` of a `tsig-list` response.
+pub(crate) fn parse_tsig_list(text: &str) -> Result<Vec<TsigKeyEntry>, RndcError> {
+    let mut keys = Vec::new();
+
+    for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        if line.starts_with("no tsig keys found") {
+            continue;
+        }
+
+        let mut view = None;
+        let mut name = None;
+        let mut algorithm = None;
+        let mut key_type = None;
+        let mut creator = None;
+        for (field, value) in parse_fields(line)? {
+            match field {
+                "view" => view = Some(value.to_string()),
+                "key" => name = Some(value.to_string()),
+                "algorithm" => algorithm = Some(value.to_string()),
+                "creator" => creator = Some(value.to_string()),
+                "type" => {
+                    key_type = Some(match value {
+                        "static" => TsigKeyType::Static,
+                        "dynamic" => TsigKeyType::Dynamic,
+                        _ => {
+                            return Err(RndcError::DecodingError(format!(
+                                "Unknown tsig key type: {}",
+                                value
+                            )));
+                        }
+                    })
+                }
+                _ => {}
+            }
+        }
+
+        match (view, name, key_type) {
+            (Some(view), Some(name), Some(key_type)) => keys.push(TsigKeyEntry {
+                view,
+                name,
+                algorithm,
+                key_type,
+                creator,
+            }),
+            _ => {
+                return Err(RndcError::DecodingError(format!(
+                    "Incomplete tsig-list entry: {}",
+                    line
+                )));
+            }
+        }
+    }
+
+    Ok(keys)
+}
+
+fn tsig_delete_command(name: &str, view: Option<&str>) -> Result<String, RndcError> {
+    validate_domain_name(name)?;
+    match view {
+        Some(view) => command_line("tsig-delete", &[name, view]),
+        None => command_line("tsig-delete", &[name]),
+    }
+}
+
+impl RndcClient {
+    /// List the TSIG keys of every view (`rndc tsig-list`).
+    pub fn tsig_list(&self) -> Result<Vec<TsigKeyEntry>, RndcError> {
+        let res = expect_success(self.rndc_command("tsig-list")?, "tsig-list")?;
+        parse_tsig_list(res.text.as_deref().unwrap_or_default())
+    }
+
+    /// Delete a dynamic TSIG key (`rndc tsig-delete`).
+    pub fn tsig_delete(&self, name: &str, view: Option<&str>) -> Result<RndcResult, RndcError> {
+        self.rndc_command(&tsig_delete_command(name, view)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tsig_list() {
+        let text = "view \"_default\"; type \"static\"; key \"rndc-key\";\n\
+                    view \"_default\"; type \"dynamic\"; key \"1234.sig-ns1.example.com\"; creator \"admin@EXAMPLE.COM\";\n\
+                    view \"internal\"; type \"static\"; key \"xfer\"; algorithm \"hmac-sha256\";\n";
+        let keys = parse_tsig_list(text).unwrap();
+
+        assert_eq!(keys.len(), 3);
+        assert_eq!(keys[0].name, "rndc-key");
+        assert_eq!(keys[0].key_type, TsigKeyType::Static);
+        assert_eq!(keys[1].key_type, TsigKeyType::Dynamic);
+        assert_eq!(keys[1].creator.as_deref(), Some("admin@EXAMPLE.COM"));
+        assert_eq!(keys[2].view, "internal");
+        assert_eq!(keys[2].algorithm.as_deref(), Some("hmac-sha256"));
+    }
+
+    #[test]
+    fn test_parse_tsig_list_empty() {
+        assert!(parse_tsig_list("no tsig keys found.").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_parse_tsig_list_malformed() {
+        assert!(parse_tsig_list("view \"_default\"; key \"rndc-key\";").is_err());
+        assert!(parse_tsig_list("view \"_default; type \"static\";").is_err());
+    }
+
+    #[test]
+    fn test_tsig_delete_command() {
+        assert_eq!(
+            tsig_delete_command("1234.sig-ns1.example.com", Some("internal")).unwrap(),
+            "tsig-delete 1234.sig-ns1.example.com internal"
+        );
+        assert!(tsig_delete_command("bad key", None).is_err());
+    }
+}
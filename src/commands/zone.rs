@@ -0,0 +1,334 @@
+use indexmap::IndexMap;
+use std::time::SystemTime;
+
+use crate::commands::expect_success;
+use crate::error::RndcError;
+use crate::internal::args::{command_line, zone_args};
+use crate::internal::utils::parse_http_date;
+use crate::{RndcClient, RndcResult};
+
+/// Parsed output of `rndc zonestatus`.
+///
+/// Every `name: value` line is kept in `fields`; the commonly used ones are
+/// also exposed as typed fields when the server reports them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ZoneStatus {
+    pub name: Option<String>,
+    pub zone_type: Option<String>,
+    /// The zone file followed by any `$INCLUDE`d files.
+    pub files: Vec<String>,
+    pub serial: Option<u32>,
+    pub nodes: Option<u64>,
+    pub dynamic: Option<bool>,
+    pub frozen: Option<bool>,
+    pub secure: Option<bool>,
+    pub fields: IndexMap<String, String>,
+}
+impl ZoneStatus {
+    /// Parse the `text` of a `zonestatus` response.
+    pub fn parse(text: &str) -> Self {
+        let mut status = ZoneStatus::default();
+
+        for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            let Some((name, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            let flag = || match value {
+                "yes" => Some(true),
+                "no" => Some(false),
+                _ => None,
+            };
+            match name {
+                "name" => status.name = Some(value.to_string()),
+                "type" => status.zone_type = Some(value.to_string()),
+                "files" => {
+                    status.files = value
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|f| !f.is_empty())
+                        .map(str::to_string)
+                        .collect()
+                }
+                "serial" => status.serial = value.parse().ok(),
+                "nodes" => status.nodes = value.parse().ok(),
+                "dynamic" => status.dynamic = flag(),
+                "frozen" => status.frozen = flag(),
+                "secure" => status.secure = flag(),
+                _ => {}
+            }
+            status.fields.insert(name.to_string(), value.to_string());
+        }
+
+        status
+    }
+
+    /// The primary zone file, if the zone has one.
+    pub fn zone_file(&self) -> Option<&str> {
+        self.files.first().map(String::as_str)
+    }
+
+    /// When a secondary zone expires unless it is refreshed, from the
+    /// `expires` field.
+    pub fn expires(&self) -> Option<SystemTime> {
+        self.fields.get("expires").and_then(|v| parse_http_date(v))
+    }
+}
+
+fn zone_command(
+    verb: &str,
+    zone: &str,
+    class: Option<&str>,
+    view: Option<&str>,
+) -> Result<String, RndcError> {
+    command_line(verb, &zone_args(zone, class, view)?)
+}
+
+/// `addzone zone [class [view]] { ... };`. The configuration block is passed
+/// through as is, since named parses it as zone options.
+fn addzone_command(
+    zone: &str,
+    class: Option<&str>,
+    view: Option<&str>,
+    config: &str,
+) -> Result<String, RndcError> {
+    let config = config.trim();
+    if !config.starts_with('{') || !(config.ends_with('}') || config.ends_with("};")) {
+        return Err(RndcError::InvalidArgument(format!(
+            "Zone configuration must be a {{ ... }} block: {}",
+            config
+        )));
+    }
+    if let Some(c) = config
+        .chars()
+        .find(|c| c.is_control() && !c.is_whitespace())
+    {
+        return Err(RndcError::InvalidArgument(format!(
+            "Zone configuration contains invalid character {:?}",
+            c
+        )));
+    }
+
+    let mut line = zone_command("addzone", zone, class, view)?;
+    line.push(' ');
+    line.push_str(&config.replace(['\n', '\r', '\t'], " "));
+    Ok(line)
+}
+
+fn delzone_command(
+    zone: &str,
+    class: Option<&str>,
+    view: Option<&str>,
+    clean: bool,
+) -> Result<String, RndcError> {
+    let mut args = zone_args(zone, class, view)?;
+    if clean {
+        args.insert(0, "-clean");
+    }
+    command_line("delzone", &args)
+}
+
+impl RndcClient {
+    /// Run `rndc zonestatus` and parse the result.
+    pub fn zonestatus(
+        &self,
+        zone: &str,
+        class: Option<&str>,
+        view: Option<&str>,
+    ) -> Result<ZoneStatus, RndcError> {
+        let res = expect_success(
+            self.rndc_command(&zone_command("zonestatus", zone, class, view)?)?,
+            "zonestatus",
+        )?;
+        Ok(ZoneStatus::parse(res.text.as_deref().unwrap_or_default()))
+    }
+
+    /// Suspend dynamic updates to `zone` (`rndc freeze`).
+    pub fn freeze(
+        &self,
+        zone: &str,
+        class: Option<&str>,
+        view: Option<&str>,
+    ) -> Result<RndcResult, RndcError> {
+        self.rndc_command(&zone_command("freeze", zone, class, view)?)
+    }
+
+    /// Reload `zone` from disk and resume dynamic updates (`rndc thaw`).
+    pub fn thaw(
+        &self,
+        zone: &str,
+        class: Option<&str>,
+        view: Option<&str>,
+    ) -> Result<RndcResult, RndcError> {
+        self.rndc_command(&zone_command("thaw", zone, class, view)?)
+    }
+
+    /// Add a zone at runtime (`rndc addzone`). `config` is the zone's
+    /// options block, e.g. `{ type primary; file "example.com.db"; };`.
+    pub fn addzone(
+        &self,
+        zone: &str,
+        class: Option<&str>,
+        view: Option<&str>,
+        config: &str,
+    ) -> Result<RndcResult, RndcError> {
+        self.rndc_command(&addzone_command(zone, class, view, config)?)
+    }
+
+    /// Remove a zone added with `addzone` (`rndc delzone`). With `clean`,
+    /// the zone's files are removed as well.
+    pub fn delzone(
+        &self,
+        zone: &str,
+        class: Option<&str>,
+        view: Option<&str>,
+        clean: bool,
+    ) -> Result<RndcResult, RndcError> {
+        self.rndc_command(&delzone_command(zone, class, view, clean)?)
+    }
+
+    /// Freeze `zone` for manual editing. The zone is thawed again when the
+    /// returned guard is committed or dropped.
+    ///
+    /// A dry run records `freeze` without reading the zone status, and the
+    /// guard does not thaw the zone when dropped.
+    pub fn freeze_guard(
+        &self,
+        zone: &str,
+        class: Option<&str>,
+        view: Option<&str>,
+    ) -> Result<ZoneFreezeGuard<'_>, RndcError> {
+        expect_success(self.freeze(zone, class, view)?, "freeze")?;
+
+        let mut guard = ZoneFreezeGuard {
+            client: self,
+            zone: zone.to_string(),
+            class: class.map(str::to_string),
+            view: view.map(str::to_string),
+            status: ZoneStatus::default(),
+            thawed: false,
+        };
+        // a dry run has no zone to read or thaw
+        if self.is_dry_run() {
+            guard.thawed = true;
+            return Ok(guard);
+        }
+        // A failure here still thaws the zone through the guard's drop.
+        guard.status = self.zonestatus(zone, class, view)?;
+
+        Ok(guard)
+    }
+}
+
+/// Keeps a zone frozen while its file is edited by hand, and thaws it when
+/// committed or dropped.
+#[derive(Debug)]
+pub struct ZoneFreezeGuard<'a> {
+    client: &'a RndcClient,
+    zone: String,
+    class: Option<String>,
+    view: Option<String>,
+    status: ZoneStatus,
+    thawed: bool,
+}
+impl ZoneFreezeGuard<'_> {
+    /// The zone status read right after freezing.
+    pub fn status(&self) -> &ZoneStatus {
+        &self.status
+    }
+
+    /// Path of the zone file to edit, as reported by `zonestatus`.
+    pub fn zone_file(&self) -> Option<&str> {
+        self.status.zone_file()
+    }
+
+    /// Thaw the zone now and report the result, instead of silently
+    /// ignoring it on drop.
+    pub fn commit(mut self) -> Result<RndcResult, RndcError> {
+        self.thaw_inner()
+    }
+
+    fn thaw_inner(&mut self) -> Result<RndcResult, RndcError> {
+        self.thawed = true;
+        let res = self
+            .client
+            .thaw(&self.zone, self.class.as_deref(), self.view.as_deref())?;
+        expect_success(res, "thaw")
+    }
+}
+impl Drop for ZoneFreezeGuard<'_> {
+    fn drop(&mut self) {
+        if !self.thawed {
+            let _ = self.thaw_inner();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_zonestatus() {
+        let text = "name: example.com\n\
+                    type: primary\n\
+                    files: /var/lib/bind/example.com.db, /var/lib/bind/keys.inc\n\
+                    serial: 2024010101\n\
+                    nodes: 12\n\
+                    last loaded: Mon, 11 Aug 2025 13:32:16 GMT\n\
+                    secure: no\n\
+                    dynamic: yes\n\
+                    frozen: yes\n\
+                    reconfigurable via modzone: no\n\
+                    expires: Thu, 01 Jan 1970 00:01:40 GMT";
+        let status = ZoneStatus::parse(text);
+
+        assert_eq!(status.name.as_deref(), Some("example.com"));
+        assert_eq!(status.zone_type.as_deref(), Some("primary"));
+        assert_eq!(status.zone_file(), Some("/var/lib/bind/example.com.db"));
+        assert_eq!(status.files.len(), 2);
+        assert_eq!(status.serial, Some(2024010101));
+        assert_eq!(status.nodes, Some(12));
+        assert_eq!(status.dynamic, Some(true));
+        assert_eq!(status.frozen, Some(true));
+        assert_eq!(status.secure, Some(false));
+        assert_eq!(
+            status.fields.get("last loaded").map(String::as_str),
+            Some("Mon, 11 Aug 2025 13:32:16 GMT")
+        );
+        assert_eq!(
+            status.expires(),
+            Some(SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(100))
+        );
+    }
+
+    #[test]
+    fn test_zone_command() {
+        assert_eq!(
+            zone_command("freeze", "example.com", None, Some("internal")).unwrap(),
+            "freeze example.com IN internal"
+        );
+        assert!(zone_command("thaw", "", None, None).is_err());
+    }
+
+    #[test]
+    fn test_addzone_command() {
+        assert_eq!(
+            addzone_command(
+                "example.com",
+                None,
+                Some("internal"),
+                "{ type primary;\n  file \"example.com.db\"; };"
+            )
+            .unwrap(),
+            "addzone example.com IN internal { type primary;   file \"example.com.db\"; };"
+        );
+        assert!(addzone_command("example.com", None, None, "type primary;").is_err());
+        assert!(addzone_command("example.com", None, None, "{ type primary; }\0").is_err());
+        assert_eq!(
+            delzone_command("example.com", None, None, true).unwrap(),
+            "delzone -clean example.com"
+        );
+    }
+}
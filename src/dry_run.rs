@@ -0,0 +1,196 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use crate::audit::AuditEvent;
+use crate::error::RndcError;
+use crate::internal::value::format_table;
+use crate::internal::{decoder, utils};
+use crate::{RndcClient, RndcResult};
+
+/// A command that a dry-run client built and signed instead of sending.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct DryRunRecord {
+    pub command: String,
+    /// Name of the key the message was signed with.
+    pub key_name: String,
+    /// The decoded message, one `name: value` line per field.
+    pub decoded: String,
+    /// Hex dump of the signed wire message.
+    pub hex_dump: String,
+}
+
+impl RndcClient {
+    /// Build and sign every command as usual, but record it instead of
+    /// connecting, and answer with a successful synthetic result. Sessions
+    /// and pools created from the client are dry runs as well.
+    pub fn with_dry_run(mut self) -> Self {
+        self.dry_run = Some(Arc::new(Mutex::new(Vec::new())));
+        self
+    }
+
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run.is_some()
+    }
+
+    /// The commands recorded so far by a dry-run client and its clones.
+    pub fn dry_run_log(&self) -> Vec<DryRunRecord> {
+        match &self.dry_run {
+            Some(log) => log.lock().unwrap().clone(),
+            None => Vec::new(),
+        }
+    }
+
+    pub(crate) fn dry_run_command(
+        &self,
+        command: &str,
+        event: &mut AuditEvent,
+    ) -> Result<RndcResult, RndcError> {
+        let key = self.active_key()?;
+        event.key_name = Some(key.name.clone());
+        event.dry_run = true;
+
+        let ser = rand::random();
+        event.serial = Some(ser);
+        let msg = Self::build_message(command, &key.algorithm, &key.secret, None, ser)?;
+
+        let record = DryRunRecord {
+            command: command.to_string(),
+            key_name: key.name,
+            decoded: format_table(&decoder::decode(&msg)?),
+            hex_dump: utils::hex_dump(&msg),
+        };
+        if let Some(log) = &self.dry_run {
+            log.lock().unwrap().push(record);
+        }
+
+        Ok(RndcResult {
+            result: true,
+            result_code: None,
+            text: Some(format!("dry run: {}", command)),
+            err: None,
+            started: SystemTime::UNIX_EPOCH,
+            elapsed: Duration::ZERO,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audit::AuditObserver;
+    use crate::{CommandPolicy, RndcPool, RndcPoolConfig};
+
+    #[derive(Debug, Default)]
+    struct Recorder(Mutex<Vec<AuditEvent>>);
+
+    impl AuditObserver for Recorder {
+        fn on_command(&self, event: &AuditEvent) {
+            self.0.lock().unwrap().push(event.clone());
+        }
+    }
+
+    fn client() -> RndcClient {
+        // nothing listens on port 1, so any connection attempt would fail
+        RndcClient::new("127.0.0.1:1", "sha256", "YmluZGl6cg==")
+            .unwrap()
+            .with_dry_run()
+    }
+
+    #[test]
+    fn test_dry_run_records_instead_of_sending() {
+        let recorder = Arc::new(Recorder::default());
+        let client = client().with_observer(recorder.clone());
+
+        let res = client.rndc_command("reload example.com").unwrap();
+        assert!(res.result);
+        assert_eq!(res.result_code, None);
+        assert_eq!(res.text.as_deref(), Some("dry run: reload example.com"));
+
+        let log = client.clone().dry_run_log();
+        assert_eq!(log.len(), 1);
+        assert_eq!(log[0].command, "reload example.com");
+        assert_eq!(log[0].key_name, "rndc-key");
+        assert!(log[0].decoded.starts_with("_auth:\n  hsha: <89 bytes> a3"));
+        assert!(
+            log[0]
+                .decoded
+                .contains("_data:\n  type: \"reload example.com\"\n")
+        );
+        assert!(log[0].hex_dump.starts_with("00000000  00 00 00 "));
+
+        let events = recorder.0.lock().unwrap();
+        assert!(events[0].dry_run);
+        assert_eq!(events[0].result_code, None);
+    }
+
+    #[test]
+    fn test_dry_run_sessions_and_pools() {
+        let client = client();
+
+        let mut session = client.session().unwrap();
+        assert!(!session.is_connected());
+        assert!(session.rndc_command("status").unwrap().result);
+
+        let pool = RndcPool::new(client.clone(), RndcPoolConfig::default()).unwrap();
+        assert!(pool.rndc_command("flush").unwrap().result);
+
+        assert_eq!(commands(&client), ["status", "flush"]);
+    }
+
+    fn commands(client: &RndcClient) -> Vec<String> {
+        client
+            .dry_run_log()
+            .into_iter()
+            .map(|r| r.command)
+            .collect()
+    }
+
+    #[test]
+    fn test_dry_run_querylog_guard() {
+        let client = client();
+        let guard = client.querylog_guard(true).unwrap();
+        assert!(guard.previous());
+        drop(guard);
+        assert_eq!(commands(&client), ["querylog on"]);
+
+        client.trace_guard(3).unwrap().restore().unwrap();
+        assert_eq!(commands(&client), ["querylog on", "trace 3"]);
+    }
+
+    #[test]
+    fn test_dry_run_freeze_guard() {
+        let client = client();
+        let guard = client.freeze_guard("example.com", None, None).unwrap();
+        assert_eq!(guard.status(), &crate::ZoneStatus::default());
+        drop(guard);
+        assert_eq!(commands(&client), ["freeze example.com"]);
+    }
+
+    #[test]
+    fn test_dry_run_wait_for_signing() {
+        let client = client();
+        let status = client
+            .wait_for_signing(
+                "example.com",
+                None,
+                None,
+                std::time::Duration::from_secs(1),
+                std::time::Duration::from_secs(1),
+            )
+            .unwrap();
+        assert!(status.is_complete());
+        assert!(client.dry_run_log().is_empty());
+    }
+
+    #[test]
+    fn test_dry_run_applies_policy() {
+        let client = client().with_policy(CommandPolicy::new().deny_verbs(&["stop"]));
+
+        assert!(matches!(
+            client.rndc_command("stop"),
+            Err(RndcError::PolicyDenied(_))
+        ));
+        assert!(client.dry_run_log().is_empty());
+    }
+}
@@ -5,8 +5,14 @@ pub enum RndcError {
     InvalidAlgorithm(String),
     Base64DecodeError(String),
     NetworkError(String),
+    ConnectionClosed(String),
     EncodingError(String),
     DecodingError(String),
+    InvalidArgument(String),
+    CommandFailed(String),
+    Timeout(String),
+    InvalidSignature(String),
+    PolicyDenied(String),
 }
 impl fmt::Display for RndcError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -14,8 +20,14 @@ impl fmt::Display for RndcError {
             RndcError::InvalidAlgorithm(msg) => write!(f, "Invalid algorithm: {}", msg),
             RndcError::Base64DecodeError(msg) => write!(f, "Base64 decode error: {}", msg),
             RndcError::NetworkError(msg) => write!(f, "Network error: {}", msg),
+            RndcError::ConnectionClosed(msg) => write!(f, "Connection closed: {}", msg),
             RndcError::EncodingError(msg) => write!(f, "Encoding error: {}", msg),
             RndcError::DecodingError(msg) => write!(f, "Decoding error: {}", msg),
+            RndcError::InvalidArgument(msg) => write!(f, "Invalid argument: {}", msg),
+            RndcError::CommandFailed(msg) => write!(f, "Command failed: {}", msg),
+            RndcError::Timeout(msg) => write!(f, "Timeout: {}", msg),
+            RndcError::InvalidSignature(msg) => write!(f, "Invalid signature: {}", msg),
+            RndcError::PolicyDenied(msg) => write!(f, "Policy denied: {}", msg),
         }
     }
 }
@@ -0,0 +1,214 @@
+use std::fmt;
+
+use indexmap::IndexMap;
+
+use crate::error::RndcError;
+use crate::internal::constants::{
+    ISCCC_ALG_HMAC_MD5, ISCCC_ALG_HMAC_SHA1, ISCCC_ALG_HMAC_SHA224, ISCCC_ALG_HMAC_SHA256,
+    ISCCC_ALG_HMAC_SHA384, ISCCC_ALG_HMAC_SHA512,
+};
+use crate::internal::value::{RNDCValue, format_table};
+use crate::internal::{decoder, encoder, utils};
+use crate::key::RndcKey;
+
+/// The decoded structure of a raw control channel packet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct PacketInspection {
+    /// The length prefix, which counts every byte after itself.
+    pub length: u32,
+    pub version: u32,
+    /// Signature algorithm named by `_auth`, like `hmac-sha256`.
+    pub algorithm: Option<String>,
+    /// The base64 signature, without the algorithm byte and NUL padding.
+    pub signature: Option<String>,
+    /// Whether the signature matches the key given to `inspect`, if any.
+    pub signature_valid: Option<bool>,
+    /// `_ctrl` fields in wire order, with `_tim` and `_exp` shown as dates.
+    pub ctrl: Vec<(String, String)>,
+    /// `_data` and any other sections, one `name: value` line per field.
+    pub data: String,
+}
+
+/// Decode a raw packet, such as one cut out of a packet capture, and check
+/// its signature against `key` when one is given.
+pub fn inspect(packet: &[u8], key: Option<&RndcKey>) -> Result<PacketInspection, RndcError> {
+    let mut msg = decoder::decode(packet)?;
+
+    let (algorithm, signature) = match msg.get("_auth").and_then(RNDCValue::as_table) {
+        Some(auth) => auth_signature(auth),
+        None => (None, None),
+    };
+    let signature_valid = key.map(|key| encoder::verify(&msg, &key.algorithm, &key.secret).is_ok());
+
+    let ctrl = match msg.get("_ctrl").and_then(RNDCValue::as_table) {
+        Some(ctrl) => ctrl
+            .iter()
+            .map(|(name, value)| (name.clone(), ctrl_value(name, value)))
+            .collect(),
+        None => Vec::new(),
+    };
+
+    msg.shift_remove("_auth");
+    msg.shift_remove("_ctrl");
+
+    Ok(PacketInspection {
+        length: u32::from_be_bytes(packet[0..4].try_into().unwrap()),
+        version: u32::from_be_bytes(packet[4..8].try_into().unwrap()),
+        algorithm,
+        signature,
+        signature_valid,
+        ctrl,
+        data: format_table(&msg),
+    })
+}
+
+fn algorithm_name(code: u8) -> Option<&'static str> {
+    match code {
+        ISCCC_ALG_HMAC_MD5 => Some("hmac-md5"),
+        ISCCC_ALG_HMAC_SHA1 => Some("hmac-sha1"),
+        ISCCC_ALG_HMAC_SHA224 => Some("hmac-sha224"),
+        ISCCC_ALG_HMAC_SHA256 => Some("hmac-sha256"),
+        ISCCC_ALG_HMAC_SHA384 => Some("hmac-sha384"),
+        ISCCC_ALG_HMAC_SHA512 => Some("hmac-sha512"),
+        _ => None,
+    }
+}
+
+fn auth_signature(auth: &IndexMap<String, RNDCValue>) -> (Option<String>, Option<String>) {
+    let text = |sig: &[u8]| {
+        let end = sig.iter().position(|&b| b == 0).unwrap_or(sig.len());
+        String::from_utf8_lossy(&sig[..end]).into_owned()
+    };
+
+    match auth.get("hmd5").or_else(|| auth.get("hsha")) {
+        Some(RNDCValue::String(sig)) | Some(RNDCValue::Binary(sig)) => {
+            if auth.contains_key("hmd5") {
+                (Some("hmac-md5".to_string()), Some(text(sig)))
+            } else {
+                // hsha starts with the algorithm code
+                let name = sig.first().map(|&code| {
+                    algorithm_name(code)
+                        .map(str::to_string)
+                        .unwrap_or_else(|| format!("unknown ({})", code))
+                });
+                (name, sig.get(1..).map(text))
+            }
+        }
+        _ => (None, None),
+    }
+}
+
+fn ctrl_value(name: &str, value: &RNDCValue) -> String {
+    let text = value.as_str().filter(|s| !s.chars().any(char::is_control));
+    match (name, text) {
+        ("_tim" | "_exp", Some(text)) => match text.parse::<u64>() {
+            Ok(secs) => format!("{} ({})", text, utils::format_unix_time(secs)),
+            Err(_) => text.to_string(),
+        },
+        (_, Some(text)) => text.to_string(),
+        _ => match value {
+            RNDCValue::String(b) | RNDCValue::Binary(b) => {
+                let hex: String = b.iter().map(|byte| format!("{:02x}", byte)).collect();
+                format!("<{} bytes> {}", b.len(), hex)
+            }
+            RNDCValue::Table(_) => "<table>".to_string(),
+            RNDCValue::List(list) => format!("<list of {}>", list.len()),
+        },
+    }
+}
+
+impl fmt::Display for PacketInspection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "length:    {} bytes", self.length)?;
+        writeln!(f, "version:   {}", self.version)?;
+        writeln!(
+            f,
+            "algorithm: {}",
+            self.algorithm.as_deref().unwrap_or("none")
+        )?;
+        let valid = match self.signature_valid {
+            Some(true) => "valid",
+            Some(false) => "INVALID",
+            None => "not checked",
+        };
+        match &self.signature {
+            Some(sig) => writeln!(f, "signature: {} ({})", sig, valid)?,
+            None => writeln!(f, "signature: none")?,
+        }
+        writeln!(f, "_ctrl:")?;
+        for (name, value) in &self.ctrl {
+            writeln!(f, "  {}: {}", name, value)?;
+        }
+        f.write_str(&self.data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RndcClient;
+
+    fn packet(algorithm: &str) -> Vec<u8> {
+        let client = RndcClient::new("127.0.0.1:953", algorithm, "YmluZGl6cg==").unwrap();
+        let mut buf = Vec::new();
+        client
+            .encode_command_into("reload example.com", &mut buf)
+            .unwrap();
+        buf
+    }
+
+    #[test]
+    fn test_inspect_sha256() {
+        let packet = packet("sha256");
+        let right = RndcKey::new("rndc-key", "sha256", "YmluZGl6cg==").unwrap();
+        let wrong = RndcKey::new("rndc-key", "sha256", "b3RoZXI=").unwrap();
+
+        let unchecked = inspect(&packet, None).unwrap();
+        assert_eq!(unchecked.length as usize, packet.len() - 4);
+        assert_eq!(unchecked.version, 1);
+        assert_eq!(unchecked.algorithm.as_deref(), Some("hmac-sha256"));
+        assert_eq!(unchecked.signature.as_ref().unwrap().len(), 44);
+        assert_eq!(unchecked.signature_valid, None);
+        let names: Vec<&str> = unchecked.ctrl.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(names, ["_ser", "_tim", "_exp"]);
+        assert!(unchecked.ctrl[1].1.ends_with(" UTC)"));
+        assert_eq!(unchecked.data, "_data:\n  type: \"reload example.com\"\n");
+
+        assert_eq!(
+            inspect(&packet, Some(&right)).unwrap().signature_valid,
+            Some(true)
+        );
+        assert_eq!(
+            inspect(&packet, Some(&wrong)).unwrap().signature_valid,
+            Some(false)
+        );
+
+        let text = unchecked.to_string();
+        assert!(text.starts_with("length:    "));
+        assert!(text.contains("algorithm: hmac-sha256\n"));
+        assert!(text.contains(" (not checked)\n_ctrl:\n  _ser: "));
+    }
+
+    #[test]
+    fn test_inspect_md5_and_garbage() {
+        let packet = packet("md5");
+        let key = RndcKey::new("rndc-key", "md5", "YmluZGl6cg==").unwrap();
+        let other = RndcKey::new("rndc-key", "sha1", "YmluZGl6cg==").unwrap();
+
+        let inspection = inspect(&packet, Some(&key)).unwrap();
+        assert_eq!(inspection.algorithm.as_deref(), Some("hmac-md5"));
+        assert_eq!(inspection.signature.as_ref().unwrap().len(), 22);
+        assert_eq!(inspection.signature_valid, Some(true));
+        assert_eq!(
+            inspect(&packet, Some(&other)).unwrap().signature_valid,
+            Some(false)
+        );
+
+        assert!(matches!(
+            inspect(&packet[..packet.len() - 1], None),
+            Err(RndcError::DecodingError(_))
+        ));
+        assert!(inspect(b"\0\0", None).is_err());
+    }
+}
@@ -0,0 +1,244 @@
+use crate::error::RndcError;
+
+/// Check that `name` is a syntactically valid domain name in presentation
+/// format (as accepted by `flushname`, `flushtree`, `addzone`, ...).
+pub(crate) fn validate_domain_name(name: &str) -> Result<(), RndcError> {
+    if name.is_empty() {
+        return Err(RndcError::InvalidArgument(
+            "Domain name must not be empty".to_string(),
+        ));
+    }
+    if name == "." {
+        return Ok(());
+    }
+
+    let trimmed = name.strip_suffix('.').unwrap_or(name);
+    if trimmed.len() > 253 {
+        return Err(RndcError::InvalidArgument(format!(
+            "Domain name is too long: {}",
+            name
+        )));
+    }
+
+    for label in trimmed.split('.') {
+        if label.is_empty() {
+            return Err(RndcError::InvalidArgument(format!(
+                "Domain name contains an empty label: {}",
+                name
+            )));
+        }
+        if label.len() > 63 {
+            return Err(RndcError::InvalidArgument(format!(
+                "Domain name label is longer than 63 octets: {}",
+                label
+            )));
+        }
+        if let Some(c) = label
+            .chars()
+            .find(|c| !(c.is_ascii_alphanumeric() || *c == '-' || *c == '_' || *c == '*'))
+        {
+            return Err(RndcError::InvalidArgument(format!(
+                "Domain name contains invalid character {:?}: {}",
+                c, name
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Quote a single command argument so that named's lexer reads it back as
+/// one token. Arguments that cannot be represented safely are rejected.
+pub(crate) fn quote_arg(arg: &str) -> Result<String, RndcError> {
+    if arg.is_empty() {
+        return Err(RndcError::InvalidArgument(
+            "Command argument must not be empty".to_string(),
+        ));
+    }
+    if let Some(c) = arg
+        .chars()
+        .find(|c| c.is_control() || *c == '"' || *c == '\\')
+    {
+        return Err(RndcError::InvalidArgument(format!(
+            "Command argument contains invalid character {:?}: {}",
+            c, arg
+        )));
+    }
+
+    if arg
+        .chars()
+        .any(|c| c.is_whitespace() || matches!(c, '{' | '}' | ';' | '(' | ')'))
+    {
+        Ok(format!("\"{}\"", arg))
+    } else {
+        Ok(arg.to_string())
+    }
+}
+
+/// Join a command verb and its arguments into a single command line.
+pub(crate) fn command_line(verb: &str, args: &[&str]) -> Result<String, RndcError> {
+    let mut line = verb.to_string();
+    for arg in args {
+        line.push(' ');
+        line.push_str(&quote_arg(arg)?);
+    }
+    Ok(line)
+}
+
+/// Join the words of a command, such as the arguments of a command line
+/// program, into a command line that named splits into the same words.
+///
+/// Words with whitespace or special characters are quoted. A word starting
+/// with `{` begins a configuration block, as for `addzone`, which is passed
+/// on unchanged together with the words after it.
+pub fn join_command<S: AsRef<str>>(words: &[S]) -> Result<String, RndcError> {
+    let Some((verb, rest)) = words.split_first() else {
+        return Err(RndcError::InvalidArgument(
+            "Command must not be empty".to_string(),
+        ));
+    };
+    let rest: Vec<&str> = rest.iter().map(AsRef::as_ref).collect();
+    let block = rest
+        .iter()
+        .position(|word| word.starts_with('{'))
+        .unwrap_or(rest.len());
+
+    let mut line = command_line(&quote_arg(verb.as_ref())?, &rest[..block])?;
+    for word in &rest[block..] {
+        line.push(' ');
+        line.push_str(word);
+    }
+    Ok(line)
+}
+
+/// Split a command line into its words, the inverse of `command_line`.
+/// Double-quoted words keep their whitespace and lose the quotes.
+pub(crate) fn split_command(line: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quoted = false;
+
+    for c in line.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                in_word = true;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            c => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+
+    words
+}
+
+/// Build the `zone [class [view]]` argument list used by zone commands.
+/// A view without a class implies class `IN`.
+pub(crate) fn zone_args<'a>(
+    zone: &'a str,
+    class: Option<&'a str>,
+    view: Option<&'a str>,
+) -> Result<Vec<&'a str>, RndcError> {
+    validate_domain_name(zone)?;
+    let mut args = vec![zone];
+    match (class, view) {
+        (Some(class), Some(view)) => args.extend([class, view]),
+        (Some(class), None) => args.push(class),
+        (None, Some(view)) => args.extend(["IN", view]),
+        (None, None) => {}
+    }
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_domain_name() {
+        assert!(validate_domain_name("example.com").is_ok());
+        assert!(validate_domain_name("example.com.").is_ok());
+        assert!(validate_domain_name(".").is_ok());
+        assert!(validate_domain_name("_dmarc.example.com").is_ok());
+
+        assert!(validate_domain_name("").is_err());
+        assert!(validate_domain_name("example..com").is_err());
+        assert!(validate_domain_name("exa mple.com").is_err());
+        assert!(validate_domain_name("example.com; flush").is_err());
+        assert!(validate_domain_name(&format!("{}.com", "a".repeat(64))).is_err());
+    }
+
+    #[test]
+    fn test_quote_arg() {
+        assert_eq!(quote_arg("internal").unwrap(), "internal");
+        assert_eq!(quote_arg("my view").unwrap(), "\"my view\"");
+        assert!(quote_arg("").is_err());
+        assert!(quote_arg("bad\"view").is_err());
+        assert!(quote_arg("line\nbreak").is_err());
+    }
+
+    #[test]
+    fn test_command_line() {
+        assert_eq!(command_line("flush", &[]).unwrap(), "flush");
+        assert_eq!(
+            command_line("flushname", &["example.com", "my view"]).unwrap(),
+            "flushname example.com \"my view\""
+        );
+    }
+
+    #[test]
+    fn test_join_command() {
+        assert_eq!(
+            join_command(&["reload", "example.com", "IN", "my view"]).unwrap(),
+            "reload example.com IN \"my view\""
+        );
+        assert_eq!(
+            join_command(&["addzone", "a.test", "{ type primary;", "file \"a.db\"; };"]).unwrap(),
+            "addzone a.test { type primary; file \"a.db\"; };"
+        );
+        assert!(join_command::<&str>(&[]).is_err());
+        assert!(join_command(&["reload", "bad\"zone"]).is_err());
+    }
+
+    #[test]
+    fn test_split_command() {
+        assert!(split_command("  ").is_empty());
+        assert_eq!(
+            split_command("flushname example.com \"my view\""),
+            ["flushname", "example.com", "my view"]
+        );
+        assert_eq!(
+            split_command(&command_line("reload", &["example.com", "IN", "a b"]).unwrap()),
+            ["reload", "example.com", "IN", "a b"]
+        );
+    }
+
+    #[test]
+    fn test_zone_args() {
+        assert_eq!(
+            zone_args("example.com", None, None).unwrap(),
+            ["example.com"]
+        );
+        assert_eq!(
+            zone_args("example.com", None, Some("internal")).unwrap(),
+            ["example.com", "IN", "internal"]
+        );
+        assert_eq!(
+            zone_args("example.com", Some("CH"), None).unwrap(),
+            ["example.com", "CH"]
+        );
+        assert!(zone_args("bad zone", None, None).is_err());
+    }
+}
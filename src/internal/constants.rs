@@ -15,8 +15,8 @@ pub(crate) const ISCCC_ALG_HMAC_SHA384: u8 = 164;
 pub(crate) const ISCCC_ALG_HMAC_SHA512: u8 = 165;
 
 /// Supported RNDC HMAC Algorithms
-#[derive(Debug, PartialEq, Clone)]
-pub(crate) enum RndcAlg {
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum RndcAlg {
     MD5,
     SHA1,
     SHA224,
@@ -25,7 +25,7 @@ pub(crate) enum RndcAlg {
     SHA512,
 }
 impl RndcAlg {
-    pub(crate) fn from_string(alg: &str) -> Result<Self, RndcError> {
+    pub fn from_string(alg: &str) -> Result<Self, RndcError> {
         match alg {
             "md5" => Ok(RndcAlg::MD5),
             "hmd5" => Ok(RndcAlg::MD5),
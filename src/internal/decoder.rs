@@ -1,100 +1,104 @@
 use byteorder::{BigEndian, ReadBytesExt};
 use indexmap::IndexMap;
-use std::io::{Cursor, Read};
 
 use crate::error::RndcError;
 use crate::internal::constants::{MSGTYPE_BINARYDATA, MSGTYPE_LIST, MSGTYPE_STRING, MSGTYPE_TABLE};
-
-#[allow(dead_code)]
-#[derive(Debug, Clone)]
-pub(crate) enum RNDCPayload {
-    String(String),
-    Binary(Vec<u8>),
-    Table(IndexMap<String, RNDCPayload>),
-    List(Vec<RNDCPayload>),
-}
-
-fn binary_fromwire(cursor: &mut Cursor<&[u8]>, len: usize) -> Result<RNDCPayload, RndcError> {
-    let mut buf = vec![0u8; len];
-    cursor
-        .read_exact(&mut buf)
-        .map_err(|e| RndcError::DecodingError(e.to_string()))?;
-
-    match String::from_utf8(buf.clone()) {
-        Ok(s) => Ok(RNDCPayload::String(s)),
-        Err(_) => Ok(RNDCPayload::Binary(buf)),
-    }
+use crate::internal::value::{RNDCValue, RNDCValueRef};
+
+fn take<'a>(buf: &mut &'a [u8], len: usize) -> Result<&'a [u8], RndcError> {
+    let (head, tail) = buf.split_at_checked(len).ok_or_else(|| {
+        RndcError::DecodingError(format!(
+            "Unexpected end of RNDC buffer (need {} bytes, have {})",
+            len,
+            buf.len()
+        ))
+    })?;
+    *buf = tail;
+    Ok(head)
 }
 
-fn key_fromwire(cursor: &mut Cursor<&[u8]>) -> Result<String, RndcError> {
-    let len = cursor
+fn key_fromwire<'a>(buf: &mut &'a [u8]) -> Result<&'a str, RndcError> {
+    let len = buf
         .read_u8()
         .map_err(|e| RndcError::DecodingError(e.to_string()))? as usize;
-    let mut buf = vec![0u8; len];
-    cursor
-        .read_exact(&mut buf)
-        .map_err(|e| RndcError::DecodingError(e.to_string()))?;
-    String::from_utf8(buf).map_err(|e| RndcError::DecodingError(e.to_string()))
+    let key = take(buf, len)?;
+    std::str::from_utf8(key).map_err(|e| RndcError::DecodingError(e.to_string()))
 }
 
-fn value_fromwire(cursor: &mut Cursor<&[u8]>) -> Result<RNDCPayload, RndcError> {
-    let typ = cursor
+fn value_fromwire<'a>(buf: &mut &'a [u8]) -> Result<RNDCValueRef<'a>, RndcError> {
+    let typ = buf
         .read_u8()
         .map_err(|e| RndcError::DecodingError(e.to_string()))?;
-    let len = cursor
+    let len = buf
         .read_u32::<BigEndian>()
         .map_err(|e| RndcError::DecodingError(e.to_string()))? as usize;
-    let pos = cursor.position() as usize;
-
-    let slice = &cursor.get_ref()[pos..pos + len];
-    let mut sub_cursor = Cursor::new(slice);
+    let data = take(buf, len)?;
 
-    let result = match typ {
-        MSGTYPE_STRING | MSGTYPE_BINARYDATA => binary_fromwire(&mut sub_cursor, len),
-        MSGTYPE_TABLE => table_fromwire(&mut sub_cursor).map(RNDCPayload::Table),
-        MSGTYPE_LIST => list_fromwire(&mut sub_cursor).map(RNDCPayload::List),
+    match typ {
+        MSGTYPE_STRING => Ok(RNDCValueRef::String(data)),
+        MSGTYPE_BINARYDATA => Ok(RNDCValueRef::Binary(data)),
+        MSGTYPE_TABLE => table_fromwire(data).map(RNDCValueRef::Table),
+        MSGTYPE_LIST => list_fromwire(data).map(RNDCValueRef::List),
         _ => Err(RndcError::DecodingError(format!(
             "Unknown RNDC message type: {}",
             typ
         ))),
-    };
-
-    cursor.set_position((pos + len) as u64);
-    result
+    }
 }
 
-fn table_fromwire(cursor: &mut Cursor<&[u8]>) -> Result<IndexMap<String, RNDCPayload>, RndcError> {
+fn table_fromwire(mut buf: &[u8]) -> Result<IndexMap<&str, RNDCValueRef<'_>>, RndcError> {
     let mut map = IndexMap::new();
-    while (cursor.position() as usize) < cursor.get_ref().len() {
-        let key = key_fromwire(cursor)?;
-        let value = value_fromwire(cursor)?;
+    while !buf.is_empty() {
+        let key = key_fromwire(&mut buf)?;
+        let value = value_fromwire(&mut buf)?;
         map.insert(key, value);
     }
     Ok(map)
 }
 
-fn list_fromwire(cursor: &mut Cursor<&[u8]>) -> Result<Vec<RNDCPayload>, RndcError> {
+fn list_fromwire(mut buf: &[u8]) -> Result<Vec<RNDCValueRef<'_>>, RndcError> {
     let mut list = Vec::new();
-    while (cursor.position() as usize) < cursor.get_ref().len() {
-        let value = value_fromwire(cursor)?;
+    while !buf.is_empty() {
+        let value = value_fromwire(&mut buf)?;
         list.push(value);
     }
     Ok(list)
 }
 
-pub(crate) fn decode(buf: &[u8]) -> Result<IndexMap<String, RNDCPayload>, RndcError> {
-    let mut cursor = Cursor::new(buf);
-
-    let len = cursor
+/// Decode a packet without copying any keys or values out of `buf`.
+///
+/// The signature is not checked. Values that need to outlive the packet can
+/// be copied with `RNDCValueRef::into_owned`.
+///
+/// ```
+/// use rndc::{RNDCValueRef, RndcClient, decode_borrowed};
+///
+/// let client = RndcClient::new("127.0.0.1:953", "sha256", "YmluZGl6cg==")?;
+/// let mut packet = Vec::new();
+/// client.encode_command_into("reload example.com", &mut packet)?;
+///
+/// let msg = decode_borrowed(&packet)?;
+/// let command = msg
+///     .get("_data")
+///     .and_then(RNDCValueRef::as_table)
+///     .and_then(|data| data.get("type"))
+///     .and_then(RNDCValueRef::as_str);
+/// assert_eq!(command, Some("reload example.com"));
+/// # Ok::<(), rndc::RndcError>(())
+/// ```
+pub fn decode_borrowed(buf: &[u8]) -> Result<IndexMap<&str, RNDCValueRef<'_>>, RndcError> {
+    let mut rest = buf;
+
+    let len = rest
         .read_u32::<BigEndian>()
         .map_err(|e| RndcError::DecodingError(e.to_string()))? as usize;
-    if len != buf.len() - 4 {
+    if len != rest.len() {
         return Err(RndcError::DecodingError(
             "RNDC buffer length mismatch".to_string(),
         ));
     }
 
-    let version = cursor
+    let version = rest
         .read_u32::<BigEndian>()
         .map_err(|e| RndcError::DecodingError(e.to_string()))?;
     if version != 1 {
@@ -104,7 +108,64 @@ pub(crate) fn decode(buf: &[u8]) -> Result<IndexMap<String, RNDCPayload>, RndcEr
         )));
     }
 
-    let res = table_fromwire(&mut cursor)?;
+    table_fromwire(rest)
+}
+
+pub(crate) fn decode(buf: &[u8]) -> Result<IndexMap<String, RNDCValue>, RndcError> {
+    Ok(decode_borrowed(buf)?
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.into_owned()))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // {"_data": {"type": "status", "blob": [0xff]}}
+    const PACKET: &[u8] = &[
+        0, 0, 0, 42, 0, 0, 0, 1, // length, version
+        5, b'_', b'd', b'a', b't', b'a', 2, 0, 0, 0, 27, // "_data" table
+        4, b't', b'y', b'p', b'e', 0, 0, 0, 0, 6, b's', b't', b'a', b't', b'u', b's', //
+        4, b'b', b'l', b'o', b'b', 1, 0, 0, 0, 1, 0xff,
+    ];
+
+    #[test]
+    fn test_decode_borrowed() {
+        let res = decode_borrowed(PACKET).unwrap();
+        let Some(RNDCValueRef::Table(data)) = res.get("_data") else {
+            panic!("missing _data table");
+        };
+
+        let Some(RNDCValueRef::String(typ)) = data.get("type") else {
+            panic!("missing type");
+        };
+        assert_eq!(*typ, b"status");
+        // the value points into the packet instead of a copy
+        assert!(PACKET.as_ptr_range().contains(&typ.as_ptr()));
+
+        assert!(matches!(
+            data.get("blob"),
+            Some(RNDCValueRef::Binary([0xff]))
+        ));
+    }
+
+    #[test]
+    fn test_decode_owned() {
+        let res = decode(PACKET).unwrap();
+        let Some(RNDCValue::Table(data)) = res.get("_data") else {
+            panic!("missing _data table");
+        };
+        assert!(matches!(data.get("type"), Some(RNDCValue::String(s)) if s == b"status"));
+        assert!(matches!(data.get("blob"), Some(RNDCValue::Binary(b)) if b == &[0xff]));
+    }
 
-    Ok(res)
+    #[test]
+    fn test_decode_truncated() {
+        let mut packet = PACKET.to_vec();
+        // claim a longer _data table than the packet holds
+        packet[18] = 40;
+        assert!(decode_borrowed(&packet).is_err());
+        assert!(decode_borrowed(&PACKET[..6]).is_err());
+    }
 }
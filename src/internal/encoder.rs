@@ -4,196 +4,239 @@ use indexmap::IndexMap;
 
 use super::constants::{
     ISCCC_ALG_HMAC_MD5, ISCCC_ALG_HMAC_SHA1, ISCCC_ALG_HMAC_SHA224, ISCCC_ALG_HMAC_SHA256,
-    ISCCC_ALG_HMAC_SHA384, ISCCC_ALG_HMAC_SHA512, MSGTYPE_BINARYDATA, MSGTYPE_LIST, MSGTYPE_TABLE,
-    RndcAlg,
+    ISCCC_ALG_HMAC_SHA384, ISCCC_ALG_HMAC_SHA512, MSGTYPE_BINARYDATA, MSGTYPE_LIST, MSGTYPE_STRING,
+    MSGTYPE_TABLE, RndcAlg,
 };
 use crate::error::RndcError;
+use crate::internal::value::RNDCValue;
 
-#[allow(dead_code)]
-#[derive(Clone, Debug)]
-pub(crate) enum RNDCValue {
-    Binary(Vec<u8>),
-    Table(IndexMap<String, RNDCValue>),
-    List(Vec<RNDCValue>),
+/// Start a value of `type_byte` and return the offset of its length field,
+/// to be patched by `end_value` once the contents are written.
+fn begin_value(buf: &mut Vec<u8>, type_byte: u8) -> usize {
+    buf.push(type_byte);
+    let len_pos = buf.len();
+    buf.extend_from_slice(&[0u8; 4]);
+    len_pos
+}
+
+fn end_value(buf: &mut [u8], len_pos: usize) {
+    let len = (buf.len() - len_pos - 4) as u32;
+    buf[len_pos..len_pos + 4].copy_from_slice(&len.to_be_bytes());
 }
 
-fn raw_towire(type_byte: u8, buffer: &[u8]) -> Vec<u8> {
-    let mut buf = Vec::with_capacity(5 + buffer.len());
+fn raw_towire(buf: &mut Vec<u8>, type_byte: u8, data: &[u8]) {
     buf.push(type_byte);
-    buf.extend_from_slice(&(buffer.len() as u32).to_be_bytes());
-    buf.extend_from_slice(buffer);
-    buf
+    buf.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    buf.extend_from_slice(data);
+}
+
+fn binary_towire(buf: &mut Vec<u8>, val: &[u8]) {
+    raw_towire(buf, MSGTYPE_BINARYDATA, val)
 }
 
-fn binary_towire(val: &[u8]) -> Vec<u8> {
-    raw_towire(MSGTYPE_BINARYDATA, val)
+fn string_towire(buf: &mut Vec<u8>, val: &[u8]) {
+    raw_towire(buf, MSGTYPE_STRING, val)
 }
 
-fn list_towire(vals: &[RNDCValue]) -> Vec<u8> {
-    let mut bufs = Vec::new();
+fn list_towire(buf: &mut Vec<u8>, vals: &[RNDCValue]) {
+    let len_pos = begin_value(buf, MSGTYPE_LIST);
     for v in vals {
-        bufs.extend(value_towire(v));
+        value_towire(buf, v);
     }
-    raw_towire(MSGTYPE_LIST, &bufs)
+    end_value(buf, len_pos);
 }
 
-fn key_towire(key: &str) -> Vec<u8> {
+fn key_towire(buf: &mut Vec<u8>, key: &str) {
     let key_bytes = key.as_bytes();
-    let mut buf = Vec::with_capacity(1 + key_bytes.len());
     buf.push(key_bytes.len() as u8);
     buf.extend_from_slice(key_bytes);
-    buf
 }
 
-fn value_towire(val: &RNDCValue) -> Vec<u8> {
+fn value_towire(buf: &mut Vec<u8>, val: &RNDCValue) {
     match val {
-        RNDCValue::List(list) => list_towire(list),
-        RNDCValue::Table(map) => table_towire(map, false),
-        RNDCValue::Binary(data) => binary_towire(data),
+        RNDCValue::List(list) => list_towire(buf, list),
+        RNDCValue::Table(map) => table_towire(buf, map, false),
+        RNDCValue::Binary(data) => binary_towire(buf, data),
+        RNDCValue::String(data) => string_towire(buf, data),
     }
 }
 
-fn table_towire(val: &IndexMap<String, RNDCValue>, no_header: bool) -> Vec<u8> {
-    let mut bufs = Vec::new();
+fn table_towire(buf: &mut Vec<u8>, val: &IndexMap<String, RNDCValue>, no_header: bool) {
+    let len_pos = (!no_header).then(|| begin_value(buf, MSGTYPE_TABLE));
     for (key, value) in val.iter() {
-        bufs.extend(key_towire(key));
-        bufs.extend(value_towire(value));
+        // `_auth` is always written by `encode` itself
+        if no_header && key == "_auth" {
+            continue;
+        }
+        key_towire(buf, key);
+        value_towire(buf, value);
     }
-    if no_header {
-        bufs
-    } else {
-        raw_towire(MSGTYPE_TABLE, &bufs)
+    if let Some(len_pos) = len_pos {
+        end_value(buf, len_pos);
+    }
+}
+
+/// Name of the signature entry in `_auth` and the size of its value.
+fn signature_layout(algorithm: &RndcAlg) -> (&'static str, usize) {
+    match algorithm {
+        // base64 of a 16 byte digest without padding
+        RndcAlg::MD5 => ("hmd5", 22),
+        // algorithm code followed by the NUL padded base64 digest
+        _ => ("hsha", 89),
     }
 }
 
 fn make_signature(
     algorithm: &RndcAlg,
     secret: &[u8],
-    message_body: &IndexMap<String, RNDCValue>,
-) -> Result<RNDCValue, RndcError> {
-    let databuf = table_towire(message_body, true);
-
-    let (sig_type, sig_b64, alg_code) = match algorithm {
+    databuf: &[u8],
+) -> Result<Vec<u8>, RndcError> {
+    let (digest, alg_code) = match algorithm {
         RndcAlg::MD5 => {
             let mut mac = Hmac::<md5::Md5>::new_from_slice(secret).map_err(|_| {
                 RndcError::EncodingError("Failed to create HMAC MD5 instance".to_string())
             })?;
 
-            mac.update(&databuf);
-            let digest = mac.finalize().into_bytes();
-            let mut sig_b64 = general_purpose::STANDARD.encode(digest);
-
-            // no padding on hmd5
-            sig_b64 = sig_b64.trim_end_matches('=').to_string();
-            ("hmd5".to_string(), sig_b64, ISCCC_ALG_HMAC_MD5)
+            mac.update(databuf);
+            (mac.finalize().into_bytes().to_vec(), ISCCC_ALG_HMAC_MD5)
         }
         RndcAlg::SHA1 => {
             let mut mac = Hmac::<sha1::Sha1>::new_from_slice(secret).map_err(|_| {
                 RndcError::EncodingError("Failed to create HMAC SHA1 instance".to_string())
             })?;
 
-            mac.update(&databuf);
-            let digest = mac.finalize().into_bytes();
-            (
-                "hsha".to_string(),
-                general_purpose::STANDARD.encode(digest),
-                ISCCC_ALG_HMAC_SHA1,
-            )
+            mac.update(databuf);
+            (mac.finalize().into_bytes().to_vec(), ISCCC_ALG_HMAC_SHA1)
         }
         RndcAlg::SHA224 => {
             let mut mac = Hmac::<sha2::Sha224>::new_from_slice(secret).map_err(|_| {
                 RndcError::EncodingError("Failed to create HMAC SHA224 instance".to_string())
             })?;
 
-            mac.update(&databuf);
-            let digest = mac.finalize().into_bytes();
-            (
-                "hsha".to_string(),
-                general_purpose::STANDARD.encode(digest),
-                ISCCC_ALG_HMAC_SHA224,
-            )
+            mac.update(databuf);
+            (mac.finalize().into_bytes().to_vec(), ISCCC_ALG_HMAC_SHA224)
         }
         RndcAlg::SHA256 => {
             let mut mac = Hmac::<sha2::Sha256>::new_from_slice(secret).map_err(|_| {
                 RndcError::EncodingError("Failed to create HMAC SHA256 instance".to_string())
             })?;
 
-            mac.update(&databuf);
-            let digest = mac.finalize().into_bytes();
-            (
-                "hsha".to_string(),
-                general_purpose::STANDARD.encode(digest),
-                ISCCC_ALG_HMAC_SHA256,
-            )
+            mac.update(databuf);
+            (mac.finalize().into_bytes().to_vec(), ISCCC_ALG_HMAC_SHA256)
         }
         RndcAlg::SHA384 => {
             let mut mac = Hmac::<sha2::Sha384>::new_from_slice(secret).map_err(|_| {
                 RndcError::EncodingError("Failed to create HMAC SHA384 instance".to_string())
             })?;
 
-            mac.update(&databuf);
-            let digest = mac.finalize().into_bytes();
-            (
-                "hsha".to_string(),
-                general_purpose::STANDARD.encode(digest),
-                ISCCC_ALG_HMAC_SHA384,
-            )
+            mac.update(databuf);
+            (mac.finalize().into_bytes().to_vec(), ISCCC_ALG_HMAC_SHA384)
         }
         RndcAlg::SHA512 => {
             let mut mac = Hmac::<sha2::Sha512>::new_from_slice(secret).map_err(|_| {
                 RndcError::EncodingError("Failed to create HMAC SHA512 instance".to_string())
             })?;
 
-            mac.update(&databuf);
-            let digest = mac.finalize().into_bytes();
-            (
-                "hsha".to_string(),
-                general_purpose::STANDARD.encode(digest),
-                ISCCC_ALG_HMAC_SHA512,
-            )
+            mac.update(databuf);
+            (mac.finalize().into_bytes().to_vec(), ISCCC_ALG_HMAC_SHA512)
         }
     };
 
-    let sig_buf = if sig_type == "hmd5" {
-        RNDCValue::Binary(sig_b64.as_bytes().to_vec())
+    let sig_b64 = general_purpose::STANDARD.encode(digest);
+    let (_, sig_len) = signature_layout(algorithm);
+
+    let sig_buf = if *algorithm == RndcAlg::MD5 {
+        // no padding on hmd5
+        sig_b64.trim_end_matches('=').as_bytes().to_vec()
     } else {
-        let mut buf = vec![0u8; 89];
+        let mut buf = vec![0u8; sig_len];
         buf[0] = alg_code;
         buf[1..(1 + sig_b64.len())].copy_from_slice(sig_b64.as_bytes());
-        RNDCValue::Binary(buf)
+        buf
     };
 
-    let mut auth_map = IndexMap::new();
-    auth_map.insert(sig_type, sig_buf);
-
-    Ok(RNDCValue::Table(auth_map))
+    Ok(sig_buf)
 }
 
-pub(crate) fn encode(
-    obj: &mut IndexMap<String, RNDCValue>,
+/// Check the `_auth` signature of a decoded message by re-encoding its
+/// body, which relies on the decoded values keeping their wire types.
+pub(crate) fn verify(
+    obj: &IndexMap<String, RNDCValue>,
     algorithm: &RndcAlg,
     secret: &[u8],
-) -> Result<Vec<u8>, RndcError> {
-    obj.shift_remove("_auth");
-
-    let databuf = table_towire(obj, true);
-
-    let sig_value = make_signature(algorithm, secret, obj)?;
-
-    let mut sig_map = IndexMap::new();
-    sig_map.insert("_auth".to_string(), sig_value);
-    let sigbuf = table_towire(&sig_map, true);
-
-    let length = 8 + sigbuf.len() + databuf.len();
-    let mut res = Vec::with_capacity(length);
+) -> Result<(), RndcError> {
+    let (sig_type, _) = signature_layout(algorithm);
+    let received = match obj
+        .get("_auth")
+        .and_then(RNDCValue::as_table)
+        .and_then(|auth| auth.get(sig_type))
+    {
+        Some(RNDCValue::String(sig)) | Some(RNDCValue::Binary(sig)) => sig,
+        _ => {
+            return Err(RndcError::InvalidSignature(format!(
+                "Message has no {} signature",
+                sig_type
+            )));
+        }
+    };
 
-    res.extend(&(length as u32 - 4).to_be_bytes());
-    res.extend(&1u32.to_be_bytes());
+    let mut body = Vec::new();
+    table_towire(&mut body, obj, true);
+    let expected = make_signature(algorithm, secret, &body)?;
+
+    // compare without short-circuiting on the first differing byte
+    let diff = received
+        .iter()
+        .zip(expected.iter())
+        .fold(received.len() ^ expected.len(), |acc, (a, b)| {
+            acc | (a ^ b) as usize
+        });
+    if diff != 0 {
+        return Err(RndcError::InvalidSignature(
+            "Message signature does not match".to_string(),
+        ));
+    }
 
-    res.extend(&sigbuf);
-    res.extend(&databuf);
+    Ok(())
+}
 
-    Ok(res)
+/// Encode and sign `obj` into `buf`, replacing its previous contents.
+///
+/// The `_auth` table has a fixed size per algorithm, so it is reserved up
+/// front and filled in once the body has been written and signed in place.
+pub(crate) fn encode_into(
+    buf: &mut Vec<u8>,
+    obj: &IndexMap<String, RNDCValue>,
+    algorithm: &RndcAlg,
+    secret: &[u8],
+) -> Result<(), RndcError> {
+    buf.clear();
+
+    // length, patched below
+    buf.extend_from_slice(&[0u8; 4]);
+    buf.extend_from_slice(&1u32.to_be_bytes());
+
+    // _auth = {hmd5|hsha: signature}
+    let (sig_type, sig_len) = signature_layout(algorithm);
+    key_towire(buf, "_auth");
+    let auth_pos = begin_value(buf, MSGTYPE_TABLE);
+    key_towire(buf, sig_type);
+    buf.push(MSGTYPE_BINARYDATA);
+    buf.extend_from_slice(&(sig_len as u32).to_be_bytes());
+    let sig_pos = buf.len();
+    buf.resize(sig_pos + sig_len, 0);
+    end_value(buf, auth_pos);
+
+    let body_pos = buf.len();
+    table_towire(buf, obj, true);
+
+    let sig = make_signature(algorithm, secret, &buf[body_pos..])?;
+    buf[sig_pos..sig_pos + sig_len].copy_from_slice(&sig);
+
+    let length = (buf.len() - 4) as u32;
+    buf[0..4].copy_from_slice(&length.to_be_bytes());
+
+    Ok(())
 }
 
 #[cfg(test)]
@@ -202,31 +245,36 @@ mod tests {
 
     #[test]
     fn test_raw_towire_empty() {
-        let result = raw_towire(2, &[]);
+        let mut result = Vec::new();
+        raw_towire(&mut result, 2, &[]);
         assert_eq!(result, vec![2, 0, 0, 0, 0]);
     }
 
     #[test]
     fn test_raw_towire_string() {
-        let result = raw_towire(1, b"abc");
+        let mut result = Vec::new();
+        raw_towire(&mut result, 1, b"abc");
         assert_eq!(result, vec![1, 0, 0, 0, 3, 97, 98, 99]);
     }
 
     #[test]
     fn test_binary_towire_empty() {
-        let result = binary_towire("".as_bytes());
+        let mut result = Vec::new();
+        binary_towire(&mut result, "".as_bytes());
         assert_eq!(result, vec![1, 0, 0, 0, 0]);
     }
 
     #[test]
     fn test_binary_towire_string() {
-        let result = binary_towire("abc".as_bytes());
+        let mut result = Vec::new();
+        binary_towire(&mut result, "abc".as_bytes());
         assert_eq!(result, vec![1, 0, 0, 0, 3, 97, 98, 99]);
     }
 
     #[test]
     fn test_list_towire_empty() {
-        let result = list_towire(&[]);
+        let mut result = Vec::new();
+        list_towire(&mut result, &[]);
         assert_eq!(result, vec![3, 0, 0, 0, 0]);
     }
 
@@ -236,7 +284,8 @@ mod tests {
             RNDCValue::Binary("abc".as_bytes().to_vec()),
             RNDCValue::Binary("ABC".as_bytes().to_vec()),
         ];
-        let result = list_towire(&values);
+        let mut result = Vec::new();
+        list_towire(&mut result, &values);
         assert_eq!(
             result,
             vec![
@@ -247,7 +296,8 @@ mod tests {
 
     #[test]
     fn test_table_towire_empty() {
-        let result = table_towire(&IndexMap::new(), false);
+        let mut result = Vec::new();
+        table_towire(&mut result, &IndexMap::new(), false);
         assert_eq!(result, vec![2, 0, 0, 0, 0]);
     }
 
@@ -263,7 +313,8 @@ mod tests {
             RNDCValue::Binary("ABC".as_bytes().to_vec()),
         );
 
-        let result = table_towire(&map, false);
+        let mut result = Vec::new();
+        table_towire(&mut result, &map, false);
         assert_eq!(
             result,
             vec![
@@ -273,4 +324,52 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn test_encode_roundtrip() {
+        let mut data = IndexMap::new();
+        data.insert(
+            "type".to_string(),
+            RNDCValue::Binary("status".as_bytes().to_vec()),
+        );
+        data.insert(
+            "text".to_string(),
+            RNDCValue::String("server is up and running".as_bytes().to_vec()),
+        );
+        let mut obj = IndexMap::new();
+        obj.insert("_data".to_string(), RNDCValue::Table(data));
+
+        for (alg, sig_type, sig_len) in [(RndcAlg::MD5, "hmd5", 22), (RndcAlg::SHA256, "hsha", 89)]
+        {
+            let mut buf = Vec::new();
+            encode_into(&mut buf, &obj, &alg, b"secret").unwrap();
+            let decoded = crate::internal::decoder::decode(&buf).unwrap();
+
+            let sig = decoded
+                .get("_auth")
+                .and_then(RNDCValue::as_table)
+                .and_then(|auth| auth.get(sig_type));
+            assert!(matches!(sig, Some(RNDCValue::Binary(b)) if b.len() == sig_len));
+            assert_eq!(decoded.get("_data"), obj.get("_data"));
+
+            // re-encoding the decoded message reproduces the original bytes
+            let mut reencoded = Vec::new();
+            encode_into(&mut reencoded, &decoded, &alg, b"secret").unwrap();
+            assert_eq!(reencoded, buf);
+
+            assert!(verify(&decoded, &alg, b"secret").is_ok());
+            assert!(verify(&decoded, &alg, b"other").is_err());
+        }
+    }
+
+    #[test]
+    fn test_encode_into_reuses_buffer() {
+        let obj = IndexMap::new();
+        let mut fresh = Vec::new();
+        encode_into(&mut fresh, &obj, &RndcAlg::SHA256, b"secret").unwrap();
+
+        let mut reused = vec![0xffu8; 1024];
+        encode_into(&mut reused, &obj, &RndcAlg::SHA256, b"secret").unwrap();
+        assert_eq!(reused, fresh);
+    }
 }
@@ -0,0 +1,43 @@
+//! Files written from observers and connection wrappers, which have no
+//! caller to return a write error to.
+
+use std::fs::File;
+use std::io::{self, Write};
+use std::sync::{Mutex, OnceLock};
+
+/// A file that records are appended and flushed to one at a time.
+///
+/// A failed write does not stop later ones. The first error is kept for
+/// `error`, and every error is logged as a warning when the `tracing`
+/// feature is enabled.
+#[derive(Debug)]
+pub(crate) struct LogFile {
+    file: Mutex<File>,
+    /// What the file holds, for error messages.
+    what: &'static str,
+    error: OnceLock<io::Error>,
+}
+
+impl LogFile {
+    pub(crate) fn new(file: File, what: &'static str) -> Self {
+        LogFile {
+            file: Mutex::new(file),
+            what,
+            error: OnceLock::new(),
+        }
+    }
+
+    pub(crate) fn append(&self, record: &[u8]) {
+        let mut file = self.file.lock().unwrap();
+        if let Err(e) = file.write_all(record).and_then(|_| file.flush()) {
+            let e = io::Error::new(e.kind(), format!("failed to write {}: {}", self.what, e));
+            #[cfg(feature = "tracing")]
+            tracing::warn!("{}", e);
+            let _ = self.error.set(e);
+        }
+    }
+
+    pub(crate) fn error(&self) -> Option<&io::Error> {
+        self.error.get()
+    }
+}
@@ -1,4 +1,9 @@
+pub(crate) mod args;
 pub(crate) mod constants;
 pub(crate) mod decoder;
 pub(crate) mod encoder;
+pub(crate) mod log_file;
+#[cfg(test)]
+pub(crate) mod test_server;
 pub(crate) mod utils;
+pub(crate) mod value;
@@ -0,0 +1,171 @@
+//! A minimal in-process control channel server for unit tests.
+
+use indexmap::IndexMap;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::RndcClient;
+use crate::internal::constants::RndcAlg;
+use crate::internal::value::RNDCValue;
+use crate::internal::{decoder, encoder};
+
+pub(crate) const TEST_SECRET_B64: &str = "YmluZGl6cg==";
+pub(crate) const TEST_SECRET: &[u8] = b"bindizr";
+
+#[derive(Debug, Default)]
+pub(crate) struct ServerState {
+    pub(crate) connections: AtomicUsize,
+    pub(crate) commands: Mutex<Vec<String>>,
+    /// Connections the server has closed.
+    pub(crate) closed: AtomicUsize,
+    /// Record commands, then close the connection without replying.
+    pub(crate) close_before_reply: AtomicBool,
+}
+
+/// Accepts messages signed with `secret` over `algorithm`, answers
+/// the `null` handshake with a nonce and every other command with
+/// `result 0` and the command echoed as `text`. Connections are closed after
+/// `max_commands` commands when set.
+pub(crate) struct TestServer {
+    pub(crate) addr: SocketAddr,
+    pub(crate) state: Arc<ServerState>,
+}
+impl TestServer {
+    pub(crate) fn start(algorithm: RndcAlg, max_commands: Option<usize>) -> Self {
+        Self::start_with_secret(algorithm, TEST_SECRET, max_commands)
+    }
+
+    pub(crate) fn start_with_secret(
+        algorithm: RndcAlg,
+        secret: &'static [u8],
+        max_commands: Option<usize>,
+    ) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let state = Arc::new(ServerState::default());
+
+        let server_state = state.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else { break };
+                server_state.connections.fetch_add(1, Ordering::SeqCst);
+                let state = server_state.clone();
+                let algorithm = algorithm.clone();
+                thread::spawn(move || {
+                    serve(stream, &algorithm, secret, &state, max_commands);
+                    state.closed.fetch_add(1, Ordering::SeqCst);
+                });
+            }
+        });
+
+        TestServer { addr, state }
+    }
+
+    pub(crate) fn client(&self, algorithm: &str) -> RndcClient {
+        RndcClient::new(&self.addr.to_string(), algorithm, TEST_SECRET_B64).unwrap()
+    }
+
+    pub(crate) fn connections(&self) -> usize {
+        self.state.connections.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn commands(&self) -> Vec<String> {
+        self.state.commands.lock().unwrap().clone()
+    }
+
+    /// Run later commands but close the connection instead of replying,
+    /// as if the server went away after executing them.
+    pub(crate) fn close_before_replies(&self) {
+        self.state.close_before_reply.store(true, Ordering::SeqCst);
+    }
+
+    /// Wait until the server has closed `count` connections.
+    pub(crate) fn wait_closed(&self, count: usize) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while self.state.closed.load(Ordering::SeqCst) < count {
+            assert!(
+                Instant::now() < deadline,
+                "server did not close connections"
+            );
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+}
+
+fn text(value: &str) -> RNDCValue {
+    RNDCValue::Binary(value.as_bytes().to_vec())
+}
+
+fn serve(
+    mut stream: TcpStream,
+    algorithm: &RndcAlg,
+    secret: &[u8],
+    state: &ServerState,
+    max: Option<usize>,
+) {
+    let nonce = rand::random::<u32>().to_string();
+    let mut handled = 0;
+
+    while let Ok(packet) = RndcClient::read_packet(&mut stream) {
+        let Ok(msg) = decoder::decode(&packet) else {
+            return;
+        };
+        // named drops the connection on a bad signature
+        if encoder::verify(&msg, algorithm, secret).is_err() {
+            return;
+        }
+
+        let ctrl = msg.get("_ctrl").and_then(RNDCValue::as_table);
+        let command = msg
+            .get("_data")
+            .and_then(RNDCValue::as_table)
+            .and_then(|data| data.get("type"))
+            .and_then(RNDCValue::as_str)
+            .unwrap_or_default()
+            .to_string();
+
+        let mut data = IndexMap::new();
+        data.insert("type".to_string(), text(&command));
+        if command != "null" {
+            let received_nonce = ctrl
+                .and_then(|ctrl| ctrl.get("_nonce"))
+                .and_then(RNDCValue::as_str);
+            if received_nonce != Some(nonce.as_str()) {
+                return;
+            }
+            state.commands.lock().unwrap().push(command.clone());
+            if state.close_before_reply.load(Ordering::SeqCst) {
+                return;
+            }
+            data.insert("result".to_string(), text("0"));
+            data.insert("text".to_string(), text(&command));
+        }
+
+        let mut reply_ctrl = IndexMap::new();
+        if let Some(ser) = ctrl.and_then(|ctrl| ctrl.get("_ser")) {
+            reply_ctrl.insert("_rpl".to_string(), text("1"));
+            reply_ctrl.insert("_ser".to_string(), ser.clone());
+        }
+        reply_ctrl.insert("_nonce".to_string(), text(&nonce));
+
+        let mut reply = IndexMap::new();
+        reply.insert("_ctrl".to_string(), RNDCValue::Table(reply_ctrl));
+        reply.insert("_data".to_string(), RNDCValue::Table(data));
+
+        let mut buf = Vec::new();
+        encoder::encode_into(&mut buf, &reply, algorithm, secret).unwrap();
+        if std::io::Write::write_all(&mut stream, &buf).is_err() {
+            return;
+        }
+
+        if command != "null" {
+            handled += 1;
+            if max.is_some_and(|max| handled >= max) {
+                return;
+            }
+        }
+    }
+}
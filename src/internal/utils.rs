@@ -6,3 +6,167 @@ pub(crate) fn get_timestamp() -> u32 {
         .unwrap()
         .as_secs() as u32
 }
+
+/// Serialize a time as seconds since the Unix epoch, with millisecond
+/// fractions, for scripts that cannot read serde's `SystemTime`.
+#[cfg(feature = "serde")]
+pub(crate) fn serialize_unix_seconds<S: serde::Serializer>(
+    time: &SystemTime,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let millis = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0);
+    serializer.serialize_f64(millis as f64 / 1000.0)
+}
+
+/// Serialize a duration as whole milliseconds.
+#[cfg(feature = "serde")]
+pub(crate) fn serialize_millis<S: serde::Serializer>(
+    duration: &std::time::Duration,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u64(duration.as_millis() as u64)
+}
+
+/// Parse a timestamp as printed by named, e.g.
+/// `Mon, 11 Aug 2025 13:32:16 GMT`.
+pub(crate) fn parse_http_date(value: &str) -> Option<SystemTime> {
+    let mut parts = value.split_whitespace();
+    let _weekday = parts.next()?;
+    let day: u64 = parts.next()?.parse().ok()?;
+    let month = match parts.next()? {
+        "Jan" => 1,
+        "Feb" => 2,
+        "Mar" => 3,
+        "Apr" => 4,
+        "May" => 5,
+        "Jun" => 6,
+        "Jul" => 7,
+        "Aug" => 8,
+        "Sep" => 9,
+        "Oct" => 10,
+        "Nov" => 11,
+        "Dec" => 12,
+        _ => return None,
+    };
+    let year: u64 = parts.next()?.parse().ok()?;
+    let mut time = parts.next()?.split(':').map(|p| p.parse::<u64>().ok());
+    let (hour, min, sec) = (time.next()??, time.next()??, time.next()??);
+    if parts.next() != Some("GMT") || !(1..=31).contains(&day) || year < 1970 {
+        return None;
+    }
+
+    // days since the epoch of the proleptic Gregorian calendar date
+    let (y, m) = if month <= 2 {
+        (year - 1, month + 9)
+    } else {
+        (year, month - 3)
+    };
+    let era = y / 400;
+    let yoe = y - era * 400;
+    let doy = (153 * m + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = (era * 146097 + doe).checked_sub(719468)?;
+
+    let secs = days * 86400 + hour * 3600 + min * 60 + sec;
+    Some(UNIX_EPOCH + std::time::Duration::from_secs(secs))
+}
+
+/// Format seconds since the Unix epoch as `2025-08-11 13:32:16 UTC`.
+pub(crate) fn format_unix_time(secs: u64) -> String {
+    let (days, rem) = (secs / 86400, secs % 86400);
+
+    // civil date from days since the epoch, the inverse of parse_http_date
+    let z = days + 719468;
+    let era = z / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+/// Classic 16-bytes-per-line hex dump with offsets and an ASCII column.
+pub(crate) fn hex_dump(bytes: &[u8]) -> String {
+    let mut out = String::new();
+    for (line, chunk) in bytes.chunks(16).enumerate() {
+        out.push_str(&format!("{:08x} ", line * 16));
+        for i in 0..16 {
+            if i == 8 {
+                out.push(' ');
+            }
+            match chunk.get(i) {
+                Some(byte) => out.push_str(&format!(" {:02x}", byte)),
+                None => out.push_str("   "),
+            }
+        }
+        let ascii: String = chunk
+            .iter()
+            .map(|&b| {
+                if b.is_ascii_graphic() || b == b' ' {
+                    b as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        out.push_str(&format!("  |{}|\n", ascii));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_parse_http_date() {
+        assert_eq!(
+            parse_http_date("Mon, 11 Aug 2025 13:32:16 GMT"),
+            Some(UNIX_EPOCH + Duration::from_secs(1754919136))
+        );
+        assert_eq!(
+            parse_http_date("Thu, 29 Feb 2024 00:00:00 GMT"),
+            Some(UNIX_EPOCH + Duration::from_secs(1709164800))
+        );
+        assert_eq!(
+            parse_http_date("Thu, 01 Jan 1970 00:00:00 GMT"),
+            Some(UNIX_EPOCH)
+        );
+        assert_eq!(parse_http_date("Mon, 11 Foo 2025 13:32:16 GMT"), None);
+        assert_eq!(parse_http_date("yesterday"), None);
+    }
+
+    #[test]
+    fn test_format_unix_time() {
+        assert_eq!(format_unix_time(0), "1970-01-01 00:00:00 UTC");
+        assert_eq!(format_unix_time(1754919136), "2025-08-11 13:32:16 UTC");
+        assert_eq!(format_unix_time(1709164800), "2024-02-29 00:00:00 UTC");
+        assert_eq!(format_unix_time(951868799), "2000-02-29 23:59:59 UTC");
+    }
+
+    #[test]
+    fn test_hex_dump() {
+        assert_eq!(hex_dump(&[]), "");
+        assert_eq!(
+            hex_dump(b"\x00\x00\x00\x8cnull command!!!!x"),
+            "00000000  00 00 00 8c 6e 75 6c 6c  20 63 6f 6d 6d 61 6e 64  |....null command|\n\
+             00000010  21 21 21 21 78                                    |!!!!x|\n"
+        );
+    }
+}
@@ -0,0 +1,111 @@
+use indexmap::IndexMap;
+
+/// An ISCCC value. `String` and `Binary` both hold raw bytes and only differ
+/// in the wire type they were read from or will be written as, so a decoded
+/// message re-encodes to exactly the same bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RNDCValue {
+    String(Vec<u8>),
+    Binary(Vec<u8>),
+    Table(IndexMap<String, RNDCValue>),
+    List(Vec<RNDCValue>),
+}
+impl RNDCValue {
+    /// The value as text, if it is a string or binary value holding UTF-8.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            RNDCValue::String(b) | RNDCValue::Binary(b) => std::str::from_utf8(b).ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_table(&self) -> Option<&IndexMap<String, RNDCValue>> {
+        match self {
+            RNDCValue::Table(map) => Some(map),
+            _ => None,
+        }
+    }
+}
+
+/// A decoded value borrowing from the packet it was read from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RNDCValueRef<'a> {
+    String(&'a [u8]),
+    Binary(&'a [u8]),
+    Table(IndexMap<&'a str, RNDCValueRef<'a>>),
+    List(Vec<RNDCValueRef<'a>>),
+}
+impl<'a> RNDCValueRef<'a> {
+    /// The value as text, if it is a string or binary value holding UTF-8.
+    pub fn as_str(&self) -> Option<&'a str> {
+        match self {
+            RNDCValueRef::String(b) | RNDCValueRef::Binary(b) => std::str::from_utf8(b).ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_table(&self) -> Option<&IndexMap<&'a str, RNDCValueRef<'a>>> {
+        match self {
+            RNDCValueRef::Table(map) => Some(map),
+            _ => None,
+        }
+    }
+
+    pub fn into_owned(self) -> RNDCValue {
+        match self {
+            RNDCValueRef::String(b) => RNDCValue::String(b.to_vec()),
+            RNDCValueRef::Binary(b) => RNDCValue::Binary(b.to_vec()),
+            RNDCValueRef::Table(map) => RNDCValue::Table(
+                map.into_iter()
+                    .map(|(k, v)| (k.to_string(), v.into_owned()))
+                    .collect(),
+            ),
+            RNDCValueRef::List(list) => {
+                RNDCValue::List(list.into_iter().map(RNDCValueRef::into_owned).collect())
+            }
+        }
+    }
+}
+
+/// Render a decoded message as indented `name: value` lines. Values holding
+/// printable text are shown quoted, anything else as hex.
+pub(crate) fn format_table(map: &IndexMap<String, RNDCValue>) -> String {
+    let mut out = String::new();
+    write_table(&mut out, map, 0);
+    out
+}
+
+fn write_table(out: &mut String, map: &IndexMap<String, RNDCValue>, indent: usize) {
+    for (key, value) in map {
+        out.push_str(&"  ".repeat(indent));
+        out.push_str(key);
+        out.push(':');
+        write_value(out, value, indent);
+    }
+}
+
+fn write_value(out: &mut String, value: &RNDCValue, indent: usize) {
+    match value {
+        RNDCValue::String(b) | RNDCValue::Binary(b) => match std::str::from_utf8(b) {
+            Ok(text) if !text.chars().any(char::is_control) => {
+                out.push_str(&format!(" {:?}\n", text));
+            }
+            _ => {
+                let hex: String = b.iter().map(|byte| format!("{:02x}", byte)).collect();
+                out.push_str(&format!(" <{} bytes> {}\n", b.len(), hex));
+            }
+        },
+        RNDCValue::Table(map) => {
+            out.push('\n');
+            write_table(out, map, indent + 1);
+        }
+        RNDCValue::List(list) => {
+            out.push('\n');
+            for item in list {
+                out.push_str(&"  ".repeat(indent + 1));
+                out.push('-');
+                write_value(out, item, indent + 1);
+            }
+        }
+    }
+}
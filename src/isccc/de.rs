@@ -0,0 +1,358 @@
+use indexmap::IndexMap;
+use serde::de::{self, DeserializeSeed, Visitor};
+use std::str::FromStr;
+
+use crate::error::RndcError;
+use crate::internal::value::RNDCValue;
+
+enum Input<'de> {
+    Text(&'de [u8]),
+    Table(&'de IndexMap<String, RNDCValue>),
+    List(&'de [RNDCValue]),
+}
+
+/// Deserializes from an `RNDCValue`. Scalars are parsed from the text held
+/// in string and binary values.
+pub(crate) struct ValueDeserializer<'de> {
+    input: Input<'de>,
+}
+
+impl<'de> ValueDeserializer<'de> {
+    pub(crate) fn new(value: &'de RNDCValue) -> Self {
+        let input = match value {
+            RNDCValue::String(b) | RNDCValue::Binary(b) => Input::Text(b),
+            RNDCValue::Table(map) => Input::Table(map),
+            RNDCValue::List(list) => Input::List(list),
+        };
+        ValueDeserializer { input }
+    }
+
+    fn key(key: &'de str) -> Self {
+        ValueDeserializer {
+            input: Input::Text(key.as_bytes()),
+        }
+    }
+
+    fn text(&self) -> Result<&'de str, RndcError> {
+        match self.input {
+            Input::Text(b) => std::str::from_utf8(b)
+                .map_err(|e| RndcError::DecodingError(format!("Value is not UTF-8: {}", e))),
+            _ => Err(RndcError::DecodingError(
+                "Expected a string or binary value".to_string(),
+            )),
+        }
+    }
+
+    fn parse<T: FromStr>(&self) -> Result<T, RndcError>
+    where
+        T::Err: std::fmt::Display,
+    {
+        let text = self.text()?;
+        text.trim()
+            .parse()
+            .map_err(|e| RndcError::DecodingError(format!("Invalid value {:?}: {}", text, e)))
+    }
+}
+
+macro_rules! deserialize_parsed {
+    ($($method:ident => $visit:ident,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, RndcError> {
+                visitor.$visit(self.parse()?)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for ValueDeserializer<'de> {
+    type Error = RndcError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, RndcError> {
+        match self.input {
+            Input::Text(b) => match std::str::from_utf8(b) {
+                Ok(s) => visitor.visit_borrowed_str(s),
+                Err(_) => visitor.visit_borrowed_bytes(b),
+            },
+            Input::Table(map) => visitor.visit_map(TableAccess {
+                iter: map.iter(),
+                value: None,
+            }),
+            Input::List(list) => visitor.visit_seq(ListAccess { iter: list.iter() }),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, RndcError> {
+        match self.text()?.trim() {
+            "1" | "true" | "yes" => visitor.visit_bool(true),
+            "0" | "false" | "no" => visitor.visit_bool(false),
+            other => Err(RndcError::DecodingError(format!(
+                "Invalid boolean value: {:?}",
+                other
+            ))),
+        }
+    }
+
+    deserialize_parsed! {
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+        deserialize_char => visit_char,
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, RndcError> {
+        visitor.visit_borrowed_str(self.text()?)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, RndcError> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, RndcError> {
+        match self.input {
+            Input::Text(b) => visitor.visit_borrowed_bytes(b),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, RndcError> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, RndcError> {
+        // absent keys are handled by serde; anything present is `Some`
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, RndcError> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, RndcError> {
+        // unit structs are written as empty tables
+        match self.input {
+            Input::Table(map) if map.is_empty() => visitor.visit_unit(),
+            _ => Err(RndcError::DecodingError(
+                "Expected an empty table for a unit struct".to_string(),
+            )),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, RndcError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, RndcError> {
+        match self.input {
+            Input::Text(_) => visitor.visit_enum(NameOnly {
+                variant: self.text()?,
+            }),
+            Input::Table(map) if map.len() == 1 => {
+                let (variant, value) = map.get_index(0).unwrap();
+                visitor.visit_enum(EnumAccess { variant, value })
+            }
+            _ => Err(RndcError::DecodingError(
+                "Expected a variant name or a single entry table".to_string(),
+            )),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+struct TableAccess<'de> {
+    iter: indexmap::map::Iter<'de, String, RNDCValue>,
+    value: Option<&'de RNDCValue>,
+}
+
+impl<'de> de::MapAccess<'de> for TableAccess<'de> {
+    type Error = RndcError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, RndcError> {
+        match self.iter.next() {
+            Some((key, value)) => {
+                self.value = Some(value);
+                seed.deserialize(ValueDeserializer::key(key)).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, RndcError> {
+        let value = self.value.take().ok_or_else(|| {
+            RndcError::DecodingError("Table value requested before its key".to_string())
+        })?;
+        seed.deserialize(ValueDeserializer::new(value))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+struct ListAccess<'de> {
+    iter: std::slice::Iter<'de, RNDCValue>,
+}
+
+impl<'de> de::SeqAccess<'de> for ListAccess<'de> {
+    type Error = RndcError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, RndcError> {
+        self.iter
+            .next()
+            .map(|value| seed.deserialize(ValueDeserializer::new(value)))
+            .transpose()
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+struct EnumAccess<'de> {
+    variant: &'de str,
+    value: &'de RNDCValue,
+}
+
+impl<'de> de::EnumAccess<'de> for EnumAccess<'de> {
+    type Error = RndcError;
+    type Variant = ValueDeserializer<'de>;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Self::Variant), RndcError> {
+        let variant = seed.deserialize(ValueDeserializer::key(self.variant))?;
+        Ok((variant, ValueDeserializer::new(self.value)))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for ValueDeserializer<'de> {
+    type Error = RndcError;
+
+    fn unit_variant(self) -> Result<(), RndcError> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, RndcError> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, RndcError> {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, RndcError> {
+        de::Deserializer::deserialize_map(self, visitor)
+    }
+}
+
+/// A variant written as just its name: a unit variant, or a newtype variant
+/// whose value was left out.
+struct NameOnly<'de> {
+    variant: &'de str,
+}
+
+impl<'de> de::EnumAccess<'de> for NameOnly<'de> {
+    type Error = RndcError;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Self::Variant), RndcError> {
+        let variant = seed.deserialize(ValueDeserializer::key(self.variant))?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for NameOnly<'de> {
+    type Error = RndcError;
+
+    fn unit_variant(self) -> Result<(), RndcError> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, RndcError> {
+        seed.deserialize(Absent)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        _visitor: V,
+    ) -> Result<V::Value, RndcError> {
+        Err(self.missing_value())
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, RndcError> {
+        Err(self.missing_value())
+    }
+}
+
+impl NameOnly<'_> {
+    fn missing_value(&self) -> RndcError {
+        RndcError::DecodingError(format!("Variant {} has no value", self.variant))
+    }
+}
+
+/// The value of a newtype variant written as just its name, which was
+/// `None` or unit.
+struct Absent;
+
+impl<'de> de::Deserializer<'de> for Absent {
+    type Error = RndcError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, RndcError> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, RndcError> {
+        visitor.visit_none()
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
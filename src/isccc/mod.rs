@@ -0,0 +1,249 @@
+//! Conversion between serde data structures and the ISCCC wire format used
+//! by the control channel.
+//!
+//! Structs and maps become tables, sequences become lists and every scalar
+//! is written as binary data holding its text form, the same way named
+//! encodes `_ser`, `_tim` or `result`. `None` fields are left out of the
+//! table.
+
+mod de;
+mod ser;
+
+use base64::Engine;
+use base64::engine::general_purpose;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::fmt;
+
+use crate::error::RndcError;
+use crate::internal::constants::RndcAlg;
+use crate::internal::value::RNDCValue;
+use crate::internal::{decoder, encoder};
+
+impl serde::ser::Error for RndcError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        RndcError::EncodingError(msg.to_string())
+    }
+}
+
+impl serde::de::Error for RndcError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        RndcError::DecodingError(msg.to_string())
+    }
+}
+
+/// Serialize `value` as the message body and sign it with `algorithm` and
+/// the base64 encoded `secret_key_b64`, as accepted by `RndcClient::new`.
+pub fn to_wire<T: Serialize + ?Sized>(
+    value: &T,
+    algorithm: &str,
+    secret_key_b64: &str,
+) -> Result<Vec<u8>, RndcError> {
+    let algorithm = RndcAlg::from_string(algorithm)?;
+    let secret_key = general_purpose::STANDARD
+        .decode(secret_key_b64.as_bytes())
+        .map_err(|e| RndcError::Base64DecodeError(e.to_string()))?;
+
+    let body = match value.serialize(ser::ValueSerializer)? {
+        Some(RNDCValue::Table(body)) => body,
+        _ => {
+            return Err(RndcError::EncodingError(
+                "Message body must serialize to a table".to_string(),
+            ));
+        }
+    };
+
+    let mut buf = Vec::new();
+    encoder::encode_into(&mut buf, &body, &algorithm, &secret_key)?;
+    Ok(buf)
+}
+
+/// Decode a packet and deserialize its top-level table, including `_auth`,
+/// into `T`. The signature is not checked.
+pub fn from_wire<T: DeserializeOwned>(buf: &[u8]) -> Result<T, RndcError> {
+    let body = RNDCValue::Table(decoder::decode(buf)?);
+    T::deserialize(de::ValueDeserializer::new(&body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Ctrl {
+        _ser: u32,
+        _tim: u32,
+        _exp: u32,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        _nonce: Option<String>,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Data {
+        #[serde(rename = "type")]
+        command: String,
+        #[serde(default)]
+        zones: Vec<String>,
+        enabled: bool,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Message {
+        _ctrl: Ctrl,
+        _data: Data,
+    }
+
+    fn message() -> Message {
+        Message {
+            _ctrl: Ctrl {
+                _ser: 1234,
+                _tim: 1700000000,
+                _exp: 1700000060,
+                _nonce: None,
+            },
+            _data: Data {
+                command: "status".to_string(),
+                zones: vec!["example.com".to_string(), "example.net".to_string()],
+                enabled: true,
+            },
+        }
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let buf = to_wire(&message(), "sha256", "YmluZGl6cg==").unwrap();
+        let decoded: Message = from_wire(&buf).unwrap();
+        assert_eq!(decoded, message());
+    }
+
+    #[test]
+    fn test_unit_struct_roundtrip() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Flush;
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct FlushData {
+            flush: Flush,
+        }
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct FlushMessage {
+            _data: FlushData,
+        }
+
+        let message = FlushMessage {
+            _data: FlushData { flush: Flush },
+        };
+        let buf = to_wire(&message, "sha256", "YmluZGl6cg==").unwrap();
+        let decoded: FlushMessage = from_wire(&buf).unwrap();
+        assert_eq!(decoded, message);
+
+        // a value other than an empty table is not a unit struct
+        let data = std::collections::BTreeMap::from([("flush", "yes")]);
+        let bad = to_wire(
+            &std::collections::BTreeMap::from([("_data", data)]),
+            "sha256",
+            "YmluZGl6cg==",
+        )
+        .unwrap();
+        assert!(from_wire::<FlushMessage>(&bad).is_err());
+    }
+
+    #[test]
+    fn test_enum_roundtrip() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        enum Zone {
+            All,
+            Named(Option<String>),
+            Unit(()),
+            Pair(String, u32),
+        }
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct ZoneData {
+            zones: Vec<Zone>,
+        }
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct ZoneMessage {
+            _data: ZoneData,
+        }
+
+        let message = ZoneMessage {
+            _data: ZoneData {
+                zones: vec![
+                    Zone::All,
+                    Zone::Named(None),
+                    Zone::Named(Some("example.com".to_string())),
+                    Zone::Unit(()),
+                    Zone::Pair("example.net".to_string(), 1),
+                ],
+            },
+        };
+        let buf = to_wire(&message, "sha256", "YmluZGl6cg==").unwrap();
+        let decoded: ZoneMessage = from_wire(&buf).unwrap();
+        assert_eq!(decoded, message);
+    }
+
+    #[test]
+    fn test_matches_manual_encoding() {
+        let buf = to_wire(&message(), "md5", "YmluZGl6cg==").unwrap();
+        let decoded = decoder::decode(&buf).unwrap();
+
+        let ctrl = decoded.get("_ctrl").and_then(RNDCValue::as_table).unwrap();
+        assert_eq!(
+            ctrl.keys().collect::<Vec<_>>(),
+            vec!["_ser", "_tim", "_exp"]
+        );
+        assert_eq!(ctrl.get("_ser"), Some(&RNDCValue::Binary(b"1234".to_vec())));
+
+        let data = decoded.get("_data").and_then(RNDCValue::as_table).unwrap();
+        assert_eq!(
+            data.get("type"),
+            Some(&RNDCValue::Binary(b"status".to_vec()))
+        );
+        assert_eq!(
+            data.get("zones"),
+            Some(&RNDCValue::List(vec![
+                RNDCValue::Binary(b"example.com".to_vec()),
+                RNDCValue::Binary(b"example.net".to_vec()),
+            ]))
+        );
+        assert!(encoder::verify(&decoded, &RndcAlg::MD5, b"bindizr").is_ok());
+    }
+
+    #[test]
+    fn test_rejects_non_table_body() {
+        assert!(to_wire(&"status", "sha256", "YmluZGl6cg==").is_err());
+    }
+
+    #[test]
+    fn test_invalid_number() {
+        #[derive(Serialize)]
+        struct BadCtrl {
+            _ser: &'static str,
+            _tim: u32,
+            _exp: u32,
+        }
+        #[derive(Serialize)]
+        struct BadMessage {
+            _ctrl: BadCtrl,
+            _data: Data,
+        }
+
+        let buf = to_wire(
+            &BadMessage {
+                _ctrl: BadCtrl {
+                    _ser: "not a number",
+                    _tim: 0,
+                    _exp: 0,
+                },
+                _data: message()._data,
+            },
+            "sha256",
+            "YmluZGl6cg==",
+        )
+        .unwrap();
+        assert!(matches!(
+            from_wire::<Message>(&buf),
+            Err(RndcError::DecodingError(_))
+        ));
+    }
+}
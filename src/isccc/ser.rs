@@ -0,0 +1,354 @@
+use indexmap::IndexMap;
+use serde::ser::{self, Serialize};
+
+use crate::error::RndcError;
+use crate::internal::value::RNDCValue;
+
+fn text(value: impl ToString) -> Option<RNDCValue> {
+    Some(RNDCValue::Binary(value.to_string().into_bytes()))
+}
+
+fn serialize_key<T: Serialize + ?Sized>(key: &T) -> Result<String, RndcError> {
+    match key.serialize(ValueSerializer)? {
+        Some(RNDCValue::Binary(b)) | Some(RNDCValue::String(b)) => String::from_utf8(b)
+            .map_err(|e| RndcError::EncodingError(format!("Table key is not UTF-8: {}", e))),
+        _ => Err(RndcError::EncodingError(
+            "Table keys must serialize to strings".to_string(),
+        )),
+    }
+}
+
+/// Enum variants with data become a single entry table keyed by the
+/// variant name. A newtype variant whose value is left out (`None` or unit)
+/// is written as just its name, like a unit variant.
+fn variant_table(variant: &str, value: Option<RNDCValue>) -> Option<RNDCValue> {
+    let Some(value) = value else {
+        return text(variant);
+    };
+    let mut map = IndexMap::new();
+    map.insert(variant.to_string(), value);
+    Some(RNDCValue::Table(map))
+}
+
+/// Serializes into an `RNDCValue`, or `None` for values that are left out
+/// of their table (`None` and unit).
+pub(crate) struct ValueSerializer;
+
+impl ser::Serializer for ValueSerializer {
+    type Ok = Option<RNDCValue>;
+    type Error = RndcError;
+
+    type SerializeSeq = SerializeList;
+    type SerializeTuple = SerializeList;
+    type SerializeTupleStruct = SerializeList;
+    type SerializeTupleVariant = SerializeVariant<SerializeList>;
+    type SerializeMap = SerializeTable;
+    type SerializeStruct = SerializeTable;
+    type SerializeStructVariant = SerializeVariant<SerializeTable>;
+
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok, RndcError> {
+        Ok(text(if v { "1" } else { "0" }))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok, RndcError> {
+        Ok(text(v))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok, RndcError> {
+        Ok(text(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok, RndcError> {
+        Ok(text(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok, RndcError> {
+        Ok(text(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok, RndcError> {
+        Ok(text(v))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok, RndcError> {
+        Ok(text(v))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok, RndcError> {
+        Ok(text(v))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok, RndcError> {
+        Ok(text(v))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok, RndcError> {
+        Ok(text(v))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok, RndcError> {
+        Ok(text(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Self::Ok, RndcError> {
+        Ok(text(v))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok, RndcError> {
+        Ok(text(v))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, RndcError> {
+        Ok(Some(RNDCValue::Binary(v.to_vec())))
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok, RndcError> {
+        Ok(None)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Self::Ok, RndcError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok, RndcError> {
+        Ok(None)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, RndcError> {
+        Ok(Some(RNDCValue::Table(IndexMap::new())))
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok, RndcError> {
+        Ok(text(variant))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, RndcError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, RndcError> {
+        Ok(variant_table(variant, value.serialize(ValueSerializer)?))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, RndcError> {
+        Ok(SerializeList {
+            list: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, RndcError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, RndcError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, RndcError> {
+        Ok(SerializeVariant {
+            variant,
+            inner: self.serialize_seq(Some(len))?,
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, RndcError> {
+        Ok(SerializeTable {
+            map: IndexMap::new(),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStruct, RndcError> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant, RndcError> {
+        Ok(SerializeVariant {
+            variant,
+            inner: self.serialize_map(Some(len))?,
+        })
+    }
+}
+
+pub(crate) struct SerializeList {
+    list: Vec<RNDCValue>,
+}
+
+impl SerializeList {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), RndcError> {
+        let value = value.serialize(ValueSerializer)?.ok_or_else(|| {
+            RndcError::EncodingError("Lists cannot contain empty values".to_string())
+        })?;
+        self.list.push(value);
+        Ok(())
+    }
+}
+
+impl ser::SerializeSeq for SerializeList {
+    type Ok = Option<RNDCValue>;
+    type Error = RndcError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), RndcError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, RndcError> {
+        Ok(Some(RNDCValue::List(self.list)))
+    }
+}
+
+impl ser::SerializeTuple for SerializeList {
+    type Ok = Option<RNDCValue>;
+    type Error = RndcError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), RndcError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, RndcError> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeList {
+    type Ok = Option<RNDCValue>;
+    type Error = RndcError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), RndcError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, RndcError> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+pub(crate) struct SerializeTable {
+    map: IndexMap<String, RNDCValue>,
+    key: Option<String>,
+}
+
+impl SerializeTable {
+    fn insert<T: Serialize + ?Sized>(&mut self, key: String, value: &T) -> Result<(), RndcError> {
+        if key.len() > u8::MAX as usize {
+            return Err(RndcError::EncodingError(format!(
+                "Table key is longer than 255 bytes: {}",
+                key
+            )));
+        }
+        if let Some(value) = value.serialize(ValueSerializer)? {
+            self.map.insert(key, value);
+        }
+        Ok(())
+    }
+}
+
+impl ser::SerializeMap for SerializeTable {
+    type Ok = Option<RNDCValue>;
+    type Error = RndcError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), RndcError> {
+        self.key = Some(serialize_key(key)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), RndcError> {
+        let key = self.key.take().ok_or_else(|| {
+            RndcError::EncodingError("Table value serialized before its key".to_string())
+        })?;
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, RndcError> {
+        Ok(Some(RNDCValue::Table(self.map)))
+    }
+}
+
+impl ser::SerializeStruct for SerializeTable {
+    type Ok = Option<RNDCValue>;
+    type Error = RndcError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), RndcError> {
+        self.insert(key.to_string(), value)
+    }
+
+    fn end(self) -> Result<Self::Ok, RndcError> {
+        ser::SerializeMap::end(self)
+    }
+}
+
+pub(crate) struct SerializeVariant<S> {
+    variant: &'static str,
+    inner: S,
+}
+
+impl ser::SerializeTupleVariant for SerializeVariant<SerializeList> {
+    type Ok = Option<RNDCValue>;
+    type Error = RndcError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), RndcError> {
+        self.inner.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, RndcError> {
+        let value = ser::SerializeSeq::end(self.inner)?;
+        Ok(variant_table(self.variant, value))
+    }
+}
+
+impl ser::SerializeStructVariant for SerializeVariant<SerializeTable> {
+    type Ok = Option<RNDCValue>;
+    type Error = RndcError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), RndcError> {
+        self.inner.insert(key.to_string(), value)
+    }
+
+    fn end(self) -> Result<Self::Ok, RndcError> {
+        let value = ser::SerializeMap::end(self.inner)?;
+        Ok(variant_table(self.variant, value))
+    }
+}
@@ -0,0 +1,269 @@
+use base64::Engine;
+use base64::engine::general_purpose;
+use std::fmt;
+
+use crate::error::RndcError;
+use crate::internal::constants::RndcAlg;
+
+/// A named control channel key.
+///
+/// The name is not sent on the wire, since named tries every key allowed for
+/// the control channel, but identifies the key in results and logs.
+#[derive(Clone, PartialEq, Eq)]
+pub struct RndcKey {
+    pub name: String,
+    pub algorithm: RndcAlg,
+    pub secret: Vec<u8>,
+}
+impl RndcKey {
+    pub fn new(name: &str, algorithm: &str, secret_key_b64: &str) -> Result<Self, RndcError> {
+        let secret = general_purpose::STANDARD
+            .decode(secret_key_b64.as_bytes())
+            .map_err(|e| RndcError::Base64DecodeError(e.to_string()))?;
+
+        Ok(RndcKey {
+            name: name.to_string(),
+            algorithm: RndcAlg::from_string(algorithm)?,
+            secret,
+        })
+    }
+
+    /// Parse every `key "name" { algorithm ...; secret "..."; };` clause of
+    /// a BIND configuration such as `rndc.key` or `rndc.conf`.
+    pub fn parse_bind_config(text: &str) -> Result<Vec<RndcKey>, RndcError> {
+        let tokens = tokenize(text)?;
+        let mut keys = Vec::new();
+        let mut depth = 0usize;
+        let mut i = 0;
+
+        while i < tokens.len() {
+            match tokens[i].as_str() {
+                "{" => depth += 1,
+                "}" => depth = depth.saturating_sub(1),
+                "key" if depth == 0 => {
+                    let (key, next) = parse_key_clause(&tokens, i + 1)?;
+                    keys.push(key);
+                    i = next;
+                    continue;
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+
+        Ok(keys)
+    }
+}
+
+/// Split a BIND configuration into words, quoted strings and `{`, `}`, `;`,
+/// dropping `#`, `//` and `/* */` comments.
+fn tokenize(text: &str) -> Result<Vec<String>, RndcError> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '#' => {
+                chars.by_ref().take_while(|c| *c != '\n').for_each(drop);
+            }
+            '/' if chars.peek() == Some(&'/') => {
+                chars.by_ref().take_while(|c| *c != '\n').for_each(drop);
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut prev = ' ';
+                loop {
+                    match chars.next() {
+                        Some('/') if prev == '*' => break,
+                        Some(c) => prev = c,
+                        None => {
+                            return Err(RndcError::InvalidArgument(
+                                "Unterminated comment in key file".to_string(),
+                            ));
+                        }
+                    }
+                }
+            }
+            '"' => {
+                let mut s = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some(c) => s.push(c),
+                        None => {
+                            return Err(RndcError::InvalidArgument(
+                                "Unterminated string in key file".to_string(),
+                            ));
+                        }
+                    }
+                }
+                tokens.push(s);
+            }
+            '{' | '}' | ';' => tokens.push(c.to_string()),
+            _ => {
+                let mut s = c.to_string();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || matches!(c, '{' | '}' | ';' | '"') {
+                        break;
+                    }
+                    s.push(c);
+                    chars.next();
+                }
+                tokens.push(s);
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Parse `name { algorithm ...; secret "..."; };` starting at `tokens[i]`
+/// and return the key and the index after the clause.
+fn parse_key_clause(tokens: &[String], mut i: usize) -> Result<(RndcKey, usize), RndcError> {
+    let malformed = || RndcError::InvalidArgument("Malformed key clause in key file".to_string());
+
+    let name = tokens.get(i).ok_or_else(malformed)?;
+    if tokens.get(i + 1).map(String::as_str) != Some("{") {
+        return Err(malformed());
+    }
+    i += 2;
+
+    let mut algorithm = None;
+    let mut secret = None;
+    while let Some(token) = tokens.get(i) {
+        match token.as_str() {
+            "}" => break,
+            "algorithm" => algorithm = tokens.get(i + 1),
+            "secret" => secret = tokens.get(i + 1),
+            _ => {}
+        }
+        // skip to the end of the statement
+        while tokens.get(i).is_some_and(|t| t != ";" && t != "}") {
+            i += 1;
+        }
+        if tokens.get(i).map(String::as_str) == Some(";") {
+            i += 1;
+        }
+    }
+    // closing "}" and ";"
+    i += 1;
+    if tokens.get(i).map(String::as_str) == Some(";") {
+        i += 1;
+    }
+
+    match (algorithm, secret) {
+        (Some(algorithm), Some(secret)) => Ok((RndcKey::new(name, algorithm, secret)?, i)),
+        _ => Err(RndcError::InvalidArgument(format!(
+            "Key {} is missing its algorithm or secret",
+            name
+        ))),
+    }
+}
+
+impl fmt::Debug for RndcKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RndcKey")
+            .field("name", &self.name)
+            .field("algorithm", &self.algorithm)
+            .field("secret", &"<redacted>")
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RndcClient;
+    use crate::internal::test_server::{TEST_SECRET_B64, TestServer};
+
+    // base64 of "old-secret"
+    const OLD_SECRET_B64: &str = "b2xkLXNlY3JldA==";
+
+    #[test]
+    fn test_key_debug_redacts_secret() {
+        let key = RndcKey::new("rndc-key", "sha256", TEST_SECRET_B64).unwrap();
+        let debug = format!("{:?}", key);
+        assert!(debug.contains("rndc-key"));
+        assert!(!debug.contains("98, 105, 110"));
+    }
+
+    #[test]
+    fn test_parse_bind_config() {
+        let text = r#"
+            # rndc.conf
+            key "rndc-key" {
+                algorithm hmac-sha256;
+                secret "YmluZGl6cg==";
+            };
+            // second key
+            key old-key { algorithm hmac-md5; /* retired */ secret "b2xkLXNlY3JldA=="; };
+            options {
+                default-key "rndc-key";
+                default-server 127.0.0.1;
+            };
+        "#;
+        let keys = RndcKey::parse_bind_config(text).unwrap();
+
+        assert_eq!(keys.len(), 2);
+        assert_eq!(keys[0].name, "rndc-key");
+        assert_eq!(keys[0].algorithm, RndcAlg::SHA256);
+        assert_eq!(keys[0].secret, b"bindizr");
+        assert_eq!(keys[1].name, "old-key");
+        assert_eq!(keys[1].algorithm, RndcAlg::MD5);
+        assert_eq!(keys[1].secret, b"old-secret");
+    }
+
+    #[test]
+    fn test_parse_bind_config_errors() {
+        assert!(RndcKey::parse_bind_config("key \"k\" { algorithm hmac-sha256; };").is_err());
+        assert!(RndcKey::parse_bind_config("key \"k\" { secret \"abc").is_err());
+        assert!(RndcKey::parse_bind_config("/* open").is_err());
+    }
+
+    #[test]
+    fn test_falls_back_to_next_key() {
+        let server = TestServer::start(RndcAlg::SHA256, None);
+        let client = RndcClient::with_keys(
+            &server.addr.to_string(),
+            vec![
+                RndcKey::new("old", "sha256", OLD_SECRET_B64).unwrap(),
+                RndcKey::new("new", "sha256", TEST_SECRET_B64).unwrap(),
+            ],
+        )
+        .unwrap();
+
+        assert!(client.rndc_command("status").unwrap().result);
+        assert_eq!(client.active_key().unwrap().name, "new");
+        // one rejected handshake, then the accepted one
+        assert_eq!(server.connections(), 2);
+
+        // later commands start with the key that worked
+        assert!(client.clone().rndc_command("status").unwrap().result);
+        assert_eq!(server.connections(), 3);
+    }
+
+    #[test]
+    fn test_all_keys_rejected() {
+        let server = TestServer::start(RndcAlg::SHA256, None);
+        let client = RndcClient::with_keys(
+            &server.addr.to_string(),
+            vec![
+                RndcKey::new("old", "sha256", OLD_SECRET_B64).unwrap(),
+                RndcKey::new("wrong-alg", "md5", TEST_SECRET_B64).unwrap(),
+            ],
+        )
+        .unwrap();
+
+        assert!(matches!(
+            client.rndc_command("status"),
+            Err(RndcError::ConnectionClosed(_))
+        ));
+        assert_eq!(server.connections(), 2);
+    }
+
+    #[test]
+    fn test_with_keys_requires_a_key() {
+        assert!(RndcClient::with_keys("127.0.0.1:953", vec![]).is_err());
+    }
+}
@@ -1,126 +1,358 @@
+mod audit;
+mod batch;
+mod commands;
+mod dry_run;
 mod error;
+mod inspect;
 mod internal;
+#[cfg(feature = "serde")]
+pub mod isccc;
+mod key;
+mod pcap;
+mod policy;
+mod pool;
+mod provider;
+mod session;
+mod transcript;
+mod transport;
 
-use base64::Engine;
-use base64::engine::general_purpose;
 use indexmap::IndexMap;
-use std::io::{Read, Write};
-use std::net::TcpStream;
-
+use std::io::{ErrorKind, Read};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+pub use crate::audit::{AuditEvent, AuditObserver, JsonLinesAuditSink};
+pub use crate::batch::{
+    BatchOptions, BatchReport, BatchScript, BatchStep, StepOutcome, StepReport,
+};
+pub use crate::commands::cache::DumpDbScope;
+pub use crate::commands::logging::{QueryLogGuard, TraceGuard};
+pub use crate::commands::serve_stale::ServeStaleStatus;
+pub use crate::commands::signing::{
+    Nsec3Param, SigningClear, SigningKeyEntry, SigningState, SigningStatus,
+};
+pub use crate::commands::status::{ClientCount, ServerStatus};
+pub use crate::commands::tsig::{TsigKeyEntry, TsigKeyType};
+pub use crate::commands::zone::{ZoneFreezeGuard, ZoneStatus};
+pub use crate::dry_run::DryRunRecord;
 pub use crate::error::RndcError;
-use crate::internal::constants::RndcAlg;
-use crate::internal::{decoder, decoder::RNDCPayload, encoder, encoder::RNDCValue, utils};
+pub use crate::inspect::{PacketInspection, inspect};
+pub use crate::internal::args::join_command;
+pub use crate::internal::constants::RndcAlg;
+pub use crate::internal::decoder::decode_borrowed;
+pub use crate::internal::value::{RNDCValue, RNDCValueRef};
+use crate::internal::{decoder, encoder, utils};
+pub use crate::key::RndcKey;
+pub use crate::pcap::PcapTransport;
+pub use crate::policy::CommandPolicy;
+pub use crate::pool::{Checkout, PooledSession, RndcPool, RndcPoolConfig};
+pub use crate::provider::{CommandKeyProvider, EnvKeyProvider, KeyFileProvider, KeyProvider};
+pub use crate::session::RndcSession;
+pub use crate::transcript::{
+    Direction, RecordingTransport, ReplayTransport, Transcript, TranscriptEntry,
+};
+pub use crate::transport::{Connection, TcpTransport, Transport};
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct RndcResult {
     pub result: bool,
+    /// The numeric `result` returned by named, `0` on success. `None` for
+    /// a dry run.
+    pub result_code: Option<u32>,
     pub text: Option<String>,
     pub err: Option<String>,
+    /// When the command was started. Serialized as seconds since the Unix
+    /// epoch.
+    #[cfg_attr(
+        feature = "serde",
+        serde(serialize_with = "crate::internal::utils::serialize_unix_seconds")
+    )]
+    pub started: SystemTime,
+    /// How long the command took, including connecting. Serialized as
+    /// `elapsed_ms`.
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            rename = "elapsed_ms",
+            serialize_with = "crate::internal::utils::serialize_millis"
+        )
+    )]
+    pub elapsed: Duration,
 }
 
 #[derive(Debug, Clone)]
 pub struct RndcClient {
     server_url: String,
-    algorithm: RndcAlg,
-    secret_key: Vec<u8>,
+    keys: Arc<[Arc<dyn KeyProvider>]>,
+    /// Index of the key provider that last completed a handshake, tried first.
+    active_key: Arc<AtomicUsize>,
+    observers: Vec<Arc<dyn AuditObserver>>,
+    policy: Option<Arc<CommandPolicy>>,
+    /// Commands recorded instead of sent, when dry run is enabled.
+    dry_run: Option<Arc<Mutex<Vec<DryRunRecord>>>>,
+    transport: Arc<dyn Transport>,
 }
 impl RndcClient {
     pub fn new(server_url: &str, algorithm: &str, secret_key_b64: &str) -> Result<Self, RndcError> {
-        let secret_key = general_purpose::STANDARD
-            .decode(secret_key_b64.as_bytes())
-            .map_err(|e| RndcError::Base64DecodeError(e.to_string()))?;
+        let key = RndcKey::new("rndc-key", algorithm, secret_key_b64)?;
+        Self::with_keys(server_url, vec![key])
+    }
+
+    /// Create a client that falls back to the next key in `keys` when the
+    /// server rejects a handshake, e.g. while a key rotation is rolled out.
+    pub fn with_keys(server_url: &str, keys: Vec<RndcKey>) -> Result<Self, RndcError> {
+        let providers = keys
+            .into_iter()
+            .map(|key| Arc::new(key) as Arc<dyn KeyProvider>)
+            .collect();
+        Self::with_providers(server_url, providers)
+    }
+
+    /// Create a client that asks `providers` for its keys on every
+    /// handshake, so a rotated key is picked up without a new client. Like
+    /// `with_keys`, the next provider is tried when the server rejects one.
+    pub fn with_providers(
+        server_url: &str,
+        providers: Vec<Arc<dyn KeyProvider>>,
+    ) -> Result<Self, RndcError> {
+        if providers.is_empty() {
+            return Err(RndcError::InvalidArgument(
+                "At least one key is required".to_string(),
+            ));
+        }
 
         Ok(RndcClient {
             server_url: server_url.to_string(),
-            algorithm: RndcAlg::from_string(algorithm)?,
-            secret_key,
+            keys: providers.into(),
+            active_key: Arc::new(AtomicUsize::new(0)),
+            observers: Vec::new(),
+            policy: None,
+            dry_run: None,
+            transport: Arc::new(TcpTransport),
         })
     }
 
-    fn get_stream(&self) -> Result<TcpStream, RndcError> {
-        TcpStream::connect(&self.server_url)
+    /// The current key of the provider that last completed a handshake, or
+    /// of the first provider.
+    pub fn active_key(&self) -> Result<RndcKey, RndcError> {
+        self.keys[self.active_key.load(Ordering::Relaxed)].key()
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), fields(server = %self.server_url), err)
+    )]
+    fn get_stream(&self) -> Result<Box<dyn Connection>, RndcError> {
+        self.transport
+            .connect(&self.server_url)
             .map_err(|e| RndcError::NetworkError(format!("Failed to connect to server: {}", e)))
     }
 
-    fn close_stream(&self, stream: &TcpStream) -> Result<(), RndcError> {
+    fn close_stream(&self, stream: &mut dyn Connection) -> Result<(), RndcError> {
         stream
-            .shutdown(std::net::Shutdown::Both)
+            .shutdown()
             .map_err(|e| RndcError::NetworkError(format!("Failed to shutdown stream: {}", e)))?;
 
         Ok(())
     }
 
-    fn rndc_handshake(&self) -> Result<(TcpStream, String), RndcError> {
-        let msg = Self::build_message(
-            "null",
-            &self.algorithm,
-            &self.secret_key,
-            None,
-            rand::random(),
-        )?;
-
-        let mut stream = self.get_stream()?;
-        stream
-            .write_all(&msg)
-            .map_err(|e| RndcError::NetworkError(format!("Failed to write to stream: {}", e)))?;
-
-        let res = RndcClient::read_packet(&mut stream)?;
+    /// Connect and perform the `null` handshake, starting with the active
+    /// key provider and moving on to the next one whenever the server drops
+    /// the connection. A rejected provider is refreshed and retried once if
+    /// that yields a different key. Returns the stream, the nonce and the
+    /// key that worked.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip(self),
+            fields(
+                server = %self.server_url,
+                key = tracing::field::Empty,
+                latency_us = tracing::field::Empty,
+            ),
+            err
+        )
+    )]
+    fn rndc_handshake(&self) -> Result<(Box<dyn Connection>, String, RndcKey), RndcError> {
+        #[cfg(feature = "tracing")]
+        let started = std::time::Instant::now();
+        let start = self.active_key.load(Ordering::Relaxed);
+        let mut last_err = None;
+
+        for offset in 0..self.keys.len() {
+            let index = (start + offset) % self.keys.len();
+            let provider = &self.keys[index];
+            let mut key = match provider.key() {
+                Ok(key) => key,
+                Err(e) => {
+                    last_err = Some(e);
+                    continue;
+                }
+            };
+            let mut refreshed = false;
+
+            loop {
+                let mut stream = self.get_stream()?;
+
+                match self.send_command(stream.as_mut(), &key, "null", None, rand::random()) {
+                    Ok(res) => {
+                        let nonce = self.get_nonce(&res)?;
+                        self.active_key.store(index, Ordering::Relaxed);
+                        #[cfg(feature = "tracing")]
+                        {
+                            let span = tracing::Span::current();
+                            span.record("key", key.name.as_str());
+                            span.record("latency_us", started.elapsed().as_micros() as u64);
+                        }
+                        return Ok((stream, nonce, key));
+                    }
+                    // named closes the channel when it cannot verify the signature
+                    Err(e @ RndcError::ConnectionClosed(_)) => {
+                        #[cfg(feature = "tracing")]
+                        tracing::debug!(key = %key.name, "server closed the connection during handshake");
+                        last_err = Some(e);
+                    }
+                    Err(e) => return Err(e),
+                }
 
-        let nonce = self.get_nonce(&res)?;
+                if refreshed {
+                    break;
+                }
+                refreshed = true;
+                provider.refresh();
+                match provider.key() {
+                    Ok(fresh) if fresh != key => key = fresh,
+                    _ => break,
+                }
+            }
+        }
 
-        Ok((stream, nonce))
+        Err(last_err.unwrap())
     }
 
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "info",
+            skip(self),
+            fields(
+                server = %self.server_url,
+                latency_us = tracing::field::Empty,
+                result = tracing::field::Empty,
+                result_code = tracing::field::Empty,
+            ),
+            err
+        )
+    )]
     pub fn rndc_command(&self, command: &str) -> Result<RndcResult, RndcError> {
-        let (mut stream, nonce) = self.rndc_handshake()?;
+        let mut event = AuditEvent::begin(&self.server_url, command);
+        let mut res = self.exec_command(command, &mut event);
+        event.finish(&mut res);
+        #[cfg(feature = "tracing")]
+        {
+            let span = tracing::Span::current();
+            if let Ok(latency) = event.finished.duration_since(event.started) {
+                span.record("latency_us", latency.as_micros() as u64);
+            }
+            if let Ok(res) = &res {
+                span.record("result", res.result);
+            }
+            if let Some(code) = event.result_code {
+                span.record("result_code", code);
+            }
+        }
+        self.notify(&event);
+        res
+    }
 
-        let msg = RndcClient::build_message(
-            command,
-            &self.algorithm,
-            &self.secret_key,
-            Some(&nonce),
-            rand::random(),
-        )?;
+    fn exec_command(&self, command: &str, event: &mut AuditEvent) -> Result<RndcResult, RndcError> {
+        self.check_policy(command)?;
+        if self.is_dry_run() {
+            return self.dry_run_command(command, event);
+        }
+        let (mut stream, nonce, key) = self.rndc_handshake()?;
+        event.key_name = Some(key.name.clone());
 
-        stream
-            .write_all(&msg)
-            .map_err(|e| RndcError::NetworkError(format!("Failed to write to stream: {}", e)))?;
+        let ser = rand::random();
+        event.serial = Some(ser);
+        let res = self.send_command(stream.as_mut(), &key, command, Some(&nonce), ser)?;
+
+        self.close_stream(stream.as_mut())?;
+
+        Self::parse_reply(&res)
+    }
 
-        let res = RndcClient::read_packet(&mut stream)?;
+    /// Sign and send one command on an open stream and read the reply.
+    fn send_command(
+        &self,
+        stream: &mut dyn Connection,
+        key: &RndcKey,
+        command: &str,
+        nonce: Option<&str>,
+        ser: u32,
+    ) -> Result<Vec<u8>, RndcError> {
+        self.write_command(stream, key, command, nonce, ser)?;
+        Self::read_packet(stream)
+    }
 
-        self.close_stream(&stream)?;
+    /// Sign and write one command without reading the reply. A
+    /// `ConnectionClosed` error from here means the server did not receive
+    /// the whole command, so it cannot have run it.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip(self, stream, key, nonce),
+            fields(key = %key.name, bytes_sent = tracing::field::Empty),
+            err
+        )
+    )]
+    fn write_command(
+        &self,
+        stream: &mut dyn Connection,
+        key: &RndcKey,
+        command: &str,
+        nonce: Option<&str>,
+        ser: u32,
+    ) -> Result<(), RndcError> {
+        let msg = Self::build_message(command, &key.algorithm, &key.secret, nonce, ser)?;
 
-        let resp = decoder::decode(&res)?;
+        stream.write_all(&msg).map_err(|e| match e.kind() {
+            ErrorKind::BrokenPipe | ErrorKind::ConnectionReset | ErrorKind::ConnectionAborted => {
+                RndcError::ConnectionClosed(format!("Failed to write to stream: {}", e))
+            }
+            _ => RndcError::NetworkError(format!("Failed to write to stream: {}", e)),
+        })?;
+        #[cfg(feature = "tracing")]
+        tracing::Span::current().record("bytes_sent", msg.len());
 
-        if let Some(RNDCPayload::Table(data)) = resp.get("_data") {
+        Ok(())
+    }
+
+    /// Parse a reply into its result and the numeric result code.
+    fn parse_reply(packet: &[u8]) -> Result<RndcResult, RndcError> {
+        let resp = decoder::decode_borrowed(packet)?;
+
+        if let Some(data) = resp.get("_data").and_then(RNDCValueRef::as_table) {
             // dbg!("Received data: {:?}", data);
 
-            let result = data.get("result").and_then(|v| {
-                if let RNDCPayload::String(s) = v {
-                    Some(s == "0")
-                } else {
-                    None
-                }
-            });
-            let text = data.get("text").and_then(|v| {
-                if let RNDCPayload::String(s) = v {
-                    Some(s.clone())
-                } else {
-                    None
-                }
-            });
-            let err = data.get("err").and_then(|v| {
-                if let RNDCPayload::String(s) = v {
-                    Some(s.clone())
-                } else {
-                    None
-                }
-            });
+            let result = data.get("result").and_then(RNDCValueRef::as_str);
+            let text = data.get("text").and_then(RNDCValueRef::as_str);
+            let err = data.get("err").and_then(RNDCValueRef::as_str);
 
+            #[cfg(feature = "tracing")]
+            tracing::debug!(result, text, err, "decoded reply");
             return Ok(RndcResult {
-                result: result.unwrap_or(false),
-                text,
-                err,
+                result: result == Some("0"),
+                result_code: result.and_then(|r| r.parse().ok()),
+                text: text.map(str::to_string),
+                err: err.map(str::to_string),
+                // set by `AuditEvent::finish`
+                started: SystemTime::UNIX_EPOCH,
+                elapsed: Duration::ZERO,
             });
         }
         Err(RndcError::DecodingError(
@@ -128,6 +360,20 @@ impl RndcClient {
         ))
     }
 
+    /// Sign `command` into `buf` (cleared first) without sending it.
+    pub fn encode_command_into(&self, command: &str, buf: &mut Vec<u8>) -> Result<(), RndcError> {
+        self.check_policy(command)?;
+        let key = self.active_key()?;
+        Self::build_message_into(
+            buf,
+            command,
+            &key.algorithm,
+            &key.secret,
+            None,
+            rand::random(),
+        )
+    }
+
     fn build_message(
         command: &str,
         algorithm: &RndcAlg,
@@ -135,6 +381,19 @@ impl RndcClient {
         nonce: Option<&str>,
         ser: u32,
     ) -> Result<Vec<u8>, RndcError> {
+        let mut buf = Vec::new();
+        Self::build_message_into(&mut buf, command, algorithm, secret, nonce, ser)?;
+        Ok(buf)
+    }
+
+    fn build_message_into(
+        buf: &mut Vec<u8>,
+        command: &str,
+        algorithm: &RndcAlg,
+        secret: &[u8],
+        nonce: Option<&str>,
+        ser: u32,
+    ) -> Result<(), RndcError> {
         let now = utils::get_timestamp();
         let exp = now + 60;
 
@@ -171,39 +430,51 @@ impl RndcClient {
         message_body.insert("_ctrl".to_string(), RNDCValue::Table(ctrl_map));
         message_body.insert("_data".to_string(), RNDCValue::Table(data_map));
 
-        encoder::encode(&mut message_body, algorithm, secret)
+        encoder::encode_into(buf, &message_body, algorithm, secret)
     }
 
     fn get_nonce(&self, packet: &[u8]) -> Result<String, RndcError> {
-        let resp = decoder::decode(packet)?;
-        if let Some(RNDCPayload::Table(ctrl_map)) = resp.get("_ctrl").and_then(|ctrl| {
-            if let RNDCPayload::Table(map) = ctrl {
-                Some(RNDCPayload::Table(map.clone()))
-            } else {
-                None
-            }
-        }) {
-            if let Some(RNDCPayload::String(new_nonce)) = ctrl_map.get("_nonce") {
-                // println!("Received nonce: {:?}", new_nonce);
-                return Ok(new_nonce.to_string());
-            }
+        let resp = decoder::decode_borrowed(packet)?;
+        if let Some(ctrl_map) = resp.get("_ctrl").and_then(RNDCValueRef::as_table)
+            && let Some(new_nonce) = ctrl_map.get("_nonce").and_then(RNDCValueRef::as_str)
+        {
+            // println!("Received nonce: {:?}", new_nonce);
+            return Ok(new_nonce.to_string());
         }
         Err(RndcError::DecodingError(
             "RNDC nonce not received".to_string(),
         ))
     }
 
-    fn read_packet(stream: &mut TcpStream) -> Result<Vec<u8>, RndcError> {
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip(stream),
+            fields(bytes_received = tracing::field::Empty),
+            err
+        )
+    )]
+    fn read_packet<R: Read + ?Sized>(stream: &mut R) -> Result<Vec<u8>, RndcError> {
         let mut header = [0u8; 8];
         stream.read_exact(&mut header).map_err(|e| {
-            RndcError::NetworkError(format!(
+            let msg = format!(
                 "Failed to read header: {} (expected length: {})",
                 e,
                 header.len()
-            ))
+            );
+            match e.kind() {
+                // the server closed the channel before answering
+                ErrorKind::UnexpectedEof
+                | ErrorKind::ConnectionReset
+                | ErrorKind::ConnectionAborted => RndcError::ConnectionClosed(msg),
+                _ => RndcError::NetworkError(msg),
+            }
         })?;
 
-        let length_field = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) - 4;
+        let length_field = u32::from_be_bytes([header[0], header[1], header[2], header[3]])
+            .checked_sub(4)
+            .ok_or_else(|| RndcError::DecodingError("RNDC packet length too short".to_string()))?;
         // let version = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
 
         let mut payload = vec![0u8; length_field as usize];
@@ -217,7 +488,81 @@ impl RndcClient {
         let mut full_packet = Vec::with_capacity(8 + payload.len());
         full_packet.extend_from_slice(&header);
         full_packet.extend_from_slice(&payload);
+        #[cfg(feature = "tracing")]
+        tracing::Span::current().record("bytes_received", full_packet.len());
 
         Ok(full_packet)
     }
 }
+
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+    use super::*;
+    use crate::internal::test_server::{TEST_SECRET_B64, TestServer};
+    use std::io::Write;
+
+    #[derive(Clone, Default)]
+    struct Capture(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Capture {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_tracing_records_exchange_without_secrets() {
+        let server = TestServer::start(RndcAlg::SHA256, None);
+        let client = server.client("sha256");
+        let capture = Capture::default();
+        let writer = capture.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::DEBUG)
+            .with_span_events(tracing_subscriber::fmt::format::FmtSpan::CLOSE)
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .finish();
+
+        tracing::subscriber::with_default(subscriber, || {
+            // Tests running in parallel may register the callsites first, on
+            // threads without a subscriber, which caches them as disabled.
+            // Once a command has registered them all, rebuilding their
+            // interest enables them for this thread again.
+            client.rndc_command("status").unwrap();
+            tracing::callsite::rebuild_interest_cache();
+            capture.0.lock().unwrap().clear();
+
+            client.rndc_command("status").unwrap();
+        });
+
+        let output = String::from_utf8(capture.0.lock().unwrap().clone()).unwrap();
+        for expected in [
+            "rndc_command",
+            "rndc_handshake",
+            "get_stream",
+            "read_packet",
+            &format!("server={}", server.addr),
+            "command=\"status\"",
+            "bytes_sent=",
+            "bytes_received=",
+            "latency_us=",
+            "result=true",
+            "result_code=0",
+            "key=rndc-key",
+        ] {
+            assert!(
+                output.contains(expected),
+                "missing {}: {}",
+                expected,
+                output
+            );
+        }
+        assert!(!output.contains("nonce"));
+        assert!(!output.contains(TEST_SECRET_B64));
+        assert!(!output.contains("bindizr"));
+    }
+}
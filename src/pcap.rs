@@ -0,0 +1,389 @@
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::RndcError;
+use crate::internal::log_file::LogFile;
+use crate::transport::{Connection, Transport};
+
+/// Raw IPv4 packets without a link layer header.
+const LINKTYPE_RAW: u32 = 101;
+const SNAPLEN: u32 = 65535;
+
+const FIN: u8 = 0x01;
+const SYN: u8 = 0x02;
+const PSH: u8 = 0x08;
+const ACK: u8 = 0x10;
+
+/// Addresses from the documentation range, for the client and for servers
+/// that were not given as an IPv4 address.
+const CLIENT_IP: Ipv4Addr = Ipv4Addr::new(192, 0, 2, 1);
+const SERVER_IP: Ipv4Addr = Ipv4Addr::new(192, 0, 2, 53);
+const DEFAULT_PORT: u16 = 953;
+
+/// Largest payload that fits in one IPv4 packet after both headers.
+const MAX_SEGMENT: usize = 65535 - 40;
+
+/// Wraps another transport and writes the exchanged bytes to a pcap file,
+/// framed as TCP/IP segments so standard packet tools can open it without
+/// a real capture.
+///
+/// Each connection gets a synthesized handshake, a segment for every chunk
+/// of bytes written or read, and a teardown when it is shut down or
+/// dropped. The client is 192.0.2.1 with a port counted up from 49152; the
+/// server keeps its address when it is given as an IPv4 address and is
+/// 192.0.2.53 otherwise, on its port or 953.
+///
+/// Records are flushed as they are written. A failed write leaves the
+/// exchange itself alone; the first one is kept for `write_error`.
+#[derive(Debug)]
+pub struct PcapTransport {
+    inner: Arc<dyn Transport>,
+    file: Arc<LogFile>,
+    connections: AtomicU32,
+}
+
+impl PcapTransport {
+    /// Capture the traffic of `inner` to `path`, replacing the file.
+    pub fn create(path: impl AsRef<Path>, inner: Arc<dyn Transport>) -> Result<Self, RndcError> {
+        let mut header = Vec::with_capacity(24);
+        header.extend_from_slice(&0xa1b2c3d4u32.to_le_bytes());
+        header.extend_from_slice(&2u16.to_le_bytes());
+        header.extend_from_slice(&4u16.to_le_bytes());
+        header.extend_from_slice(&[0; 8]); // timezone offset, timestamp accuracy
+        header.extend_from_slice(&SNAPLEN.to_le_bytes());
+        header.extend_from_slice(&LINKTYPE_RAW.to_le_bytes());
+
+        let file = File::create(path.as_ref())
+            .and_then(|mut file| file.write_all(&header).map(|_| file))
+            .map_err(|e| {
+                RndcError::InvalidArgument(format!(
+                    "Failed to create pcap file {}: {}",
+                    path.as_ref().display(),
+                    e
+                ))
+            })?;
+
+        Ok(PcapTransport {
+            inner,
+            file: Arc::new(LogFile::new(file, "pcap file")),
+            connections: AtomicU32::new(0),
+        })
+    }
+
+    /// The first error writing the capture, if any.
+    pub fn write_error(&self) -> Option<&io::Error> {
+        self.file.error()
+    }
+}
+
+/// The address to show for `server`, which may also be a host name.
+fn server_addr(server: &str) -> SocketAddrV4 {
+    match server.parse::<SocketAddr>() {
+        Ok(SocketAddr::V4(addr)) => addr,
+        Ok(addr) => SocketAddrV4::new(SERVER_IP, addr.port()),
+        Err(_) => {
+            let port = server
+                .rsplit_once(':')
+                .and_then(|(_, port)| port.parse().ok())
+                .unwrap_or(DEFAULT_PORT);
+            SocketAddrV4::new(SERVER_IP, port)
+        }
+    }
+}
+
+impl Transport for PcapTransport {
+    fn connect(&self, server: &str) -> io::Result<Box<dyn Connection>> {
+        let inner = self.inner.connect(server)?;
+        let id = self.connections.fetch_add(1, Ordering::Relaxed);
+
+        let mut conn = PcapConnection {
+            inner,
+            file: self.file.clone(),
+            client: SocketAddrV4::new(CLIENT_IP, 49152 + (id % 16384) as u16),
+            server: server_addr(server),
+            client_seq: 0,
+            server_seq: 0,
+            client_fin: false,
+            server_fin: false,
+        };
+        conn.segment(Side::Client, SYN, &[]);
+        conn.segment(Side::Server, SYN | ACK, &[]);
+        conn.segment(Side::Client, ACK, &[]);
+        Ok(Box::new(conn))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Side {
+    Client,
+    Server,
+}
+
+#[derive(Debug)]
+struct PcapConnection {
+    inner: Box<dyn Connection>,
+    file: Arc<LogFile>,
+    client: SocketAddrV4,
+    server: SocketAddrV4,
+    /// Next sequence number each side sends.
+    client_seq: u32,
+    server_seq: u32,
+    client_fin: bool,
+    server_fin: bool,
+}
+
+impl PcapConnection {
+    /// Write one segment sent by `from`, advancing its sequence number.
+    fn segment(&mut self, from: Side, flags: u8, payload: &[u8]) {
+        let (src, dst, seq, ack) = match from {
+            Side::Client => (self.client, self.server, self.client_seq, self.server_seq),
+            Side::Server => (self.server, self.client, self.server_seq, self.client_seq),
+        };
+        // the very first SYN acknowledges nothing
+        let ack = if flags & ACK != 0 { ack } else { 0 };
+        let packet = tcp_packet(src, dst, seq, ack, flags, payload);
+
+        let advance = payload.len() as u32 + u32::from(flags & (SYN | FIN) != 0);
+        match from {
+            Side::Client => self.client_seq = self.client_seq.wrapping_add(advance),
+            Side::Server => self.server_seq = self.server_seq.wrapping_add(advance),
+        }
+
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let mut record = Vec::with_capacity(16 + packet.len());
+        record.extend_from_slice(&(time.as_secs() as u32).to_le_bytes());
+        record.extend_from_slice(&time.subsec_micros().to_le_bytes());
+        record.extend_from_slice(&(packet.len() as u32).to_le_bytes());
+        record.extend_from_slice(&(packet.len() as u32).to_le_bytes());
+        record.extend_from_slice(&packet);
+
+        self.file.append(&record);
+    }
+
+    fn data(&mut self, from: Side, payload: &[u8]) {
+        for chunk in payload.chunks(MAX_SEGMENT) {
+            self.segment(from, PSH | ACK, chunk);
+        }
+    }
+
+    /// The client's FIN, the server's FIN if it has not sent one yet, and
+    /// the last acknowledgement.
+    fn close(&mut self) {
+        if self.client_fin {
+            return;
+        }
+        self.client_fin = true;
+        self.segment(Side::Client, FIN | ACK, &[]);
+        if self.server_fin {
+            self.segment(Side::Server, ACK, &[]);
+        } else {
+            self.server_fin = true;
+            self.segment(Side::Server, FIN | ACK, &[]);
+            self.segment(Side::Client, ACK, &[]);
+        }
+    }
+}
+
+impl Read for PcapConnection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if n > 0 {
+            self.data(Side::Server, &buf[..n]);
+        } else if !buf.is_empty() && !self.server_fin {
+            // the server closed its side
+            self.server_fin = true;
+            self.segment(Side::Server, FIN | ACK, &[]);
+        }
+        Ok(n)
+    }
+}
+
+impl Write for PcapConnection {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.data(Side::Client, &buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl Connection for PcapConnection {
+    fn shutdown(&mut self) -> io::Result<()> {
+        self.close();
+        self.inner.shutdown()
+    }
+
+    fn peer_closed(&mut self) -> bool {
+        self.inner.peer_closed()
+    }
+}
+
+impl Drop for PcapConnection {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+/// An IPv4 packet holding one TCP segment, with both checksums filled in.
+fn tcp_packet(
+    src: SocketAddrV4,
+    dst: SocketAddrV4,
+    seq: u32,
+    ack: u32,
+    flags: u8,
+    payload: &[u8],
+) -> Vec<u8> {
+    let tcp_len = 20 + payload.len();
+    let mut packet = Vec::with_capacity(20 + tcp_len);
+
+    // version and header length, DSCP, total length, id, don't fragment,
+    // TTL, protocol, checksum
+    packet.extend_from_slice(&[0x45, 0]);
+    packet.extend_from_slice(&((20 + tcp_len) as u16).to_be_bytes());
+    packet.extend_from_slice(&[0, 0, 0x40, 0, 64, 6, 0, 0]);
+    packet.extend_from_slice(&src.ip().octets());
+    packet.extend_from_slice(&dst.ip().octets());
+    let sum = checksum(&[&packet]);
+    packet[10..12].copy_from_slice(&sum.to_be_bytes());
+
+    // ports, sequence and acknowledgement numbers, header length, flags,
+    // window, checksum, urgent pointer
+    packet.extend_from_slice(&src.port().to_be_bytes());
+    packet.extend_from_slice(&dst.port().to_be_bytes());
+    packet.extend_from_slice(&seq.to_be_bytes());
+    packet.extend_from_slice(&ack.to_be_bytes());
+    packet.extend_from_slice(&[0x50, flags, 0xff, 0xff, 0, 0, 0, 0]);
+    packet.extend_from_slice(payload);
+
+    let mut pseudo = Vec::with_capacity(12);
+    pseudo.extend_from_slice(&src.ip().octets());
+    pseudo.extend_from_slice(&dst.ip().octets());
+    pseudo.extend_from_slice(&[0, 6]);
+    pseudo.extend_from_slice(&(tcp_len as u16).to_be_bytes());
+    let sum = checksum(&[&pseudo, &packet[20..]]);
+    packet[36..38].copy_from_slice(&sum.to_be_bytes());
+
+    packet
+}
+
+/// The Internet checksum of `parts`. Only the last part may have an odd
+/// length.
+fn checksum(parts: &[&[u8]]) -> u16 {
+    let mut sum: u32 = 0;
+    for part in parts {
+        for pair in part.chunks(2) {
+            sum += u32::from(u16::from_be_bytes([pair[0], *pair.get(1).unwrap_or(&0)]));
+        }
+    }
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TcpTransport;
+    use crate::internal::constants::RndcAlg;
+    use crate::internal::test_server::TestServer;
+
+    /// The IPv4 packets of a pcap file written by `PcapTransport`.
+    fn read_pcap(path: &Path) -> Vec<Vec<u8>> {
+        let bytes = std::fs::read(path).unwrap();
+        assert_eq!(bytes[0..4], [0xd4, 0xc3, 0xb2, 0xa1]);
+        assert_eq!(u32::from_le_bytes(bytes[20..24].try_into().unwrap()), 101);
+
+        let mut packets = Vec::new();
+        let mut rest = &bytes[24..];
+        while !rest.is_empty() {
+            let len = u32::from_le_bytes(rest[8..12].try_into().unwrap()) as usize;
+            packets.push(rest[16..16 + len].to_vec());
+            rest = &rest[16 + len..];
+        }
+        packets
+    }
+
+    #[test]
+    fn test_pcap_capture() {
+        let path = std::env::temp_dir().join(format!("rndc-{}-capture.pcap", std::process::id()));
+        let server = TestServer::start(RndcAlg::SHA256, None);
+        let pcap = PcapTransport::create(&path, Arc::new(TcpTransport)).unwrap();
+        let client = server.client("sha256").with_transport(Arc::new(pcap));
+
+        client.rndc_command("status").unwrap();
+        let mut session = client.session().unwrap();
+        session.rndc_command("reload example.com").unwrap();
+        drop(session);
+
+        let packets = read_pcap(&path);
+        let port = server.addr.port();
+        let mut sent = Vec::new();
+        let mut summary = Vec::new();
+        for packet in &packets {
+            // both checksums verify to zero
+            assert_eq!(checksum(&[&packet[..20]]), 0);
+            let mut pseudo = packet[12..20].to_vec();
+            pseudo.extend_from_slice(&[0, 6]);
+            pseudo.extend_from_slice(&((packet.len() - 20) as u16).to_be_bytes());
+            assert_eq!(checksum(&[&pseudo, &packet[20..]]), 0);
+
+            let src_port = u16::from_be_bytes([packet[20], packet[21]]);
+            let from_client = src_port != port;
+            let flags = packet[33];
+            if from_client && packet.len() > 40 {
+                sent.extend_from_slice(&packet[40..]);
+            }
+            summary.push((from_client, flags));
+        }
+        // replies can take more than one read, and so more than one segment
+        summary.dedup();
+
+        let handshake = [(true, SYN), (false, SYN | ACK), (true, ACK)];
+        let data = [(true, PSH | ACK), (false, PSH | ACK)];
+        let exchange = [data, data].concat();
+        let teardown = [(true, FIN | ACK), (false, FIN | ACK), (true, ACK)];
+        let connection: Vec<(bool, u8)> = [&handshake[..], &exchange, &teardown].concat();
+        assert_eq!(summary, [connection.clone(), connection].concat());
+
+        // the client's segments carry its packets in order
+        let text = String::from_utf8_lossy(&sent);
+        let status = text.find("status").unwrap();
+        let reload = text.find("reload example.com").unwrap();
+        assert!(status < reload);
+
+        // connections get their own client ports
+        let ports: Vec<u16> = packets
+            .iter()
+            .filter(|p| p[33] == SYN)
+            .map(|p| u16::from_be_bytes([p[20], p[21]]))
+            .collect();
+        assert_eq!(ports, [49152, 49153]);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_server_addr() {
+        assert_eq!(
+            server_addr("10.0.0.1:5953"),
+            "10.0.0.1:5953".parse::<SocketAddrV4>().unwrap()
+        );
+        assert_eq!(server_addr("ns1.example.com:953").ip(), &SERVER_IP);
+        assert_eq!(server_addr("[::1]:954"), SocketAddrV4::new(SERVER_IP, 954));
+        assert_eq!(
+            server_addr("ns1"),
+            SocketAddrV4::new(SERVER_IP, DEFAULT_PORT)
+        );
+    }
+}
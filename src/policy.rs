@@ -0,0 +1,511 @@
+use crate::RndcClient;
+use crate::error::RndcError;
+use crate::internal::args::split_command;
+
+/// Commands that act on one zone, or on every zone when none is given.
+const ZONE_VERBS: &[&str] = &[
+    "reload",
+    "refresh",
+    "retransfer",
+    "freeze",
+    "thaw",
+    "sync",
+    "notify",
+    "zonestatus",
+    "addzone",
+    "delzone",
+    "modzone",
+    "showzone",
+    "sign",
+    "loadkeys",
+    "signing",
+    "dnssec",
+];
+
+/// Commands that take a view and act on every view when none is given.
+const VIEW_VERBS: &[&str] = &[
+    "flush",
+    "flushname",
+    "flushtree",
+    "dumpdb",
+    "serve-stale",
+    "servestale",
+    "validation",
+    "nta",
+    "tsig-delete",
+    "managed-keys",
+    "mkeys",
+    "secroots",
+    "fetchlimit",
+];
+
+/// Client-side allow and deny lists for outgoing commands.
+///
+/// Commands are matched by verb and by the zone and view they act on.
+/// Patterns may use `*` as a wildcard, e.g. `*.example.com`; zone names are
+/// compared case-insensitively and without a trailing dot, verbs
+/// case-insensitively. Deny lists win over allow lists. When zones or views
+/// are restricted, commands that would act on every zone or view, like a
+/// bare `reload`, are denied.
+///
+/// Backslash escapes, comments and unquoted `(`, `)`, `;`, `}` or a `{` glued
+/// to a word are refused in the words before a configuration block, since
+/// named's lexer would read those words differently from the policy.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CommandPolicy {
+    allowed_verbs: Option<Vec<String>>,
+    denied_verbs: Vec<String>,
+    allowed_zones: Option<Vec<String>>,
+    denied_zones: Vec<String>,
+    allowed_views: Option<Vec<String>>,
+    denied_views: Vec<String>,
+}
+
+impl CommandPolicy {
+    /// A policy that allows every command until restricted.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only allow these verbs (and others allowed by earlier calls).
+    pub fn allow_verbs(mut self, verbs: &[&str]) -> Self {
+        self.allowed_verbs
+            .get_or_insert_with(Vec::new)
+            .extend(verbs.iter().map(|v| v.to_ascii_lowercase()));
+        self
+    }
+
+    pub fn deny_verbs(mut self, verbs: &[&str]) -> Self {
+        self.denied_verbs
+            .extend(verbs.iter().map(|v| v.to_ascii_lowercase()));
+        self
+    }
+
+    /// Only allow zone commands for zones matching these patterns.
+    pub fn allow_zones(mut self, patterns: &[&str]) -> Self {
+        extend(&mut self.allowed_zones, patterns);
+        self
+    }
+
+    pub fn deny_zones(mut self, patterns: &[&str]) -> Self {
+        self.denied_zones
+            .extend(patterns.iter().map(|p| p.to_string()));
+        self
+    }
+
+    /// Only allow commands for views matching these patterns.
+    pub fn allow_views(mut self, patterns: &[&str]) -> Self {
+        extend(&mut self.allowed_views, patterns);
+        self
+    }
+
+    pub fn deny_views(mut self, patterns: &[&str]) -> Self {
+        self.denied_views
+            .extend(patterns.iter().map(|p| p.to_string()));
+        self
+    }
+
+    /// Check `command` against the policy, returning
+    /// `RndcError::PolicyDenied` if it may not be sent.
+    pub fn check(&self, command: &str) -> Result<(), RndcError> {
+        if let Some(c) = misread_char(command) {
+            return Err(RndcError::PolicyDenied(format!(
+                "Character {:?} is not allowed: {}",
+                c, command
+            )));
+        }
+
+        let words = split_command(command);
+        let Some((verb, args)) = words.split_first() else {
+            return Err(RndcError::PolicyDenied("Empty command".to_string()));
+        };
+        let verb = verb.to_ascii_lowercase();
+
+        let denied = |what: &str| {
+            Err(RndcError::PolicyDenied(format!(
+                "{} is not allowed: {}",
+                what, command
+            )))
+        };
+
+        if !permits(&self.allowed_verbs, &self.denied_verbs, &verb, false) {
+            return denied(&format!("Command {}", verb));
+        }
+
+        let (zone, views) = targets(&verb, args);
+
+        let zones_restricted = self.allowed_zones.is_some() || !self.denied_zones.is_empty();
+        if zones_restricted && ZONE_VERBS.contains(&verb.as_str()) {
+            match zone {
+                Some(zone) if permits(&self.allowed_zones, &self.denied_zones, zone, true) => {}
+                Some(zone) => return denied(&format!("Zone {}", zone)),
+                None => return denied("A command for every zone"),
+            }
+        }
+
+        let views_restricted = self.allowed_views.is_some() || !self.denied_views.is_empty();
+        let view_scoped =
+            ZONE_VERBS.contains(&verb.as_str()) || VIEW_VERBS.contains(&verb.as_str());
+        if views_restricted && view_scoped {
+            if views.is_empty() {
+                return denied("A command for every view");
+            }
+            for view in views {
+                if !permits(&self.allowed_views, &self.denied_views, view, false) {
+                    return denied(&format!("View {}", view));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl RndcClient {
+    /// Check every command against `policy` before it is signed. Denied
+    /// commands fail with `RndcError::PolicyDenied` without connecting.
+    pub fn with_policy(mut self, policy: CommandPolicy) -> Self {
+        self.policy = Some(std::sync::Arc::new(policy));
+        self
+    }
+
+    pub(crate) fn check_policy(&self, command: &str) -> Result<(), RndcError> {
+        match &self.policy {
+            Some(policy) => policy.check(command),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The first character before any configuration block that named's lexer
+/// reads differently from `split_command`: a backslash, which escapes the
+/// next character or starts a `\DDD` code, the start of a `/* */`, `//` or
+/// `#` comment, or an unquoted `(`, `)`, `;`, `}` or `{` glued to a word,
+/// which named treats as a token of its own.
+fn misread_char(command: &str) -> Option<char> {
+    let mut quoted = false;
+    let mut prev = ' ';
+    let mut chars = command.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => return Some(c),
+            '"' => quoted = !quoted,
+            _ if quoted => {}
+            // the configuration block of addzone and modzone
+            '{' if prev.is_whitespace() => return None,
+            '{' | '}' | ';' | '(' | ')' | '#' => return Some(c),
+            '/' if matches!(chars.peek(), Some('*' | '/')) => return Some(c),
+            _ => {}
+        }
+        prev = c;
+    }
+    None
+}
+
+fn extend(list: &mut Option<Vec<String>>, items: &[&str]) {
+    list.get_or_insert_with(Vec::new)
+        .extend(items.iter().map(|i| i.to_string()));
+}
+
+fn permits(allowed: &Option<Vec<String>>, denied: &[String], value: &str, is_zone: bool) -> bool {
+    let matches = |pattern: &String| {
+        if is_zone {
+            glob_match(&normalize_zone(pattern), &normalize_zone(value))
+        } else {
+            glob_match(pattern, value)
+        }
+    };
+
+    !denied.iter().any(matches)
+        && allowed
+            .as_ref()
+            .is_none_or(|allowed| allowed.iter().any(matches))
+}
+
+fn normalize_zone(name: &str) -> String {
+    let name = name.to_ascii_lowercase();
+    match name.strip_suffix('.') {
+        Some(stripped) if !stripped.is_empty() => stripped.to_string(),
+        _ => name,
+    }
+}
+
+/// Match `value` against `pattern`, where `*` matches any run of characters.
+fn glob_match(pattern: &str, value: &str) -> bool {
+    let pattern = pattern.as_bytes();
+    let value = value.as_bytes();
+    let (mut p, mut v) = (0, 0);
+    let mut backtrack = None;
+
+    while v < value.len() {
+        if p < pattern.len() && pattern[p] == b'*' {
+            backtrack = Some((p, v));
+            p += 1;
+        } else if p < pattern.len() && pattern[p] == value[v] {
+            p += 1;
+            v += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            v = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == b'*')
+}
+
+/// The zone and views a command acts on, as far as they can be told from
+/// its arguments.
+fn targets<'a>(verb: &str, args: &'a [String]) -> (Option<&'a str>, Vec<&'a str>) {
+    let positional = |args: &'a [String], with_values: &[&str]| -> Vec<&'a str> {
+        let mut out = Vec::new();
+        let mut i = 0;
+        while i < args.len() {
+            let arg = args[i].as_str();
+            if arg.starts_with('{') {
+                // the configuration block of addzone and modzone
+                break;
+            }
+            if arg.starts_with('-') {
+                i += match arg {
+                    "-nsec3param" if args.get(i + 1).map(String::as_str) == Some("none") => 2,
+                    "-nsec3param" => 5,
+                    flag if with_values.contains(&flag) => 2,
+                    _ => 1,
+                };
+                continue;
+            }
+            out.push(arg);
+            i += 1;
+        }
+        out
+    };
+
+    if ZONE_VERBS.contains(&verb) {
+        let mut words = positional(args, &["-clear", "-serial", "-key", "-when", "-alg"]);
+        if verb == "dnssec" {
+            words.retain(|w| !matches!(*w, "published" | "withdrawn"));
+        }
+        // zone [class [view]]
+        return (
+            words.first().copied(),
+            words.get(2).copied().into_iter().collect(),
+        );
+    }
+
+    let views = match verb {
+        "flush" => positional(args, &[]).into_iter().take(1).collect(),
+        "flushname" | "flushtree" | "tsig-delete" => {
+            positional(args, &[]).get(1).copied().into_iter().collect()
+        }
+        "dumpdb" => positional(args, &[]),
+        // action [class [view]]
+        "serve-stale" | "servestale" | "managed-keys" | "mkeys" => {
+            positional(args, &[]).get(2).copied().into_iter().collect()
+        }
+        // [-] [view ...]
+        "secroots" => positional(args, &[]),
+        // [view]
+        "fetchlimit" => positional(args, &[]),
+        // action [view]
+        "validation" => positional(args, &[]).get(1).copied().into_iter().collect(),
+        // domain [view]
+        "nta" => positional(args, &["-class", "-lifetime"])
+            .get(1)
+            .copied()
+            .into_iter()
+            .collect(),
+        _ => Vec::new(),
+    };
+    (None, views)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RndcAlg;
+    use crate::internal::test_server::TestServer;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*", ""));
+        assert!(glob_match("*.example.com", "www.example.com"));
+        assert!(!glob_match("*.example.com", "example.com"));
+        assert!(glob_match("a*b*c", "aXXbYYc"));
+        assert!(!glob_match("a*b", "aXXc"));
+        assert!(glob_match("internal", "internal"));
+    }
+
+    #[test]
+    fn test_verb_policy() {
+        let policy = CommandPolicy::new()
+            .allow_verbs(&["reload", "zonestatus"])
+            .deny_verbs(&["stop", "halt", "delzone"]);
+
+        assert!(policy.check("reload example.com").is_ok());
+        assert!(policy.check("ZONESTATUS example.com").is_ok());
+        assert!(matches!(
+            policy.check("stop"),
+            Err(RndcError::PolicyDenied(_))
+        ));
+        assert!(policy.check("flush").is_err());
+        assert!(policy.check("").is_err());
+
+        let policy = CommandPolicy::new().deny_verbs(&["halt"]);
+        assert!(policy.check("stop").is_ok());
+        assert!(policy.check("halt").is_err());
+
+        // patterns are case-insensitive too
+        let policy = CommandPolicy::new()
+            .allow_verbs(&["Reload", "STOP"])
+            .deny_verbs(&["Stop"]);
+        assert!(policy.check("reload").is_ok());
+        assert!(policy.check("stop").is_err());
+        assert!(policy.check("STOP").is_err());
+    }
+
+    #[test]
+    fn test_policy_refuses_escapes() {
+        let policy = CommandPolicy::new().deny_zones(&["secret.example.com"]);
+
+        // named reads both as secret.example.com
+        assert!(policy.check("reload secret\\.example.com").is_err());
+        assert!(policy.check("reload \\115ecret.example.com").is_err());
+        assert!(policy.check("reload \"secret\\046example.com\"").is_err());
+        assert!(
+            policy
+                .check("addzone a.example.com { type primary; file \"C:\\\\a.db\"; };")
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_policy_refuses_parentheses() {
+        let policy = CommandPolicy::new().allow_zones(&["*.example.com"]);
+
+        // named splits these into tokens the policy never saw
+        assert!(policy.check("reload a.example.com(").is_err());
+        assert!(
+            policy
+                .check("reload (secret.example.org) a.example.com")
+                .is_err()
+        );
+        assert!(policy.check("reload \"a(b).example.com\"").is_ok());
+        assert!(
+            policy
+                .check("addzone a.example.com { type primary; file \"a\"; also-notify { 192.0.2.1; }; };")
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_policy_refuses_comments_and_glued_braces() {
+        let policy = CommandPolicy::new().allow_zones(&["*.example.com"]);
+
+        // named's config parser skips comments and splits at braces and
+        // semicolons, so it would add evil.org or secret.example.com
+        assert!(
+            policy
+                .check("addzone /*a.example.com */ evil.org { type primary; file \"x\"; };")
+                .is_err()
+        );
+        assert!(
+            policy
+                .check("addzone #a.example.com\nevil.org { type primary; file \"x\"; };")
+                .is_err()
+        );
+        assert!(
+            policy
+                .check("addzone //a.example.com\nevil.org { type primary; file \"x\"; };")
+                .is_err()
+        );
+        assert!(policy.check("reload a.example.com;").is_err());
+
+        let policy = CommandPolicy::new().deny_zones(&["secret.example.com"]);
+        assert!(
+            policy
+                .check("addzone secret.example.com{ type primary; file \"x\"; };")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_zone_policy() {
+        let policy = CommandPolicy::new()
+            .allow_zones(&["*.example.com", "example.com"])
+            .deny_zones(&["secret.example.com"]);
+
+        assert!(policy.check("reload example.com").is_ok());
+        assert!(policy.check("reload WWW.Example.COM.").is_ok());
+        assert!(policy.check("sync -clean a.example.com").is_ok());
+        assert!(policy.check("signing -clear all a.example.com").is_ok());
+        assert!(
+            policy
+                .check("signing -nsec3param 1 0 10 auto a.example.com")
+                .is_ok()
+        );
+        assert!(
+            policy
+                .check("addzone new.example.com { type primary; file \"x\"; };")
+                .is_ok()
+        );
+        assert!(policy.check("reload example.org").is_err());
+        assert!(policy.check("reload secret.example.com").is_err());
+        assert!(policy.check("signing -clear all example.org").is_err());
+        // a bare reload would reload every zone
+        assert!(policy.check("reload").is_err());
+        // commands without a zone are not affected
+        assert!(policy.check("status").is_ok());
+    }
+
+    #[test]
+    fn test_view_policy() {
+        let policy = CommandPolicy::new().allow_views(&["internal"]);
+
+        assert!(policy.check("reload example.com IN internal").is_ok());
+        assert!(policy.check("flushname www.example.com internal").is_ok());
+        assert!(policy.check("dumpdb -cache internal").is_ok());
+        assert!(policy.check("serve-stale status IN internal").is_ok());
+        assert!(policy.check("managed-keys status IN internal").is_ok());
+        assert!(policy.check("secroots - internal").is_ok());
+        assert!(policy.check("fetchlimit internal").is_ok());
+        assert!(policy.check("managed-keys destroy IN external").is_err());
+        assert!(policy.check("mkeys refresh").is_err());
+        assert!(policy.check("secroots external").is_err());
+        assert!(policy.check("fetchlimit external").is_err());
+        assert!(policy.check("reload example.com IN external").is_err());
+        assert!(policy.check("dumpdb -cache internal external").is_err());
+        assert!(policy.check("flush").is_err());
+        assert!(policy.check("reload example.com").is_err());
+        assert!(policy.check("status").is_ok());
+    }
+
+    #[test]
+    fn test_client_enforces_policy() {
+        let server = TestServer::start(RndcAlg::SHA256, None);
+        let client = server
+            .client("sha256")
+            .with_policy(CommandPolicy::new().allow_verbs(&["status"]));
+
+        assert!(client.rndc_command("status").is_ok());
+        assert!(matches!(
+            client.rndc_command("stop"),
+            Err(RndcError::PolicyDenied(_))
+        ));
+        assert!(matches!(
+            client.session().unwrap().rndc_command("halt"),
+            Err(RndcError::PolicyDenied(_))
+        ));
+        assert!(client.encode_command_into("stop", &mut Vec::new()).is_err());
+        // the denied commands never reached the server
+        assert_eq!(server.commands(), ["status"]);
+
+        // the handshake is not subject to the policy
+        let client = server
+            .client("sha256")
+            .with_policy(CommandPolicy::new().deny_verbs(&["null"]));
+        assert!(client.rndc_command("status").is_ok());
+    }
+}
@@ -0,0 +1,749 @@
+use std::collections::VecDeque;
+use std::future::Future;
+use std::ops::{Deref, DerefMut};
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, Weak};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::error::RndcError;
+use crate::session::RndcSession;
+use crate::{RndcClient, RndcResult};
+
+/// Settings for an `RndcPool`.
+#[derive(Debug, Clone)]
+pub struct RndcPoolConfig {
+    /// Maximum number of live connections to the server.
+    pub max_size: usize,
+    /// Idle connections unused for longer than this are closed.
+    pub idle_timeout: Option<Duration>,
+    /// Idle connections unused for longer than this are checked with a
+    /// `null` command before being handed out by `get` or `get_async`.
+    pub health_check_after: Option<Duration>,
+    /// How long `get` and `get_async` wait for a free connection before
+    /// failing.
+    pub checkout_timeout: Option<Duration>,
+}
+impl Default for RndcPoolConfig {
+    fn default() -> Self {
+        RndcPoolConfig {
+            max_size: 4,
+            idle_timeout: Some(Duration::from_secs(60)),
+            health_check_after: Some(Duration::from_secs(10)),
+            checkout_timeout: Some(Duration::from_secs(30)),
+        }
+    }
+}
+
+struct Idle {
+    session: RndcSession,
+    since: Instant,
+}
+
+struct Waiter {
+    ticket: u64,
+    waker: Option<Waker>,
+}
+
+#[derive(Default)]
+struct PoolState {
+    idle: Vec<Idle>,
+    live: usize,
+    /// Checkouts waiting for a connection, served strictly in order.
+    queue: VecDeque<Waiter>,
+    next_ticket: u64,
+}
+
+enum Acquired {
+    Idle(Box<Idle>),
+    New,
+}
+
+struct PoolInner {
+    client: RndcClient,
+    config: RndcPoolConfig,
+    state: Mutex<PoolState>,
+    available: Condvar,
+}
+
+/// A bounded pool of control channel sessions to one server.
+///
+/// Checkouts are served first come, first served. `get` blocks the calling
+/// thread; `get_async` returns a future that waits without blocking and
+/// works with any executor. Commands themselves use blocking sockets, so
+/// async callers should prefer `rndc_command_async`, which runs the
+/// exchange on a background thread.
+#[derive(Clone)]
+pub struct RndcPool {
+    inner: Arc<PoolInner>,
+}
+
+impl std::fmt::Debug for RndcPool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = self.inner.lock();
+        f.debug_struct("RndcPool")
+            .field("config", &self.inner.config)
+            .field("live", &state.live)
+            .field("idle", &state.idle.len())
+            .field("waiting", &state.queue.len())
+            .finish()
+    }
+}
+
+impl std::fmt::Debug for PoolInner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PoolInner")
+            .field("config", &self.config)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PoolInner {
+    fn lock(&self) -> MutexGuard<'_, PoolState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Wake whoever is now first in line.
+    fn notify(&self, state: &PoolState) {
+        self.available.notify_all();
+        if let Some(waker) = state.queue.front().and_then(|w| w.waker.as_ref()) {
+            waker.wake_by_ref();
+        }
+    }
+
+    fn enqueue(&self, state: &mut PoolState, waker: Option<Waker>) -> u64 {
+        let ticket = state.next_ticket;
+        state.next_ticket += 1;
+        state.queue.push_back(Waiter { ticket, waker });
+        ticket
+    }
+
+    fn dequeue(&self, state: &mut PoolState, ticket: u64) {
+        state.queue.retain(|w| w.ticket != ticket);
+        self.notify(state);
+    }
+
+    /// Close idle sessions past the idle timeout. The sessions are returned
+    /// so they can be dropped outside the lock.
+    fn evict(&self, state: &mut PoolState) -> Vec<RndcSession> {
+        let Some(timeout) = self.config.idle_timeout else {
+            return Vec::new();
+        };
+        let (expired, kept): (Vec<_>, Vec<_>) = state
+            .idle
+            .drain(..)
+            .partition(|idle| idle.since.elapsed() >= timeout);
+        state.idle = kept;
+        state.live -= expired.len();
+        if !expired.is_empty() {
+            self.notify(state);
+        }
+        expired.into_iter().map(|idle| idle.session).collect()
+    }
+
+    fn try_acquire(&self, state: &mut PoolState, ticket: u64) -> Option<Acquired> {
+        if state.queue.front().map(|w| w.ticket) != Some(ticket) {
+            return None;
+        }
+        let acquired = if let Some(idle) = state.idle.pop() {
+            Acquired::Idle(Box::new(idle))
+        } else if state.live < self.config.max_size {
+            state.live += 1;
+            Acquired::New
+        } else {
+            return None;
+        };
+        state.queue.pop_front();
+        self.notify(state);
+        Some(acquired)
+    }
+
+    fn check_health(&self, mut idle: Idle) -> Result<RndcSession, RndcError> {
+        let stale = self
+            .config
+            .health_check_after
+            .is_some_and(|after| idle.since.elapsed() >= after);
+        if stale && idle.session.probe().is_err() {
+            idle.session.close()?;
+            return self.client.session();
+        }
+        Ok(idle.session)
+    }
+
+    fn release(&self, session: Option<RndcSession>) {
+        let mut state = self.lock();
+        match session {
+            Some(session) => state.idle.push(Idle {
+                session,
+                since: Instant::now(),
+            }),
+            None => state.live -= 1,
+        }
+        self.notify(&state);
+    }
+}
+
+impl RndcPool {
+    pub fn new(client: RndcClient, config: RndcPoolConfig) -> Result<Self, RndcError> {
+        if config.max_size == 0 {
+            return Err(RndcError::InvalidArgument(
+                "Pool max_size must be at least 1".to_string(),
+            ));
+        }
+        Ok(RndcPool {
+            inner: Arc::new(PoolInner {
+                client,
+                config,
+                state: Mutex::new(PoolState::default()),
+                available: Condvar::new(),
+            }),
+        })
+    }
+
+    pub fn client(&self) -> &RndcClient {
+        &self.inner.client
+    }
+
+    /// Number of open connections, idle or checked out.
+    pub fn live(&self) -> usize {
+        self.inner.lock().live
+    }
+
+    /// Number of connections waiting in the pool.
+    pub fn idle(&self) -> usize {
+        self.inner.lock().idle.len()
+    }
+
+    /// Close idle connections that exceeded the idle timeout. This also
+    /// happens on every checkout.
+    pub fn evict_idle(&self) {
+        let expired = self.inner.evict(&mut self.inner.lock());
+        drop(expired);
+    }
+
+    /// Check out a connected session, waiting up to `checkout_timeout` for
+    /// one to become free.
+    pub fn get(&self) -> Result<PooledSession, RndcError> {
+        let inner = &self.inner;
+        let deadline = inner.config.checkout_timeout.map(|t| Instant::now() + t);
+
+        let mut state = inner.lock();
+        let mut expired = inner.evict(&mut state);
+        let ticket = inner.enqueue(&mut state, None);
+        let acquired = loop {
+            if let Some(acquired) = inner.try_acquire(&mut state, ticket) {
+                break acquired;
+            }
+            state = match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        inner.dequeue(&mut state, ticket);
+                        return Err(RndcError::Timeout(
+                            "No pooled connection became available".to_string(),
+                        ));
+                    }
+                    inner
+                        .available
+                        .wait_timeout(state, deadline - now)
+                        .unwrap_or_else(|e| e.into_inner())
+                        .0
+                }
+                None => inner
+                    .available
+                    .wait(state)
+                    .unwrap_or_else(|e| e.into_inner()),
+            };
+            expired.extend(inner.evict(&mut state));
+        };
+        drop(state);
+        drop(expired);
+
+        let session = match acquired {
+            Acquired::Idle(idle) => inner.check_health(*idle),
+            Acquired::New => inner.client.session(),
+        };
+        match session {
+            Ok(session) => Ok(PooledSession {
+                pool: inner.clone(),
+                session: Some(session),
+            }),
+            Err(e) => {
+                inner.release(None);
+                Err(e)
+            }
+        }
+    }
+
+    /// Check out a session without blocking the executor while waiting,
+    /// for up to `checkout_timeout`. A new session connects on its first
+    /// command.
+    pub fn get_async(&self) -> Checkout {
+        Checkout {
+            pool: self.inner.clone(),
+            ticket: None,
+            deadline: self
+                .inner
+                .config
+                .checkout_timeout
+                .map(|t| Instant::now() + t),
+            timer: false,
+        }
+    }
+
+    /// Run one command on a pooled session.
+    pub fn rndc_command(&self, command: &str) -> Result<RndcResult, RndcError> {
+        self.get()?.rndc_command(command)
+    }
+
+    /// Run one command on a pooled session from async code. The blocking
+    /// exchange runs on a background thread.
+    pub async fn rndc_command_async(&self, command: &str) -> Result<RndcResult, RndcError> {
+        let mut session = self.get_async().await?;
+        let command = command.to_string();
+        BlockingTask::spawn(move || session.rndc_command(&command)).await
+    }
+}
+
+/// A session checked out of an `RndcPool`, returned to it when dropped.
+#[derive(Debug)]
+pub struct PooledSession {
+    pool: Arc<PoolInner>,
+    session: Option<RndcSession>,
+}
+impl PooledSession {
+    /// Close the connection instead of returning it to the pool.
+    pub fn discard(mut self) {
+        if let Some(mut session) = self.session.take() {
+            let _ = session.close();
+        }
+        self.pool.release(None);
+    }
+}
+impl Deref for PooledSession {
+    type Target = RndcSession;
+
+    fn deref(&self) -> &RndcSession {
+        self.session.as_ref().unwrap()
+    }
+}
+impl DerefMut for PooledSession {
+    fn deref_mut(&mut self) -> &mut RndcSession {
+        self.session.as_mut().unwrap()
+    }
+}
+impl Drop for PooledSession {
+    fn drop(&mut self) {
+        if let Some(session) = self.session.take() {
+            self.pool.release(Some(session));
+        }
+    }
+}
+
+/// Future returned by `RndcPool::get_async`.
+#[derive(Debug)]
+pub struct Checkout {
+    pool: Arc<PoolInner>,
+    ticket: Option<u64>,
+    deadline: Option<Instant>,
+    /// Whether a thread was started to wake the checkout at `deadline`.
+    timer: bool,
+}
+impl Future for Checkout {
+    type Output = Result<PooledSession, RndcError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let pool = self.pool.clone();
+        let mut state = pool.lock();
+        let expired = pool.evict(&mut state);
+
+        let ticket = match self.ticket {
+            Some(ticket) => {
+                if let Some(waiter) = state.queue.iter_mut().find(|w| w.ticket == ticket) {
+                    waiter.waker = Some(cx.waker().clone());
+                }
+                ticket
+            }
+            None => {
+                let ticket = pool.enqueue(&mut state, Some(cx.waker().clone()));
+                self.ticket = Some(ticket);
+                ticket
+            }
+        };
+
+        let Some(acquired) = pool.try_acquire(&mut state, ticket) else {
+            match self.deadline {
+                Some(deadline) if Instant::now() >= deadline => {
+                    pool.dequeue(&mut state, ticket);
+                    self.ticket = None;
+                    return Poll::Ready(Err(RndcError::Timeout(
+                        "No pooled connection became available".to_string(),
+                    )));
+                }
+                Some(deadline) if !self.timer => {
+                    self.timer = true;
+                    wake_at(Arc::downgrade(&pool), ticket, deadline);
+                }
+                _ => {}
+            }
+            return Poll::Pending;
+        };
+        self.ticket = None;
+        drop(state);
+        drop(expired);
+
+        let session = match acquired {
+            Acquired::Idle(idle) => pool.check_health(*idle),
+            Acquired::New => Ok(RndcSession::disconnected(pool.client.clone())),
+        };
+        match session {
+            Ok(session) => Poll::Ready(Ok(PooledSession {
+                pool,
+                session: Some(session),
+            })),
+            Err(e) => {
+                pool.release(None);
+                Poll::Ready(Err(e))
+            }
+        }
+    }
+}
+
+/// Wake the checkout holding `ticket` at `deadline`, so it can time out
+/// without an executor timer.
+fn wake_at(pool: Weak<PoolInner>, ticket: u64, deadline: Instant) {
+    thread::spawn(move || {
+        thread::sleep(deadline.saturating_duration_since(Instant::now()));
+        if let Some(pool) = pool.upgrade()
+            && let Some(waker) = pool
+                .lock()
+                .queue
+                .iter()
+                .find(|w| w.ticket == ticket)
+                .and_then(|w| w.waker.as_ref())
+        {
+            waker.wake_by_ref();
+        }
+    });
+}
+impl Drop for Checkout {
+    fn drop(&mut self) {
+        if let Some(ticket) = self.ticket {
+            self.pool.dequeue(&mut self.pool.lock(), ticket);
+        }
+    }
+}
+
+struct TaskState<T> {
+    result: Option<T>,
+    waker: Option<Waker>,
+}
+
+/// Runs a blocking closure on its own thread and resolves with its result.
+struct BlockingTask<T> {
+    state: Arc<Mutex<TaskState<T>>>,
+}
+impl<T: Send + 'static> BlockingTask<T> {
+    fn spawn(f: impl FnOnce() -> T + Send + 'static) -> Self {
+        let state = Arc::new(Mutex::new(TaskState {
+            result: None,
+            waker: None,
+        }));
+        let task_state = state.clone();
+        thread::spawn(move || {
+            let result = f();
+            let mut state = task_state.lock().unwrap_or_else(|e| e.into_inner());
+            state.result = Some(result);
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+        });
+        BlockingTask { state }
+    }
+}
+impl<T> Future for BlockingTask<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        match state.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CommandPolicy;
+    use crate::internal::constants::RndcAlg;
+    use crate::internal::test_server::TestServer;
+    use std::sync::mpsc;
+    use std::task::Wake;
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        struct ThreadWaker(thread::Thread);
+        impl Wake for ThreadWaker {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark();
+            }
+        }
+
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        let mut future = std::pin::pin!(future);
+        loop {
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(output) => return output,
+                Poll::Pending => thread::park(),
+            }
+        }
+    }
+
+    fn pool(server: &TestServer, config: RndcPoolConfig) -> RndcPool {
+        RndcPool::new(server.client("sha256"), config).unwrap()
+    }
+
+    #[test]
+    fn test_pool_limits_connections() {
+        let server = TestServer::start(RndcAlg::SHA256, None);
+        let pool = pool(
+            &server,
+            RndcPoolConfig {
+                max_size: 2,
+                ..Default::default()
+            },
+        );
+
+        let handles = (0..8)
+            .map(|_| {
+                let pool = pool.clone();
+                thread::spawn(move || {
+                    for _ in 0..5 {
+                        assert!(pool.rndc_command("status").unwrap().result);
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(server.commands().len(), 40);
+        assert!(server.connections() <= 2);
+        assert!(pool.live() <= 2);
+    }
+
+    #[test]
+    fn test_pool_checkout_is_fair() {
+        let server = TestServer::start(RndcAlg::SHA256, None);
+        let pool = pool(
+            &server,
+            RndcPoolConfig {
+                max_size: 1,
+                ..Default::default()
+            },
+        );
+
+        let held = pool.get().unwrap();
+        let (tx, rx) = mpsc::channel();
+        let handles = (0..4)
+            .map(|i| {
+                let waiter_pool = pool.clone();
+                let tx = tx.clone();
+                let handle = thread::spawn(move || {
+                    let _session = waiter_pool.get().unwrap();
+                    tx.send(i).unwrap();
+                });
+                // let each waiter queue up before the next one starts
+                while pool.inner.lock().queue.len() < i + 1 {
+                    thread::yield_now();
+                }
+                handle
+            })
+            .collect::<Vec<_>>();
+
+        drop(held);
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_pool_checkout_timeout() {
+        let server = TestServer::start(RndcAlg::SHA256, None);
+        let pool = pool(
+            &server,
+            RndcPoolConfig {
+                max_size: 1,
+                checkout_timeout: Some(Duration::from_millis(50)),
+                ..Default::default()
+            },
+        );
+
+        let _held = pool.get().unwrap();
+        assert!(matches!(pool.get(), Err(RndcError::Timeout(_))));
+        assert!(pool.inner.lock().queue.is_empty());
+    }
+
+    #[test]
+    fn test_pool_evicts_idle_connections() {
+        let server = TestServer::start(RndcAlg::SHA256, None);
+        let pool = pool(
+            &server,
+            RndcPoolConfig {
+                idle_timeout: Some(Duration::from_millis(20)),
+                ..Default::default()
+            },
+        );
+
+        pool.rndc_command("status").unwrap();
+        assert_eq!(pool.idle(), 1);
+
+        thread::sleep(Duration::from_millis(40));
+        pool.evict_idle();
+        assert_eq!(pool.idle(), 0);
+        assert_eq!(pool.live(), 0);
+
+        pool.rndc_command("status").unwrap();
+        assert_eq!(server.connections(), 2);
+    }
+
+    #[test]
+    fn test_pool_health_check_replaces_closed_connection() {
+        let server = TestServer::start(RndcAlg::SHA256, Some(1));
+        let pool = pool(
+            &server,
+            RndcPoolConfig {
+                health_check_after: Some(Duration::ZERO),
+                ..Default::default()
+            },
+        );
+
+        // the server closes each connection after one command
+        for _ in 0..3 {
+            assert!(pool.rndc_command("status").unwrap().result);
+        }
+        assert_eq!(server.commands(), vec!["status"; 3]);
+    }
+
+    #[test]
+    fn test_pool_health_check_ignores_policy() {
+        let server = TestServer::start(RndcAlg::SHA256, None);
+        let client = server
+            .client("sha256")
+            .with_policy(CommandPolicy::new().allow_verbs(&["reload"]));
+        let pool = RndcPool::new(
+            client,
+            RndcPoolConfig {
+                health_check_after: Some(Duration::ZERO),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        for _ in 0..3 {
+            assert!(pool.rndc_command("reload").unwrap().result);
+        }
+        // a probe refused by the policy would have replaced the connection
+        assert_eq!(server.connections(), 1);
+        assert_eq!(server.commands(), vec!["reload"; 3]);
+    }
+
+    #[test]
+    fn test_pool_async() {
+        let server = TestServer::start(RndcAlg::SHA256, None);
+        let pool = pool(
+            &server,
+            RndcPoolConfig {
+                max_size: 1,
+                ..Default::default()
+            },
+        );
+
+        let held = pool.get().unwrap();
+        let waiting = thread::spawn({
+            let pool = pool.clone();
+            move || block_on(pool.rndc_command_async("reload"))
+        });
+        while pool.inner.lock().queue.is_empty() {
+            thread::yield_now();
+        }
+        drop(held);
+
+        let res = waiting.join().unwrap().unwrap();
+        assert_eq!(res.text.as_deref(), Some("reload"));
+        assert_eq!(server.connections(), 1);
+    }
+
+    #[test]
+    fn test_pool_async_checkout_timeout() {
+        let server = TestServer::start(RndcAlg::SHA256, None);
+        let pool = pool(
+            &server,
+            RndcPoolConfig {
+                max_size: 1,
+                checkout_timeout: Some(Duration::from_millis(50)),
+                ..Default::default()
+            },
+        );
+
+        let _held = pool.get().unwrap();
+        assert!(matches!(
+            block_on(pool.get_async()),
+            Err(RndcError::Timeout(_))
+        ));
+        assert!(pool.inner.lock().queue.is_empty());
+    }
+
+    #[test]
+    fn test_pool_async_health_check() {
+        let server = TestServer::start(RndcAlg::SHA256, Some(1));
+        let pool = pool(
+            &server,
+            RndcPoolConfig {
+                health_check_after: Some(Duration::ZERO),
+                ..Default::default()
+            },
+        );
+
+        // the server closes the connection after one command, which the
+        // checkout finds and replaces before handing the session out
+        assert!(block_on(pool.rndc_command_async("status")).unwrap().result);
+        server.wait_closed(1);
+        let session = block_on(pool.get_async()).unwrap();
+        assert!(session.is_connected());
+        assert_eq!(server.connections(), 2);
+    }
+
+    #[test]
+    fn test_pool_dropped_checkout_leaves_queue() {
+        let server = TestServer::start(RndcAlg::SHA256, None);
+        let pool = pool(
+            &server,
+            RndcPoolConfig {
+                max_size: 1,
+                ..Default::default()
+            },
+        );
+
+        let held = pool.get().unwrap();
+        {
+            let mut checkout = pool.get_async();
+            let waker = Waker::noop();
+            let mut cx = Context::from_waker(waker);
+            assert!(Pin::new(&mut checkout).poll(&mut cx).is_pending());
+            assert_eq!(pool.inner.lock().queue.len(), 1);
+        }
+        assert!(pool.inner.lock().queue.is_empty());
+
+        drop(held);
+        assert!(pool.get().is_ok());
+    }
+}
@@ -0,0 +1,321 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::error::RndcError;
+use crate::internal::constants::RndcAlg;
+use crate::key::RndcKey;
+
+/// A source of control channel keys.
+///
+/// The client asks its providers for a key on every handshake, so a provider
+/// that reloads its source lets a long-lived client pick up a rotated key.
+/// When the server rejects a key, the client calls `refresh` and retries
+/// once if the provider then returns a different key.
+pub trait KeyProvider: Send + Sync + fmt::Debug {
+    /// The current key.
+    fn key(&self) -> Result<RndcKey, RndcError>;
+
+    /// Drop any cached key so the next call to `key` reads the source again.
+    fn refresh(&self) {}
+}
+
+/// A literal key that never changes.
+impl KeyProvider for RndcKey {
+    fn key(&self) -> Result<RndcKey, RndcError> {
+        Ok(self.clone())
+    }
+}
+
+/// Reads a base64 secret from an environment variable on every handshake.
+#[derive(Debug, Clone)]
+pub struct EnvKeyProvider {
+    name: String,
+    algorithm: String,
+    var: String,
+}
+
+impl EnvKeyProvider {
+    pub fn new(name: &str, algorithm: &str, var: &str) -> Result<Self, RndcError> {
+        RndcAlg::from_string(algorithm)?;
+
+        Ok(EnvKeyProvider {
+            name: name.to_string(),
+            algorithm: algorithm.to_string(),
+            var: var.to_string(),
+        })
+    }
+}
+
+impl KeyProvider for EnvKeyProvider {
+    fn key(&self) -> Result<RndcKey, RndcError> {
+        let secret = std::env::var(&self.var).map_err(|e| {
+            RndcError::InvalidArgument(format!(
+                "Failed to read environment variable {}: {}",
+                self.var, e
+            ))
+        })?;
+        RndcKey::new(&self.name, &self.algorithm, secret.trim())
+    }
+}
+
+/// Reads a key from a BIND key file such as `rndc.key` or `rndc.conf`.
+///
+/// The parsed key is cached and the file is read again when its
+/// modification time changes or after `refresh`.
+#[derive(Debug)]
+pub struct KeyFileProvider {
+    path: PathBuf,
+    key_name: Option<String>,
+    cache: Mutex<Option<(SystemTime, RndcKey)>>,
+}
+
+impl KeyFileProvider {
+    /// Use the key called `key_name`, or the first key in the file.
+    pub fn new(path: impl AsRef<Path>, key_name: Option<&str>) -> Self {
+        KeyFileProvider {
+            path: path.as_ref().to_path_buf(),
+            key_name: key_name.map(str::to_string),
+            cache: Mutex::new(None),
+        }
+    }
+}
+
+impl KeyProvider for KeyFileProvider {
+    fn key(&self) -> Result<RndcKey, RndcError> {
+        let read_err = |e: std::io::Error| {
+            RndcError::InvalidArgument(format!(
+                "Failed to read key file {}: {}",
+                self.path.display(),
+                e
+            ))
+        };
+
+        let modified = std::fs::metadata(&self.path)
+            .and_then(|m| m.modified())
+            .map_err(read_err)?;
+
+        let mut cache = self.cache.lock().unwrap();
+        if let Some((cached_at, key)) = cache.as_ref()
+            && *cached_at == modified
+        {
+            return Ok(key.clone());
+        }
+
+        let text = std::fs::read_to_string(&self.path).map_err(read_err)?;
+        let key = select_key(RndcKey::parse_bind_config(&text)?, self.key_name.as_deref())?;
+        *cache = Some((modified, key.clone()));
+        Ok(key)
+    }
+
+    fn refresh(&self) {
+        *self.cache.lock().unwrap() = None;
+    }
+}
+
+/// Runs a command, e.g. a secrets manager CLI, and reads the key from its
+/// standard output.
+///
+/// The output is either a BIND `key` clause or a bare base64 secret, which
+/// is combined with the name and algorithm given here. The key is cached
+/// for the TTL (five minutes by default) or until `refresh`.
+#[derive(Debug)]
+pub struct CommandKeyProvider {
+    name: String,
+    algorithm: String,
+    program: String,
+    args: Vec<String>,
+    ttl: Duration,
+    cache: Mutex<Option<(Instant, RndcKey)>>,
+}
+
+impl CommandKeyProvider {
+    pub fn new(
+        name: &str,
+        algorithm: &str,
+        program: &str,
+        args: &[&str],
+    ) -> Result<Self, RndcError> {
+        RndcAlg::from_string(algorithm)?;
+
+        Ok(CommandKeyProvider {
+            name: name.to_string(),
+            algorithm: algorithm.to_string(),
+            program: program.to_string(),
+            args: args.iter().map(|a| a.to_string()).collect(),
+            ttl: Duration::from_secs(300),
+            cache: Mutex::new(None),
+        })
+    }
+
+    /// How long a fetched key is used before the command runs again.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    fn fetch(&self) -> Result<RndcKey, RndcError> {
+        let output = Command::new(&self.program)
+            .args(&self.args)
+            .output()
+            .map_err(|e| {
+                RndcError::InvalidArgument(format!("Failed to run {}: {}", self.program, e))
+            })?;
+
+        if !output.status.success() {
+            return Err(RndcError::InvalidArgument(format!(
+                "{} exited with {}: {}",
+                self.program,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+
+        let stdout = String::from_utf8(output.stdout).map_err(|e| {
+            RndcError::InvalidArgument(format!("{} printed invalid UTF-8: {}", self.program, e))
+        })?;
+
+        let keys = RndcKey::parse_bind_config(&stdout)?;
+        if keys.is_empty() {
+            RndcKey::new(&self.name, &self.algorithm, stdout.trim())
+        } else {
+            select_key(keys, Some(&self.name))
+        }
+    }
+}
+
+impl KeyProvider for CommandKeyProvider {
+    fn key(&self) -> Result<RndcKey, RndcError> {
+        let mut cache = self.cache.lock().unwrap();
+        if let Some((fetched_at, key)) = cache.as_ref()
+            && fetched_at.elapsed() < self.ttl
+        {
+            return Ok(key.clone());
+        }
+
+        let key = self.fetch()?;
+        *cache = Some((Instant::now(), key.clone()));
+        Ok(key)
+    }
+
+    fn refresh(&self) {
+        *self.cache.lock().unwrap() = None;
+    }
+}
+
+/// Pick the key called `name`, or the first key if there is no name or a
+/// single key.
+fn select_key(keys: Vec<RndcKey>, name: Option<&str>) -> Result<RndcKey, RndcError> {
+    match name {
+        Some(name) => keys
+            .into_iter()
+            .find(|k| k.name == name)
+            .ok_or_else(|| RndcError::InvalidArgument(format!("No key named {} found", name))),
+        None => keys
+            .into_iter()
+            .next()
+            .ok_or_else(|| RndcError::InvalidArgument("No matching key found".to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RndcClient;
+    use crate::internal::test_server::{TEST_SECRET_B64, TestServer};
+    use std::sync::Arc;
+
+    // base64 of "old-secret"
+    const OLD_SECRET_B64: &str = "b2xkLXNlY3JldA==";
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("rndc-{}-{}", std::process::id(), name))
+    }
+
+    fn key_clause(secret_b64: &str) -> String {
+        format!(
+            "key \"rndc-key\" {{\n\talgorithm hmac-sha256;\n\tsecret \"{}\";\n}};\n",
+            secret_b64
+        )
+    }
+
+    #[test]
+    fn test_file_provider_reloads_rotated_file() {
+        let path = temp_path("file-provider.key");
+        std::fs::write(&path, key_clause(OLD_SECRET_B64)).unwrap();
+
+        let provider = KeyFileProvider::new(&path, Some("rndc-key"));
+        assert_eq!(provider.key().unwrap().secret, b"old-secret");
+
+        // the mtime may not change within the file system's resolution
+        std::fs::write(&path, key_clause(TEST_SECRET_B64)).unwrap();
+        provider.refresh();
+        assert_eq!(provider.key().unwrap().secret, b"bindizr");
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(provider.key().is_err());
+    }
+
+    #[test]
+    fn test_file_provider_requires_named_key() {
+        let path = temp_path("named-key.key");
+        std::fs::write(&path, key_clause(TEST_SECRET_B64)).unwrap();
+
+        assert!(KeyFileProvider::new(&path, None).key().is_ok());
+        assert!(matches!(
+            KeyFileProvider::new(&path, Some("other-key")).key(),
+            Err(RndcError::InvalidArgument(_))
+        ));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_command_provider_caches_until_refresh() {
+        let path = temp_path("command-provider.secret");
+        std::fs::write(&path, OLD_SECRET_B64).unwrap();
+        let path_str = path.to_str().unwrap();
+
+        let provider = CommandKeyProvider::new("rndc-key", "sha256", "cat", &[path_str]).unwrap();
+        assert_eq!(provider.key().unwrap().secret, b"old-secret");
+
+        std::fs::write(&path, key_clause(TEST_SECRET_B64)).unwrap();
+        assert_eq!(provider.key().unwrap().secret, b"old-secret");
+
+        provider.refresh();
+        let key = provider.key().unwrap();
+        assert_eq!(key.secret, b"bindizr");
+        assert_eq!(key.algorithm, RndcAlg::SHA256);
+
+        std::fs::remove_file(&path).unwrap();
+        provider.refresh();
+        assert!(provider.key().is_err());
+    }
+
+    #[test]
+    fn test_client_picks_up_rotated_key() {
+        let path = temp_path("client-rotation.key");
+        std::fs::write(&path, key_clause(OLD_SECRET_B64)).unwrap();
+        let provider =
+            CommandKeyProvider::new("rndc-key", "sha256", "cat", &[path.to_str().unwrap()])
+                .unwrap()
+                .with_ttl(Duration::from_secs(3600));
+        provider.key().unwrap();
+
+        // the server already uses the new secret while the old one is cached
+        std::fs::write(&path, key_clause(TEST_SECRET_B64)).unwrap();
+
+        let server = TestServer::start(RndcAlg::SHA256, None);
+        let client =
+            RndcClient::with_providers(&server.addr.to_string(), vec![Arc::new(provider)]).unwrap();
+
+        assert!(client.rndc_command("status").unwrap().result);
+        assert_eq!(client.active_key().unwrap().secret, b"bindizr");
+        // the rejected handshake, then the one with the refreshed key
+        assert_eq!(server.connections(), 2);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,227 @@
+use crate::error::RndcError;
+use crate::{AuditEvent, Connection, RndcClient, RndcKey, RndcResult};
+
+/// A control channel connection kept open across commands.
+///
+/// named accepts any number of commands on one connection as long as each
+/// carries the nonce from its previous reply, so a session saves the
+/// connect and `null` handshake round trips of `RndcClient::rndc_command`.
+/// An idle connection the server has closed in the meantime is replaced
+/// before the command is sent. Once a command has been written it is never
+/// resent, since named may have run it; if the reply is lost the error is
+/// returned and the next command reconnects.
+#[derive(Debug)]
+pub struct RndcSession {
+    client: RndcClient,
+    stream: Option<Box<dyn Connection>>,
+    nonce: Option<String>,
+    /// The key the connection was opened with.
+    key: Option<RndcKey>,
+}
+
+impl RndcClient {
+    /// Open a session that reuses one connection for multiple commands.
+    pub fn session(&self) -> Result<RndcSession, RndcError> {
+        let mut session = RndcSession::disconnected(self.clone());
+        if !self.is_dry_run() {
+            session.connect()?;
+        }
+        Ok(session)
+    }
+}
+
+impl RndcSession {
+    /// A session that connects on its first command.
+    pub(crate) fn disconnected(client: RndcClient) -> Self {
+        RndcSession {
+            client,
+            stream: None,
+            nonce: None,
+            key: None,
+        }
+    }
+
+    /// The client this session was opened from.
+    pub fn client(&self) -> &RndcClient {
+        &self.client
+    }
+
+    /// Whether the session currently holds an open connection.
+    pub fn is_connected(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    pub fn rndc_command(&mut self, command: &str) -> Result<RndcResult, RndcError> {
+        let mut event = AuditEvent::begin(&self.client.server_url, command);
+        let mut res = match self.client.check_policy(command) {
+            Err(e) => Err(e),
+            Ok(()) if self.client.is_dry_run() => self.client.dry_run_command(command, &mut event),
+            Ok(()) => self.try_command(command, &mut event),
+        };
+        event.finish(&mut res);
+        self.client.notify(&event);
+        res
+    }
+
+    /// Send `null` on the open connection to check that it still works,
+    /// bypassing the policy, observers and dry run. A session without a
+    /// connection has nothing to check.
+    pub(crate) fn probe(&mut self) -> Result<(), RndcError> {
+        if self.stream.is_none() {
+            return Ok(());
+        }
+        let mut event = AuditEvent::begin(&self.client.server_url, "null");
+        self.try_command("null", &mut event).map(drop)
+    }
+
+    /// Shut down the connection. Later commands reconnect.
+    pub fn close(&mut self) -> Result<(), RndcError> {
+        self.nonce = None;
+        match self.stream.take() {
+            Some(mut stream) => self.client.close_stream(stream.as_mut()),
+            None => Ok(()),
+        }
+    }
+
+    fn connect(&mut self) -> Result<(), RndcError> {
+        let (stream, nonce, key) = self.client.rndc_handshake()?;
+        self.stream = Some(stream);
+        self.nonce = Some(nonce);
+        self.key = Some(key);
+        Ok(())
+    }
+
+    fn try_command(
+        &mut self,
+        command: &str,
+        event: &mut AuditEvent,
+    ) -> Result<RndcResult, RndcError> {
+        if self
+            .stream
+            .as_mut()
+            .is_some_and(|stream| stream.peer_closed())
+        {
+            let _ = self.close();
+        }
+        let reused = self.stream.is_some();
+        if !reused {
+            self.connect()?;
+        }
+        let stream = self.stream.as_mut().unwrap();
+        let key = self.key.as_ref().unwrap();
+        event.key_name = Some(key.name.clone());
+
+        let ser = rand::random();
+        event.serial = Some(ser);
+        let res = self
+            .client
+            .write_command(stream.as_mut(), key, command, self.nonce.as_deref(), ser)
+            .map_err(|e| (e, false))
+            .and_then(|()| RndcClient::read_packet(stream.as_mut()).map_err(|e| (e, true)));
+        let res = match res {
+            Ok(res) => res,
+            Err((e, written)) => {
+                // the channel is in an unknown state; start over next time
+                self.stream = None;
+                self.nonce = None;
+                // the server closed the connection before taking the whole
+                // command, so it is safe to send it on a new one
+                if !written && reused && matches!(e, RndcError::ConnectionClosed(_)) {
+                    return self.try_command(command, event);
+                }
+                return Err(e);
+            }
+        };
+
+        if let Ok(nonce) = self.client.get_nonce(&res) {
+            self.nonce = Some(nonce);
+        }
+
+        RndcClient::parse_reply(&res)
+    }
+}
+
+impl Drop for RndcSession {
+    fn drop(&mut self) {
+        let _ = self.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::RndcError;
+    use crate::internal::constants::RndcAlg;
+    use crate::internal::test_server::TestServer;
+
+    #[test]
+    fn test_session_reuses_connection() {
+        let server = TestServer::start(RndcAlg::SHA256, None);
+        let mut session = server.client("sha256").session().unwrap();
+
+        for command in ["status", "reload", "zonestatus example.com"] {
+            let res = session.rndc_command(command).unwrap();
+            assert!(res.result);
+            assert_eq!(res.text.as_deref(), Some(command));
+        }
+
+        assert_eq!(server.connections(), 1);
+        assert_eq!(
+            server.commands(),
+            vec!["status", "reload", "zonestatus example.com"]
+        );
+    }
+
+    #[test]
+    fn test_session_reconnects_after_server_close() {
+        let server = TestServer::start(RndcAlg::SHA256, Some(2));
+        let mut session = server.client("sha256").session().unwrap();
+
+        for sent in 1..=5 {
+            assert!(session.rndc_command("status").unwrap().result);
+            // let the server's close arrive before the next command
+            server.wait_closed(sent / 2);
+        }
+
+        assert_eq!(server.commands().len(), 5);
+        assert_eq!(server.connections(), 3);
+    }
+
+    #[test]
+    fn test_session_does_not_resend_after_lost_reply() {
+        let server = TestServer::start(RndcAlg::SHA256, None);
+        let mut session = server.client("sha256").session().unwrap();
+        assert!(session.rndc_command("status").unwrap().result);
+
+        server.close_before_replies();
+        assert!(matches!(
+            session.rndc_command("addzone example.com {};"),
+            Err(RndcError::ConnectionClosed(_))
+        ));
+        assert!(!session.is_connected());
+        assert_eq!(server.commands(), ["status", "addzone example.com {};"]);
+        assert_eq!(server.connections(), 1);
+    }
+
+    #[test]
+    fn test_session_close_and_reopen() {
+        let server = TestServer::start(RndcAlg::MD5, None);
+        let mut session = server.client("md5").session().unwrap();
+
+        assert!(session.rndc_command("status").unwrap().result);
+        session.close().unwrap();
+        assert!(!session.is_connected());
+
+        assert!(session.rndc_command("status").unwrap().result);
+        assert_eq!(server.connections(), 2);
+    }
+
+    #[test]
+    fn test_client_command_against_test_server() {
+        let server = TestServer::start(RndcAlg::SHA512, None);
+        let res = server.client("sha512").rndc_command("reload").unwrap();
+        assert!(res.result);
+        assert_eq!(res.result_code, Some(0));
+        assert_eq!(res.text.as_deref(), Some("reload"));
+        assert!(res.started > std::time::SystemTime::UNIX_EPOCH);
+    }
+}
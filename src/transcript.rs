@@ -0,0 +1,514 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, ErrorKind, Read, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::error::RndcError;
+use crate::internal::decoder;
+use crate::internal::log_file::LogFile;
+use crate::internal::value::RNDCValueRef;
+use crate::transport::{Connection, Transport};
+
+const HEADER: &str = "# rndc transcript";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// From the client to the server.
+    Sent,
+    /// From the server to the client.
+    Received,
+}
+
+/// One packet of a recorded control channel exchange.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TranscriptEntry {
+    pub time: SystemTime,
+    /// Number of the connection the packet was exchanged on, counted from 0
+    /// in the order they were opened.
+    pub connection: u32,
+    pub direction: Direction,
+    /// The whole packet, including its length and version header.
+    pub packet: Vec<u8>,
+}
+
+impl TranscriptEntry {
+    /// The entry as a transcript line: milliseconds since the Unix epoch,
+    /// connection, `send` or `recv`, and the packet in hex.
+    fn to_line(&self) -> String {
+        let millis = self
+            .time
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or(0);
+        let direction = match self.direction {
+            Direction::Sent => "send",
+            Direction::Received => "recv",
+        };
+        let hex: String = self.packet.iter().map(|b| format!("{:02x}", b)).collect();
+        format!("{} {} {} {}\n", millis, self.connection, direction, hex)
+    }
+}
+
+/// The packets written by a `RecordingTransport`, in the order they were
+/// exchanged.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Transcript {
+    pub entries: Vec<TranscriptEntry>,
+}
+
+impl Transcript {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, RndcError> {
+        let text = std::fs::read_to_string(path.as_ref()).map_err(|e| {
+            RndcError::InvalidArgument(format!(
+                "Failed to read transcript {}: {}",
+                path.as_ref().display(),
+                e
+            ))
+        })?;
+        Self::parse(&text)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, RndcError> {
+        let mut entries = Vec::new();
+
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let err = |what: &str| {
+                RndcError::DecodingError(format!("Transcript line {}: {}", index + 1, what))
+            };
+
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let [millis, connection, direction, hex] = fields[..] else {
+                return Err(err("expected time, connection, direction and packet"));
+            };
+            let millis: u64 = millis.parse().map_err(|_| err("invalid time"))?;
+            let direction = match direction {
+                "send" => Direction::Sent,
+                "recv" => Direction::Received,
+                _ => return Err(err("direction must be send or recv")),
+            };
+
+            entries.push(TranscriptEntry {
+                time: UNIX_EPOCH + Duration::from_millis(millis),
+                connection: connection.parse().map_err(|_| err("invalid connection"))?,
+                direction,
+                packet: decode_hex(hex).ok_or_else(|| err("invalid packet hex"))?,
+            });
+        }
+
+        Ok(Transcript { entries })
+    }
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Remove the complete packets at the front of `buf`.
+fn take_packets(buf: &mut Vec<u8>) -> Vec<Vec<u8>> {
+    let mut packets = Vec::new();
+    while buf.len() >= 4 {
+        let length = u32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]) as usize + 4;
+        if buf.len() < length {
+            break;
+        }
+        packets.push(buf.drain(..length).collect());
+    }
+    packets
+}
+
+/// Wraps another transport and writes every packet sent and received to a
+/// transcript file, which `ReplayTransport` can play back.
+///
+/// Lines are flushed as they are written. A failed write leaves the
+/// exchange itself alone; the first one is kept for `write_error`.
+#[derive(Debug)]
+pub struct RecordingTransport {
+    inner: Arc<dyn Transport>,
+    file: Arc<LogFile>,
+    connections: AtomicU32,
+}
+
+impl RecordingTransport {
+    /// Record the traffic of `inner` to `path`, replacing the file.
+    pub fn create(path: impl AsRef<Path>, inner: Arc<dyn Transport>) -> Result<Self, RndcError> {
+        let mut file = File::create(path.as_ref())
+            .and_then(|mut file| writeln!(file, "{}", HEADER).map(|_| file))
+            .map_err(|e| {
+                RndcError::InvalidArgument(format!(
+                    "Failed to create transcript {}: {}",
+                    path.as_ref().display(),
+                    e
+                ))
+            })?;
+        let _ = file.flush();
+
+        Ok(RecordingTransport {
+            inner,
+            file: Arc::new(LogFile::new(file, "transcript")),
+            connections: AtomicU32::new(0),
+        })
+    }
+
+    /// The first error writing the transcript, if any.
+    pub fn write_error(&self) -> Option<&io::Error> {
+        self.file.error()
+    }
+}
+
+impl Transport for RecordingTransport {
+    fn connect(&self, server: &str) -> io::Result<Box<dyn Connection>> {
+        let inner = self.inner.connect(server)?;
+        Ok(Box::new(RecordingConnection {
+            inner,
+            id: self.connections.fetch_add(1, Ordering::Relaxed),
+            file: self.file.clone(),
+            sent: Vec::new(),
+            received: Vec::new(),
+        }))
+    }
+}
+
+#[derive(Debug)]
+struct RecordingConnection {
+    inner: Box<dyn Connection>,
+    id: u32,
+    file: Arc<LogFile>,
+    /// Bytes of a packet that has not been completely sent yet.
+    sent: Vec<u8>,
+    received: Vec<u8>,
+}
+
+impl RecordingConnection {
+    fn record(&mut self, direction: Direction) {
+        let buf = match direction {
+            Direction::Sent => &mut self.sent,
+            Direction::Received => &mut self.received,
+        };
+        let packets = take_packets(buf);
+        if packets.is_empty() {
+            return;
+        }
+
+        for packet in packets {
+            let entry = TranscriptEntry {
+                time: SystemTime::now(),
+                connection: self.id,
+                direction,
+                packet,
+            };
+            self.file.append(entry.to_line().as_bytes());
+        }
+    }
+}
+
+impl Read for RecordingConnection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.received.extend_from_slice(&buf[..n]);
+        self.record(Direction::Received);
+        Ok(n)
+    }
+}
+
+impl Write for RecordingConnection {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.sent.extend_from_slice(&buf[..n]);
+        self.record(Direction::Sent);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl Connection for RecordingConnection {
+    fn shutdown(&mut self) -> io::Result<()> {
+        self.inner.shutdown()
+    }
+
+    fn peer_closed(&mut self) -> bool {
+        self.inner.peer_closed()
+    }
+}
+
+/// Answers from a recorded transcript instead of connecting, so tests can
+/// reproduce a server's exact responses offline.
+///
+/// Each connection opened plays back the next recorded connection. The
+/// command of every packet the client sends must match the recorded one;
+/// it is then answered with the recorded replies. Signatures, nonces and
+/// timestamps in the client's packets are not compared, since they differ
+/// on every run. Once the recorded replies are used up, the connection
+/// reads as closed.
+#[derive(Debug)]
+pub struct ReplayTransport {
+    connections: Mutex<VecDeque<VecDeque<TranscriptEntry>>>,
+}
+
+impl ReplayTransport {
+    pub fn new(transcript: Transcript) -> Self {
+        let mut ids: Vec<u32> = Vec::new();
+        let mut connections: Vec<VecDeque<TranscriptEntry>> = Vec::new();
+        for entry in transcript.entries {
+            let index = match ids.iter().position(|id| *id == entry.connection) {
+                Some(index) => index,
+                None => {
+                    ids.push(entry.connection);
+                    connections.push(VecDeque::new());
+                    connections.len() - 1
+                }
+            };
+            connections[index].push_back(entry);
+        }
+
+        ReplayTransport {
+            connections: Mutex::new(connections.into()),
+        }
+    }
+
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, RndcError> {
+        Ok(Self::new(Transcript::load(path)?))
+    }
+}
+
+impl Transport for ReplayTransport {
+    fn connect(&self, _server: &str) -> io::Result<Box<dyn Connection>> {
+        let entries = self
+            .connections
+            .lock()
+            .unwrap()
+            .pop_front()
+            .ok_or_else(|| {
+                io::Error::new(
+                    ErrorKind::ConnectionRefused,
+                    "no more connections in the transcript",
+                )
+            })?;
+
+        Ok(Box::new(ReplayConnection {
+            entries,
+            sent: Vec::new(),
+            reply: VecDeque::new(),
+        }))
+    }
+}
+
+#[derive(Debug)]
+struct ReplayConnection {
+    entries: VecDeque<TranscriptEntry>,
+    sent: Vec<u8>,
+    /// The rest of the reply being read.
+    reply: VecDeque<u8>,
+}
+
+impl ReplayConnection {
+    /// Take the next entry if it went in `direction`.
+    fn next_entry(&mut self, direction: Direction) -> Option<TranscriptEntry> {
+        match self.entries.front() {
+            Some(entry) if entry.direction == direction => self.entries.pop_front(),
+            _ => None,
+        }
+    }
+}
+
+/// The `_data.type` of a packet, i.e. the command it carries.
+fn command_of(packet: &[u8]) -> Option<String> {
+    let msg = decoder::decode_borrowed(packet).ok()?;
+    let data = msg.get("_data").and_then(RNDCValueRef::as_table)?;
+    data.get("type")
+        .and_then(RNDCValueRef::as_str)
+        .map(str::to_string)
+}
+
+impl Read for ReplayConnection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.reply.is_empty()
+            && let Some(entry) = self.next_entry(Direction::Received)
+        {
+            self.reply.extend(entry.packet);
+        }
+
+        let n = buf.len().min(self.reply.len());
+        for (dst, src) in buf.iter_mut().zip(self.reply.drain(..n)) {
+            *dst = src;
+        }
+        Ok(n)
+    }
+}
+
+impl Write for ReplayConnection {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.sent.extend_from_slice(buf);
+
+        for packet in take_packets(&mut self.sent) {
+            let sent = command_of(&packet);
+            let expected = self
+                .next_entry(Direction::Sent)
+                .map(|e| command_of(&e.packet));
+            match expected {
+                Some(expected) if expected == sent => {}
+                Some(expected) => {
+                    return Err(io::Error::new(
+                        ErrorKind::InvalidData,
+                        format!(
+                            "transcript expected command {:?}, got {:?}",
+                            expected.unwrap_or_default(),
+                            sent.unwrap_or_default()
+                        ),
+                    ));
+                }
+                None => {
+                    return Err(io::Error::new(
+                        ErrorKind::BrokenPipe,
+                        "no more packets sent on this connection in the transcript",
+                    ));
+                }
+            }
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Connection for ReplayConnection {
+    fn shutdown(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal::constants::RndcAlg;
+    use crate::internal::test_server::{TEST_SECRET_B64, TestServer};
+    use crate::{RndcClient, TcpTransport};
+
+    fn transcript_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("rndc-{}-{}.transcript", std::process::id(), name))
+    }
+
+    /// Record a `status` and a session with `reload` and `flush`.
+    fn record(path: &Path) -> Vec<String> {
+        let server = TestServer::start(RndcAlg::SHA256, None);
+        let recording = RecordingTransport::create(path, Arc::new(TcpTransport)).unwrap();
+        let client = server.client("sha256").with_transport(Arc::new(recording));
+
+        let mut texts = vec![client.rndc_command("status").unwrap().text.unwrap()];
+        let mut session = client.session().unwrap();
+        for command in ["reload example.com", "flush"] {
+            texts.push(session.rndc_command(command).unwrap().text.unwrap());
+        }
+        texts
+    }
+
+    fn replay_client(path: &Path) -> RndcClient {
+        // nothing listens on port 1, so any real connection would fail
+        RndcClient::new("127.0.0.1:1", "sha256", TEST_SECRET_B64)
+            .unwrap()
+            .with_transport(Arc::new(ReplayTransport::open(path).unwrap()))
+    }
+
+    #[test]
+    fn test_record_transcript() {
+        let path = transcript_path("record");
+        record(&path);
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert!(text.starts_with("# rndc transcript\n"));
+
+        let transcript = Transcript::load(&path).unwrap();
+        let summary: Vec<(u32, Direction, Option<String>)> = transcript
+            .entries
+            .iter()
+            .map(|e| (e.connection, e.direction, command_of(&e.packet)))
+            .collect();
+        let entry = |connection, direction, command: &str| {
+            (connection, direction, Some(command.to_string()))
+        };
+        assert_eq!(
+            summary,
+            [
+                entry(0, Direction::Sent, "null"),
+                entry(0, Direction::Received, "null"),
+                entry(0, Direction::Sent, "status"),
+                entry(0, Direction::Received, "status"),
+                entry(1, Direction::Sent, "null"),
+                entry(1, Direction::Received, "null"),
+                entry(1, Direction::Sent, "reload example.com"),
+                entry(1, Direction::Received, "reload example.com"),
+                entry(1, Direction::Sent, "flush"),
+                entry(1, Direction::Received, "flush"),
+            ]
+        );
+        assert!(transcript.entries[0].time <= SystemTime::now());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_replay_transcript() {
+        let path = transcript_path("replay");
+        let recorded = record(&path);
+
+        let client = replay_client(&path);
+        let mut texts = vec![client.rndc_command("status").unwrap().text.unwrap()];
+        let mut session = client.session().unwrap();
+        for command in ["reload example.com", "flush"] {
+            texts.push(session.rndc_command(command).unwrap().text.unwrap());
+        }
+        assert_eq!(texts, recorded);
+
+        // every recorded connection has been played back
+        assert!(matches!(
+            client.rndc_command("status"),
+            Err(RndcError::NetworkError(_))
+        ));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_replay_rejects_other_commands() {
+        let path = transcript_path("mismatch");
+        record(&path);
+
+        let client = replay_client(&path);
+        let err = client.rndc_command("stop").unwrap_err();
+        assert!(err.to_string().contains("expected command \"status\""));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_parse_transcript_errors() {
+        assert!(
+            Transcript::parse("# only a comment\n")
+                .unwrap()
+                .entries
+                .is_empty()
+        );
+        assert!(Transcript::parse("1 0 send 0").is_err());
+        assert!(Transcript::parse("1 0 sideways 00").is_err());
+        assert!(Transcript::parse("1 0 send").is_err());
+        assert!(Transcript::parse("x 0 send 00").is_err());
+    }
+}
@@ -0,0 +1,62 @@
+use std::fmt;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{Shutdown, TcpStream};
+use std::sync::Arc;
+
+use crate::RndcClient;
+
+/// A byte stream to a control channel server.
+pub trait Connection: Read + Write + Send + fmt::Debug {
+    /// Close both directions of the connection.
+    fn shutdown(&mut self) -> io::Result<()>;
+
+    /// Whether the server has closed the connection, or sent something no
+    /// command asked for, checked without blocking before an idle
+    /// connection is reused. Connections that cannot tell return `false`.
+    fn peer_closed(&mut self) -> bool {
+        false
+    }
+}
+
+impl Connection for TcpStream {
+    fn shutdown(&mut self) -> io::Result<()> {
+        TcpStream::shutdown(self, Shutdown::Both)
+    }
+
+    fn peer_closed(&mut self) -> bool {
+        if self.set_nonblocking(true).is_err() {
+            return false;
+        }
+        let res = self.peek(&mut [0u8; 1]);
+        let _ = self.set_nonblocking(false);
+        !matches!(res, Err(e) if e.kind() == ErrorKind::WouldBlock)
+    }
+}
+
+/// Opens the connections a client sends its commands over.
+///
+/// The default is `TcpTransport`. Wrappers like `RecordingTransport` can
+/// observe the traffic of another transport, and `ReplayTransport` answers
+/// from a recorded transcript without any server.
+pub trait Transport: Send + Sync + fmt::Debug {
+    fn connect(&self, server: &str) -> io::Result<Box<dyn Connection>>;
+}
+
+/// Plain TCP connections to the server address.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TcpTransport;
+
+impl Transport for TcpTransport {
+    fn connect(&self, server: &str) -> io::Result<Box<dyn Connection>> {
+        Ok(Box::new(TcpStream::connect(server)?))
+    }
+}
+
+impl RndcClient {
+    /// Open connections through `transport` instead of plain TCP. Sessions
+    /// and pools created from the client use it as well.
+    pub fn with_transport(mut self, transport: Arc<dyn Transport>) -> Self {
+        self.transport = transport;
+        self
+    }
+}
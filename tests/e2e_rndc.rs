@@ -38,3 +38,51 @@ fn e2e_rndc_reload() {
         "rndc reload response text is missing or incorrect"
     );
 }
+
+#[test]
+#[ignore]
+fn e2e_rndc_flush_name() {
+    let client = get_test_client();
+    let response = client.flush_name("example.com", None).unwrap();
+
+    assert!(response.result, "rndc command failed: {:?}", &response);
+}
+
+#[test]
+#[ignore]
+fn e2e_rndc_tsig_list() {
+    let client = get_test_client();
+    let keys = client.tsig_list().unwrap();
+
+    assert!(
+        keys.iter().any(|k| k.name == "rndc-key"),
+        "rndc-key missing from tsig-list: {:?}",
+        keys
+    );
+}
+
+#[test]
+#[ignore]
+fn e2e_rndc_querylog_guard_restores_state() {
+    let client = get_test_client();
+    let before = client.status().unwrap().query_logging;
+
+    {
+        let _guard = client.querylog_guard(true).unwrap();
+        assert_eq!(client.status().unwrap().query_logging, Some(true));
+    }
+
+    assert_eq!(client.status().unwrap().query_logging, before);
+}
+
+#[test]
+#[ignore]
+fn e2e_rndc_session() {
+    let client = get_test_client();
+    let mut session = client.session().unwrap();
+
+    for _ in 0..3 {
+        let response = session.rndc_command("status").unwrap();
+        assert!(response.result, "rndc command failed: {:?}", &response);
+    }
+}
@@ -0,0 +1,16 @@
+use rndc::{EnvKeyProvider, KeyProvider};
+
+#[test]
+fn env_provider() {
+    let var = "RNDC_TEST_ENV_PROVIDER_SECRET";
+    let provider = EnvKeyProvider::new("rndc-key", "sha256", var).unwrap();
+    assert!(provider.key().is_err());
+
+    // SAFETY: this binary runs no other test, so nothing else touches the
+    // environment concurrently
+    unsafe { std::env::set_var(var, "YmluZGl6cg==") };
+    assert_eq!(provider.key().unwrap().secret, b"bindizr");
+    unsafe { std::env::remove_var(var) };
+
+    assert!(EnvKeyProvider::new("rndc-key", "rot13", var).is_err());
+}